@@ -0,0 +1,115 @@
+use game_state;
+
+//games packed for bulk storage, e.g. the positions self play generates for training.
+//a game is a 4 byte header followed by its moves, 3 bits per column, least significant bits first:
+//byte 0 is the width in the high 4 bits and the height in the low 4, byte 1 the result
+//(0 unfinished, 1 white won, 2 black won, 3 tie) and bytes 2 and 3 the number of moves, little endian.
+//only drops are stored, so pop out games can not be archived
+
+const HEADER : usize = 4;
+const BITS_PER_MOVE : usize = 3;
+
+fn result_code(result : game_state::End) -> u8{
+    match result{
+        game_state::End::Ongoing => 0,
+        game_state::End::Victory(game_state::Color::White) => 1,
+        game_state::End::Victory(game_state::Color::Black) => 2,
+        game_state::End::Tie => 3
+    }
+}
+
+fn result_from_code(code : u8) -> Result<game_state::End, String>{
+    match code{
+        0 => Ok(game_state::End::Ongoing),
+        1 => Ok(game_state::End::Victory(game_state::Color::White)),
+        2 => Ok(game_state::End::Victory(game_state::Color::Black)),
+        3 => Ok(game_state::End::Tie),
+        _ => Err(format!("unknown result {}", code))
+    }
+}
+
+pub fn encode_game(moves : &[usize], result : game_state::End) -> Vec<u8>{
+    //a game on the standard board, as the columns of its moves from the start
+    let standard = game_state::GameState::new();
+    encode_game_sized(standard.width(), standard.height(), moves, result)
+}
+
+pub fn encode_game_sized(width : usize, height : usize, moves : &[usize], result : game_state::End) -> Vec<u8>{
+    //every column has to fit in 3 bits, which every board up to game_state::MAX_WIDTH does,
+    //and a game has at most u16::MAX moves
+    debug_assert!(width <= game_state::MAX_WIDTH && height <= game_state::MAX_HEIGHT);
+    debug_assert!(moves.len() <= u16::MAX as usize);
+    let mut bytes = vec![0u8; HEADER + (moves.len() * BITS_PER_MOVE).div_ceil(8)];
+    bytes[0] = ((width as u8) << 4) | height as u8;
+    bytes[1] = result_code(result);
+    bytes[2..HEADER].copy_from_slice(&(moves.len() as u16).to_le_bytes());
+    for (i, &column) in moves.iter().enumerate(){
+        debug_assert!(column < 1 << BITS_PER_MOVE);
+        //a move can straddle two bytes
+        let bits = (column as u16 & 0b111) << ((i * BITS_PER_MOVE) % 8);
+        let at = HEADER + i * BITS_PER_MOVE / 8;
+        bytes[at] |= bits as u8;
+        if bits > 0xff{
+            bytes[at + 1] |= (bits >> 8) as u8;
+        }
+    }
+    bytes
+}
+
+pub fn decode_game(bytes : &[u8]) -> Result<(Vec<usize>, game_state::End), String>{
+    //the moves and result encode_game stored, whatever the board size in the header
+    decode_game_sized(bytes).map(|(_, _, moves, result)| (moves, result))
+}
+
+pub fn decode_game_sized(bytes : &[u8]) -> Result<(usize, usize, Vec<usize>, game_state::End), String>{
+    //(width, height, moves, result) of a game from encode_game_sized
+    if bytes.len() < HEADER{
+        return Err(format!("a game needs a {} byte header, got {} bytes", HEADER, bytes.len()));
+    }
+    let (width, height) = ((bytes[0] >> 4) as usize, (bytes[0] & 0xf) as usize);
+    let result = result_from_code(bytes[1])?;
+    let count = u16::from_le_bytes([bytes[2], bytes[3]]) as usize;
+    let expected = HEADER + (count * BITS_PER_MOVE).div_ceil(8);
+    if bytes.len() != expected{
+        return Err(format!("{} moves take {} bytes, got {}", count, expected, bytes.len()));
+    }
+    let moves = (0..count).map(|i|{
+        let at = HEADER + i * BITS_PER_MOVE / 8;
+        let pair = bytes[at] as u16 | (*bytes.get(at + 1).unwrap_or(&0) as u16) << 8;
+        ((pair >> ((i * BITS_PER_MOVE) % 8)) & 0b111) as usize
+    }).collect();
+    Ok((width, height, moves, result))
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn games_round_trip(){
+        let white = game_state::End::Victory(game_state::Color::White);
+        let games : Vec<(Vec<usize>, game_state::End)> = vec![
+            (vec![], game_state::End::Ongoing),
+            (vec![3, 3, 2, 4, 1, 5, 0], white),
+            ((0..42).map(|i| (i * 5 + i / 7) % 8).collect(), game_state::End::Tie),
+            (vec![7; 9], game_state::End::Victory(game_state::Color::Black))
+        ];
+        for (moves, result) in games{
+            let bytes = encode_game(&moves, result);
+            assert_eq!(bytes.len(), 4 + (moves.len() * 3).div_ceil(8));
+            assert_eq!(decode_game(&bytes), Ok((moves.clone(), result)));
+        }
+        let bytes = encode_game_sized(5, 4, &[4, 0, 2], white);
+        assert_eq!(decode_game_sized(&bytes), Ok((5, 4, vec![4, 0, 2], white)));
+    }
+
+    #[test]
+    fn rejects_damaged_games(){
+        let bytes = encode_game(&[3, 3, 2], game_state::End::Ongoing);
+        assert!(decode_game(&bytes[..3]).is_err());
+        assert!(decode_game(&bytes[..bytes.len() - 1]).is_err());
+        let mut bad_result = bytes.clone();
+        bad_result[1] = 9;
+        assert!(decode_game(&bad_result).is_err());
+    }
+}
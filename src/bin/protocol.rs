@@ -0,0 +1,6 @@
+extern crate connect_three;
+
+//the engine protocol on its own, the same as connect_three --engine, see connect_three::protocol
+fn main(){
+    connect_three::protocol::run();
+}
@@ -0,0 +1,146 @@
+use game_state;
+use game_state::{has_four, STRIDE};
+use rand::Rng;
+
+//a standard position as one bit mask per player, the masks GameState keeps laid out as game_state::STRIDE describes,
+//without the move count and last drop it has to keep up to date, for the rollouts that dominate a search
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Bitboard{
+    //white's pieces, then black's
+    pieces : [u64; 2],
+    heights : [usize; game_state::MAX_WIDTH],
+    width : usize,
+    height : usize,
+    player : game_state::Color
+}
+
+fn side(color : game_state::Color) -> usize{
+    match color{
+        game_state::Color::White => 0,
+        game_state::Color::Black => 1
+    }
+}
+
+impl Bitboard{
+    pub fn from_state(state : &game_state::GameState) -> Option<Bitboard>{
+        //None for pop out positions, which the bitboard has no pops for
+        if state.variant() != game_state::Variant::Standard{
+            return None;
+        }
+        let mut heights = [0; game_state::MAX_WIDTH];
+        for (column, height) in heights.iter_mut().enumerate().take(state.width()){
+            *height = state.column_height(column);
+        }
+        Some(Bitboard{ pieces : [state.pieces(game_state::Color::White), state.pieces(game_state::Color::Black)], heights,
+            width : state.width(), height : state.height(), player : state.player })
+    }
+
+    pub fn to_state(&self) -> game_state::GameState{
+        let grid = (0..self.height).rev().map(|row| (0..self.width).map(|column|{
+            let bit = 1 << (column * STRIDE + row);
+            if self.pieces[0] & bit != 0 { Some(game_state::Color::White) }
+            else if self.pieces[1] & bit != 0 { Some(game_state::Color::Black) }
+            else { None }
+        }).collect::<Vec<_>>()).collect::<Vec<_>>();
+        game_state::GameState::from_grid(&grid, self.player).expect("a bitboard only stacks pieces on the board")
+    }
+
+    pub fn player(&self) -> game_state::Color{
+        self.player
+    }
+
+    pub fn can_drop(&self, column : usize) -> bool{
+        column < self.width && self.heights[column] < self.height
+    }
+
+    pub fn drop(&mut self, column : usize){
+        //the player to move drops a piece in column, which has to have room
+        debug_assert!(self.can_drop(column));
+        self.pieces[side(self.player)] |= 1 << (column * STRIDE + self.heights[column]);
+        self.heights[column] += 1;
+        self.player = self.player.opponent();
+    }
+
+    pub fn is_win_for(&self, color : game_state::Color) -> bool{
+        has_four(self.pieces[side(color)])
+    }
+
+    pub fn is_full(&self) -> bool{
+        self.heights[..self.width].iter().all(|&height| height == self.height)
+    }
+
+    pub fn win(&self) -> game_state::End{
+        //as GameState::win, a drop can not give both players four in a row
+        match (self.is_win_for(game_state::Color::White), self.is_win_for(game_state::Color::Black)){
+            (true, _) => game_state::End::Victory(game_state::Color::White),
+            (_, true) => game_state::End::Victory(game_state::Color::Black),
+            _ if self.is_full() => game_state::End::Tie,
+            _ => game_state::End::Ongoing
+        }
+    }
+
+    pub fn rollout<R : Rng>(self, rng : &mut R) -> game_state::End{
+        //a uniformly random playout to the end of the game. the legal columns are drawn from left to right
+        //with one gen_range each, as monte_carlo::run_simulation_with does, so the same rng plays the same game
+        let mut board = self;
+        let result = board.win();
+        if result.is_over(){
+            return result;
+        }
+        let mut columns = [0usize; game_state::MAX_WIDTH];
+        loop{
+            let mut count = 0;
+            for column in (0..board.width).filter(|&column| board.can_drop(column)){
+                columns[count] = column;
+                count += 1;
+            }
+            if count == 0{
+                return game_state::End::Tie;
+            }
+            let mover = board.player;
+            board.drop(columns[rng.gen_range(0, count)]);
+            if board.is_win_for(mover){
+                return game_state::End::Victory(mover);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    fn play(columns : &[usize]) -> game_state::GameState{
+        columns.iter().fold(game_state::GameState::new(), |state, &x| state.place(&game_state::Move::new(x, state.player)))
+    }
+
+    #[test]
+    fn bitboards_follow_the_game(){
+        let positions = [play(&[]), play(&[3, 3, 2]), play(&[0, 6, 1, 6, 2, 6, 3]), play(&[0, 1, 0, 1, 0, 1, 0])];
+        for state in positions.iter(){
+            let board = Bitboard::from_state(state).unwrap();
+            assert_eq!(board.to_state(), *state);
+            assert_eq!(board.win(), state.win());
+            for column in 0..7{
+                assert_eq!(board.can_drop(column), state.legal(&game_state::Move::new(column, state.player)));
+            }
+        }
+        assert!(has_four(0b1111) && has_four(1 | 1 << STRIDE | 1 << (2 * STRIDE) | 1 << (3 * STRIDE)));
+        //the top two cells of a 7 high column and the bottom two of the next are not a line
+        assert!(!has_four(0b11 << 5 | 0b11 << STRIDE));
+        assert!(Bitboard::from_state(&game_state::GameState::with_variant(game_state::Variant::PopOut)).is_none());
+    }
+
+    #[test]
+    fn rollouts_match_the_game_state_ones(){
+        let mut rng = StdRng::from_seed(&[506]);
+        for _ in 0..50{
+            let seed = rng.gen::<usize>();
+            let state = play(&[3, 2, 4]);
+            let fast = Bitboard::from_state(&state).unwrap().rollout(&mut StdRng::from_seed(&[seed]));
+            let checked = ::monte_carlo::RolloutOptions{ validate : true, ..Default::default() };
+            assert_eq!(fast, ::monte_carlo::run_simulation_with(state, state.player, checked, &mut StdRng::from_seed(&[seed])));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use game_state;
+use monte_carlo;
+use std::collections::HashMap;
+
+//how much a threat of the right parity is worth, the evaluation saturates after a few of them
+const PARITY_WEIGHT : f64 = 0.4;
+//a threat that can be completed on the very next move is worth far more
+const THREAT_WEIGHT : f64 = 1f64;
+
+pub fn good_parity(state : &game_state::GameState, row : usize, color : game_state::Color) -> bool{
+    //counting rows from 1 at the bottom, the first player profits from threats on odd rows and the second
+    //player from threats on even rows: with the rest of the board filled in turn, zugzwang hands them that square
+    let odd = (state.height() - row) % 2 == 1;
+    odd == (color == game_state::Color::White)
+}
+
+pub fn parity_threats(state : &game_state::GameState, color : game_state::Color) -> usize{
+    state.threats(color).iter().filter(|&&(_, row)| good_parity(state, row, color)).count()
+}
+
+pub fn parity(state : &game_state::GameState, color : game_state::Color) -> i32{
+    //color's well placed threats minus the opponent's
+    parity_threats(state, color) as i32 - parity_threats(state, color.opponent()) as i32
+}
+
+pub fn playable(state : &game_state::GameState, column : usize, row : usize) -> bool{
+    //a piece dropped in column lands on row
+    state.cell(column, row).is_none() && (row + 1 == state.height() || state.cell(column, row + 1).is_some())
+}
+
+pub fn count_threats(state : &game_state::GameState, color : game_state::Color) -> usize{
+    //cells that would complete four for color and can be played into right now
+    state.threats(color).iter().filter(|&&(column, row)| playable(state, column, row)).count()
+}
+
+pub fn creates_fork(state : &game_state::GameState, mv : &game_state::Move, color : game_state::Color) -> bool{
+    //after mv, color threatens to win in two places the opponent can not both block with one move:
+    //two playable threats in different columns, or a playable threat with another one right above it,
+    //which blocking the first only makes playable. a move that wins outright is not a fork.
+    //whether the opponent could win first instead of blocking is left to the search
+    if state.is_terminal() || !state.legal(mv){
+        return false;
+    }
+    let after = state.place(mv);
+    if after.is_terminal(){
+        return false;
+    }
+    let threats = after.threats(color);
+    let mut columns = threats.iter().filter(|&&(column, row)| playable(&after, column, row)).map(|&(column, _)| column).collect::<Vec<_>>();
+    columns.dedup();
+    let stacked = threats.iter().any(|&(column, row)| playable(&after, column, row) && row > 0 && threats.contains(&(column, row - 1)));
+    columns.len() >= 2 || stacked
+}
+
+pub fn evaluate(state : &game_state::GameState, color : game_state::Color) -> f64{
+    //a guess at the reward color can expect, on the same 0 to 1 scale as a rollout result
+    if state.is_terminal(){
+        return monte_carlo::get_result_value(state.win(), color);
+    }
+    let threats = count_threats(state, color) as i32 - count_threats(state, color.opponent()) as i32;
+    let score = PARITY_WEIGHT * parity(state, color) as f64 + THREAT_WEIGHT * threats as f64;
+    0.5f64 + 0.5f64 * score.tanh()
+}
+
+//remembers evaluations by zobrist key, so positions the search reaches again are not evaluated twice.
+//two positions whose keys collide share an evaluation, which at 64 bits is rare enough to ignore
+pub struct EvalCache{
+    entries : HashMap<u64, f64>,
+    //the most positions kept at once, 0 keeps none. a full cache starts over empty rather than
+    //choosing what to forget
+    capacity : usize,
+    pub hits : u64,
+    pub misses : u64
+}
+
+impl EvalCache{
+    pub fn new(capacity : usize) -> Self{
+        EvalCache{ entries : HashMap::new(), capacity, hits : 0, misses : 0 }
+    }
+
+    pub fn len(&self) -> usize{
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self){
+        self.entries.clear();
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    pub fn evaluate(&mut self, state : &game_state::GameState, color : game_state::Color) -> f64{
+        //the same as evaluate. only white's value is stored, black's is its mirror image
+        let key = state.zobrist();
+        let white = match self.entries.get(&key){
+            Some(&value) => {
+                self.hits += 1;
+                value
+            },
+            None => {
+                self.misses += 1;
+                let value = evaluate(state, game_state::Color::White);
+                if self.capacity > 0{
+                    if self.entries.len() >= self.capacity{
+                        self.entries.clear();
+                    }
+                    self.entries.insert(key, value);
+                }
+                value
+            }
+        };
+        match color{
+            game_state::Color::White => white,
+            game_state::Color::Black => 1f64 - white
+        }
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn play(moves : &[usize]) -> game_state::GameState{
+        moves.iter().fold(game_state::GameState::new(), |state, &x| state.place(&game_state::Move::new(x, state.player)))
+    }
+
+    #[test]
+    fn parity_of_rows(){
+        let state = game_state::GameState::new();
+        //row 5 is the bottom row, the first from the bottom
+        assert!(good_parity(&state, 5, game_state::Color::White));
+        assert!(!good_parity(&state, 5, game_state::Color::Black));
+        assert!(good_parity(&state, 4, game_state::Color::Black));
+        assert!(good_parity(&state, 0, game_state::Color::Black));
+    }
+
+    #[test]
+    fn rewards_threats_of_the_right_parity(){
+        //white's three on the bottom row threatens two odd cells, black's three above it two even cells
+        let state = play(&[1, 1, 2, 2, 3, 3]);
+        assert_eq!(parity_threats(&state, game_state::Color::White), 2);
+        assert_eq!(parity_threats(&state, game_state::Color::Black), 2);
+        assert_eq!(parity(&state, game_state::Color::White), 0);
+
+        //whatever the position, the two sides see mirror images of the same evaluation
+        let state = play(&[1, 1, 2, 2, 0, 3, 6, 6]);
+        assert_eq!(parity(&state, game_state::Color::White), -parity(&state, game_state::Color::Black));
+        let white = evaluate(&state, game_state::Color::White);
+        let black = evaluate(&state, game_state::Color::Black);
+        assert!((white + black - 1f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn an_odd_threat_favours_white(){
+        //a white three in a row on row 3 from the bottom, over a mixed foundation that gives black nothing
+        let mut state = game_state::GameState::new();
+        for &(x, color) in [(0, game_state::Color::White), (1, game_state::Color::Black), (2, game_state::Color::White),
+            (0, game_state::Color::Black), (1, game_state::Color::White), (2, game_state::Color::Black),
+            (0, game_state::Color::White), (1, game_state::Color::White), (2, game_state::Color::White)].iter(){
+            state = state.place(&game_state::Move::new(x, color));
+        }
+        //the diagonal also threatens row 4, which is the wrong parity for white and not counted
+        assert_eq!(state.threats(game_state::Color::White), vec![(3, 2), (3, 3)]);
+        assert_eq!(parity_threats(&state, game_state::Color::White), 1);
+        assert_eq!(parity_threats(&state, game_state::Color::Black), 0);
+        assert!(evaluate(&state, game_state::Color::White) > 0.5f64);
+        assert!(evaluate(&state, game_state::Color::Black) < 0.5f64);
+    }
+
+    #[test]
+    fn terminal_positions_are_exact(){
+        let won = play(&[0, 6, 1, 6, 2, 6, 3]);
+        assert_eq!(evaluate(&won, game_state::Color::White), 1f64);
+        assert_eq!(evaluate(&won, game_state::Color::Black), 0f64);
+    }
+
+    #[test]
+    fn counts_only_playable_threats(){
+        //white's open three on the bottom row can be completed at either end
+        let state = play(&[1, 1, 2, 2, 3, 3]);
+        assert_eq!(count_threats(&state, game_state::Color::White), 2);
+        //black's cells on the second row have nothing under them yet
+        assert_eq!(count_threats(&state, game_state::Color::Black), 0);
+        assert_eq!(state.threats(game_state::Color::Black).len(), 2);
+        assert!(evaluate(&state, game_state::Color::White) > 0.9f64);
+        assert_eq!(count_threats(&game_state::GameState::new(), game_state::Color::White), 0);
+    }
+
+    #[test]
+    fn playable_cells_sit_on_top(){
+        let state = play(&[3]);
+        assert!(playable(&state, 3, 4));
+        assert!(!playable(&state, 3, 5));
+        assert!(!playable(&state, 3, 3));
+        assert!(playable(&state, 0, 5));
+    }
+
+    #[test]
+    fn the_cache_agrees_with_evaluate(){
+        let mut cache = EvalCache::new(16);
+        let state = play(&[1, 1, 2, 2, 0, 3, 6, 6]);
+        for &color in [game_state::Color::White, game_state::Color::Black].iter(){
+            assert!((cache.evaluate(&state, color) - evaluate(&state, color)).abs() < 1e-12);
+        }
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+        //a transposition is a hit too
+        let won = play(&[0, 6, 1, 6, 2, 6, 3]);
+        assert_eq!(cache.evaluate(&won, game_state::Color::Black), 0f64);
+        assert_eq!(cache.evaluate(&play(&[2, 6, 1, 6, 0, 6, 3]), game_state::Color::White), 1f64);
+        assert_eq!((cache.hits, cache.misses, cache.len()), (2, 2, 2));
+    }
+
+    #[test]
+    fn the_cache_stays_within_its_capacity(){
+        let mut cache = EvalCache::new(2);
+        for column in 0..5{
+            cache.evaluate(&play(&[column]), game_state::Color::White);
+            assert!(cache.len() <= 2);
+        }
+        assert_eq!(cache.misses, 5);
+        let mut off = EvalCache::new(0);
+        off.evaluate(&play(&[3]), game_state::Color::White);
+        off.evaluate(&play(&[3]), game_state::Color::White);
+        assert!(off.is_empty());
+        assert_eq!(off.hits, 0);
+    }
+
+    #[test]
+    fn finds_forks(){
+        //white's third piece on the bottom row leaves both ends open
+        let state = play(&[1, 1, 2, 2]);
+        assert!(creates_fork(&state, &game_state::Move::new(3, game_state::Color::White), game_state::Color::White));
+        assert!(!creates_fork(&state, &game_state::Move::new(0, game_state::Color::White), game_state::Color::White));
+        //one open end is a threat, not a fork
+        let state = play(&[0, 0, 1, 1]);
+        assert!(!creates_fork(&state, &game_state::Move::new(2, game_state::Color::White), game_state::Color::White));
+        //a move that wins on the spot is no fork, and neither is one that can not be played
+        let state = play(&[1, 1, 2, 2, 3, 3]);
+        assert!(!creates_fork(&state, &game_state::Move::new(4, game_state::Color::White), game_state::Color::White));
+        assert!(!creates_fork(&state, &game_state::Move::new(7, game_state::Color::White), game_state::Color::White));
+    }
+
+    #[test]
+    fn stacked_threats_are_forks(){
+        //dropping in column 1 makes three on the bottom row, open only at column 4, and the cell above that
+        //completes four on the second row. blocking the first threat sets up the second
+        let state = play(&[3, 5, 5, 5, 2, 5, 2, 0, 3, 0]);
+        let mv = game_state::Move::new(1, game_state::Color::White);
+        assert!(creates_fork(&state, &mv, game_state::Color::White));
+        let after = state.place(&mv);
+        assert_eq!(count_threats(&after, game_state::Color::White), 1);
+        assert_eq!(after.threats(game_state::Color::White), vec![(4, 4), (4, 5)]);
+    }
+}
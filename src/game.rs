@@ -0,0 +1,184 @@
+use game_state;
+use monte_carlo;
+use bitboard;
+use evaluation;
+use rand::Rng;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+//the rules monte_carlo's search needs to know, so selection, rollouts and backpropagation run on any two player game
+//where the players take turns, e.g. tic-tac-toe or gomoku on a small board as well as connect four.
+//the players are game_state::Color and a game's result is a game_state::End, whatever the game calls its sides.
+//the methods with a body are there for the search options built around connect four, another game can leave them be
+pub trait Game : Copy + Eq + Hash + Debug{
+    type Move : Copy + Eq + Hash + Debug;
+
+    fn current_player(&self) -> game_state::Color;
+
+    //the moves of the player to move, in the same order every time so a seeded search picks the same moves on every run
+    fn legal_moves(&self) -> Vec<Self::Move>;
+
+    fn place(&self, mv : &Self::Move) -> Self;
+
+    //who has won, a tie, or Ongoing while the game goes on
+    fn win(&self) -> game_state::End;
+
+    //how many moves a rollout from here plays at most before it is called a tie, see RolloutOptions::move_cap
+    fn move_cap(&self) -> usize;
+
+    fn legal(&self, mv : &Self::Move) -> bool{
+        self.legal_moves().contains(mv)
+    }
+
+    fn legal_moves_into(&self, moves : &mut Vec<Self::Move>){
+        //legal_moves written over the contents of moves, a game can keep reusing the buffer
+        *moves = self.legal_moves();
+    }
+
+    fn place_mut(&mut self, mv : &Self::Move){
+        *self = self.place(mv);
+    }
+
+    fn is_terminal(&self) -> bool{
+        self.win().is_over()
+    }
+
+    fn previous_player(&self) -> game_state::Color{
+        self.current_player().opponent()
+    }
+
+    fn immediate_wins(&self) -> Vec<Self::Move>{
+        //the moves that win on the spot for the player to move
+        let player = self.current_player();
+        self.legal_moves().into_iter().filter(|mv| self.place(mv).win().is_win_for(player)).collect()
+    }
+
+    fn ended_by(&self, _last_move : &Self::Move) -> bool{
+        //whether the game is over now that last_move made this position, a game can look at the move alone
+        self.is_terminal()
+    }
+
+    fn canonical(&self) -> Self{
+        //the one position standing in for this one and its symmetric images, see MctsConfig.mirror_keys
+        *self
+    }
+
+    fn fold_symmetric(&self, moves : Vec<Self::Move>) -> Vec<Self::Move>{
+        //moves, less those a symmetry of this position makes the same as another of them, see MctsConfig.fold_symmetry
+        moves
+    }
+
+    fn fast_rollout<R : Rng>(&self, _rng : &mut R) -> Option<game_state::End>{
+        //a uniformly random rollout played some faster way than move by move, None when there is none
+        None
+    }
+
+    fn evaluate(&self, _player : game_state::Color) -> Option<f64>{
+        //a heuristic value of this position for player between 0 and 1, see MctsConfig.lambda
+        None
+    }
+
+    fn rollout_move(&self, _policy : monte_carlo::RolloutPolicy, moves : &[Self::Move], u : f64) -> Self::Move{
+        //which of moves a rollout under policy plays, u is a number in [0, 1]. without a policy of its own a game
+        //picks uniformly
+        moves[((u * moves.len() as f64) as usize).min(moves.len() - 1)]
+    }
+
+    fn expansion_move<R : Rng>(&self, _order : monte_carlo::ExpansionOrder, moves : &[Self::Move], rng : &mut R) -> Self::Move{
+        //which of moves, the unexplored children of this position, selection opens first
+        monte_carlo::choose_random_with(moves, rng)
+    }
+
+    fn print(&self) -> String{
+        format!("{:?}", self)
+    }
+}
+
+//how many times a full board's worth of moves a pop out rollout may play by default
+const POP_OUT_ROLLOUT_BOARDS : usize = 4;
+
+impl Game for game_state::GameState{
+    type Move = game_state::Move;
+
+    fn current_player(&self) -> game_state::Color{
+        self.player
+    }
+
+    fn legal_moves(&self) -> Vec<game_state::Move>{
+        game_state::GameState::legal_moves(self)
+    }
+
+    fn place(&self, mv : &game_state::Move) -> Self{
+        game_state::GameState::place(self, mv)
+    }
+
+    fn win(&self) -> game_state::End{
+        game_state::GameState::win(self)
+    }
+
+    fn move_cap(&self) -> usize{
+        //a standard game can not outlast its cells, while pop out games can go on for as long as
+        //pieces are popped, so they get a few boards' worth of moves
+        match self.variant(){
+            game_state::Variant::Standard => self.width() * self.height(),
+            game_state::Variant::PopOut => POP_OUT_ROLLOUT_BOARDS * self.width() * self.height()
+        }
+    }
+
+    fn legal(&self, mv : &game_state::Move) -> bool{
+        //GameState::legal takes either player's moves, only the player to move's can come next
+        game_state::GameState::legal(self, mv) && mv.color == self.player
+    }
+
+    fn legal_moves_into(&self, moves : &mut Vec<game_state::Move>){
+        game_state::GameState::legal_moves_into(self, moves)
+    }
+
+    fn place_mut(&mut self, mv : &game_state::Move){
+        game_state::GameState::place_mut(self, mv)
+    }
+
+    fn is_terminal(&self) -> bool{
+        game_state::GameState::is_terminal(self)
+    }
+
+    fn immediate_wins(&self) -> Vec<game_state::Move>{
+        self.winning_moves(self.player)
+    }
+
+    fn ended_by(&self, last_move : &game_state::Move) -> bool{
+        //a drop can only win through its own cell or fill the board, so the rollouts only scan the whole board after pops
+        match last_move.kind{
+            game_state::MoveKind::Drop => self.last_move_won() || (self.is_full() && game_state::GameState::legal_moves(self).is_empty()),
+            game_state::MoveKind::Pop => game_state::GameState::is_terminal(self)
+        }
+    }
+
+    fn canonical(&self) -> Self{
+        game_state::GameState::canonical(self)
+    }
+
+    fn fold_symmetric(&self, moves : Vec<game_state::Move>) -> Vec<game_state::Move>{
+        monte_carlo::fold_symmetric(self, moves)
+    }
+
+    fn fast_rollout<R : Rng>(&self, rng : &mut R) -> Option<game_state::End>{
+        bitboard::Bitboard::from_state(self).map(|board| board.rollout(rng))
+    }
+
+    fn evaluate(&self, player : game_state::Color) -> Option<f64>{
+        Some(evaluation::evaluate(self, player))
+    }
+
+    fn rollout_move(&self, policy : monte_carlo::RolloutPolicy, moves : &[game_state::Move], u : f64) -> game_state::Move{
+        policy.choose(self, moves, u)
+    }
+
+    fn expansion_move<R : Rng>(&self, order : monte_carlo::ExpansionOrder, moves : &[game_state::Move], rng : &mut R) -> game_state::Move{
+        order.choose(self, moves, rng)
+    }
+
+    fn print(&self) -> String{
+        game_state::GameState::print(self)
+    }
+}
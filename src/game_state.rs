@@ -1,23 +1,195 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::hash::{Hash, Hasher};
+use serde::{Serialize, Deserialize};
+
 const BOARD_WIDTH : usize = 7;
 const BOARD_HEIGHT : usize = 6;
+//the largest board with_size accepts. a column plus one spare cell for every column still fits in 64 bits
+pub const MAX_WIDTH : usize = 8;
+pub const MAX_HEIGHT : usize = 7;
+//a player's pieces are one bit mask, column c, row r counted from the bottom, is bit c * STRIDE + r. every column
+//keeps a spare row on top that is never set, so a line shifted from one column into the next can not wrap around
+//into a false four in a row
+pub const STRIDE : usize = MAX_HEIGHT + 1;
+
+pub fn has_four(pieces : u64) -> bool{
+    //vertical, horizontal and the two diagonals: a pair of neighbours along the direction, then a pair of pairs
+    [1, STRIDE, STRIDE + 1, STRIDE - 1].iter().any(|&step|{
+        let pairs = pieces & (pieces >> step);
+        pairs & (pairs >> (2 * step)) != 0
+    })
+}
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+//GameState is the key of every map in the search, so Eq/Hash define which positions are "the same"
+//two states are equal exactly when they have the same pieces on the board, the same size and variant and
+//the same player to move, no matter which move order produced them. the move history is deliberately not stored,
+//which is what lets transpositions share statistics. moves_played is only a count and last_drop only speeds up
+//last_move_won, both are left out of Eq and Hash
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SavedState", into = "SavedState")]
 pub struct GameState{
-    board : [[Color; BOARD_HEIGHT]; BOARD_WIDTH],
+    //white's pieces, then black's, laid out as STRIDE describes. only the first width columns and height rows are used,
+    //and the pieces of a column always fill its lowest bits, so its height is how many bits it has set
+    pieces : [u64; 2],
+    width : usize,
+    height : usize,
+    variant : Variant,
+    moves_played : usize,
+    //the (column, row) the last move dropped a piece on, None after a pop or when no move made this position
+    last_drop : Option<(usize, usize)>,
     pub player : Color
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+//GameState as it is written and read back, the cells indexed [column][row] with row 0 at the top.
+//checked by TryFrom before it becomes one, since a size from a damaged or edited file that with_size would refuse
+//makes every board lookup go out of bounds, and a floating piece breaks the column heights
+#[derive(Serialize, Deserialize)]
+struct SavedState{
+    board : [[Cell; MAX_HEIGHT]; MAX_WIDTH],
+    width : usize,
+    height : usize,
+    variant : Variant,
+    moves_played : usize,
+    last_drop : Option<(usize, usize)>,
+    player : Color
+}
+
+impl TryFrom<SavedState> for GameState{
+    type Error = String;
+
+    fn try_from(saved : SavedState) -> Result<Self, String>{
+        let empty = GameState::with_size_and_variant(saved.width, saved.height, saved.variant)
+            .ok_or_else(|| format!("a {} by {} board is not allowed", saved.width, saved.height))?;
+        if saved.last_drop.is_some_and(|(column, row)| column >= empty.width || row >= empty.height){
+            return Err(format!("the last drop {:?} is off the board", saved.last_drop));
+        }
+        let grid = (0..empty.height).map(|row| (0..empty.width).map(|column| saved.board[column][row]).collect()).collect::<Vec<_>>();
+        let state = GameState::from_grid_with_variant(&grid, saved.player, saved.variant).map_err(|error| error.to_string())?;
+        Ok(GameState{
+            moves_played : saved.moves_played,
+            last_drop : saved.last_drop,
+            ..state
+        })
+    }
+}
+
+impl From<GameState> for SavedState{
+    fn from(state : GameState) -> SavedState{
+        let mut board = [[None; MAX_HEIGHT]; MAX_WIDTH];
+        for (row, column, cell) in state.cells(){
+            board[column][row] = cell;
+        }
+        SavedState{
+            board,
+            width : state.width,
+            height : state.height,
+            variant : state.variant,
+            moves_played : state.moves_played,
+            last_drop : state.last_drop,
+            player : state.player
+        }
+    }
+}
+
+impl PartialEq for GameState{
+    fn eq(&self, other : &GameState) -> bool{
+        self.pieces == other.pieces && self.width == other.width && self.height == other.height
+            && self.variant == other.variant && self.player == other.player
+    }
+}
+
+impl Eq for GameState{}
+
+//a position that is equal to its mirror image, for maps that should share one entry between the two.
+//that is only sound while the rules are the same left to right, as they are for every board and variant here
+#[derive(Debug, Copy, Clone)]
+pub struct CanonicalState(pub GameState);
+
+impl PartialEq for CanonicalState{
+    fn eq(&self, other : &CanonicalState) -> bool{
+        self.0.canonical() == other.0.canonical()
+    }
+}
+
+impl Eq for CanonicalState{}
+
+impl Hash for CanonicalState{
+    fn hash<H : Hasher>(&self, state : &mut H){
+        self.0.canonical().hash(state);
+    }
+}
+
+impl Hash for GameState{
+    fn hash<H : Hasher>(&self, state : &mut H){
+        self.pieces.hash(state);
+        self.width.hash(state);
+        self.height.hash(state);
+        self.variant.hash(state);
+        self.player.hash(state);
+    }
+}
+
+//one key for each (column, row, color) and one more for black to move, the size and variant keys follow
+const ZOBRIST_BOARD_KEYS : usize = MAX_WIDTH * MAX_HEIGHT * 2 + 1;
+
+fn zobrist_key(index : usize) -> u64{
+    //splitmix64 of the index, any two indices give unrelated looking numbers
+    let mut z = (index as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+//a player, never a square: a cell of the board is a Cell, and None is an empty one
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Color {
-    Empty,
     White,
     Black,
 }
 
-#[derive(Debug, Copy, Clone)]
+pub type Cell = Option<Color>;
+
+impl Color{
+    pub fn opponent(&self) -> Color{
+        match *self{
+            Color::White => Color::Black,
+            Color::Black => Color::White
+        }
+    }
+}
+
+fn side(color : Color) -> usize{
+    //the index of color's mask in GameState.pieces
+    match color{
+        Color::White => 0,
+        Color::Black => 1
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Variant {
+    //pieces can only be dropped in
+    Standard,
+    //a player may also remove one of their own pieces from the bottom of a column
+    PopOut,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MoveKind {
+    Drop,
+    Pop,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Move {
     pub color : Color,
-    pub x: usize
+    pub x: usize,
+    pub kind : MoveKind
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -27,166 +199,1529 @@ pub enum End{
     Tie
 }
 
+impl End{
+    pub fn is_over(&self) -> bool{
+        *self != End::Ongoing
+    }
+
+    pub fn winner(&self) -> Option<Color>{
+        match *self{
+            End::Victory(color) => Some(color),
+            _ => None
+        }
+    }
+
+    pub fn is_win_for(&self, color : Color) -> bool{
+        self.winner() == Some(color)
+    }
+}
+
 impl Move{
     pub fn new(nx : usize, ncolor : Color) -> Self{
         Move{
             color : ncolor,
-            x: nx
+            x: nx,
+            kind : MoveKind::Drop
+        }
+    }
+
+    pub fn pop(nx : usize, ncolor : Color) -> Self{
+        Move{
+            color : ncolor,
+            x: nx,
+            kind : MoveKind::Pop
         }
     }
 
     pub fn white_new(nx : usize) -> Self{
-        Move::new(nx, Color::White) 
+        Move::new(nx, Color::White)
     }
 
     pub fn black_new(nx : usize) -> Self{
         Move::new(nx, Color::Black)
     }
+}
 
-    fn in_bounds(&self) -> bool{
-        (self.x <= BOARD_WIDTH && self.x  >= 0)
-    }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GridError{
+    //no board of this size can be made, see GameState::with_size
+    Size{ width : usize, height : usize },
+    //this row is not as long as the first one
+    Ragged{ row : usize },
+    //this piece has an empty cell under it
+    Floating{ column : usize, row : usize },
+    //a piece can not go in this column, it is full or off the board
+    Unplayable{ column : usize },
+    //the pieces already make four in a row, so no more can be played
+    Finished,
+    //both players have a piece in this cell
+    Overlap{ column : usize, row : usize },
+    //a piece outside the board
+    OffBoard{ column : usize, row : usize }
 }
 
-fn push_back(item : Color, column : &mut [Color; 6]) -> usize{
-    for i in (0..column.len()).rev(){
-        if (column[i] == Color::Empty){
-            return i;
+impl fmt::Display for GridError{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result{
+        match *self{
+            GridError::Size{ width, height } => write!(f, "a {} by {} board is not supported", width, height),
+            GridError::Ragged{ row } => write!(f, "row {} is not as long as the first row", row),
+            GridError::Floating{ column, row } => write!(f, "the piece in column {} row {} has nothing under it", column, row),
+            GridError::Unplayable{ column } => write!(f, "no piece can be dropped in column {}", column),
+            GridError::Finished => write!(f, "the game is already over"),
+            GridError::Overlap{ column, row } => write!(f, "both players have a piece in column {} row {}", column, row),
+            GridError::OffBoard{ column, row } => write!(f, "the piece in column {} row {} is off the board", column, row)
         }
     }
-    return 0;
+}
+
+impl TryFrom<(Vec<Vec<Option<Color>>>, Color)> for GameState{
+    type Error = GridError;
+
+    fn try_from((grid, player) : (Vec<Vec<Option<Color>>>, Color)) -> Result<Self, GridError>{
+        GameState::from_grid(&grid, player)
+    }
+}
+
+impl Default for GameState{
+    fn default() -> Self{
+        GameState::new()
+    }
 }
 
 impl GameState{
+    //every game starts from one of these constructors, with an empty board and white to move
+
     pub fn new() -> Self{
-        GameState{ 
-            board: [[Color::Empty; BOARD_HEIGHT]; BOARD_WIDTH],
+        GameState::with_variant(Variant::Standard)
+    }
+
+    pub fn empty() -> Self{
+        //the standard 7 wide, 6 high board
+        GameState::new()
+    }
+
+    pub fn with_size(width : usize, height : usize) -> Option<Self>{
+        //a custom board, or None if it is empty or bigger than MAX_WIDTH by MAX_HEIGHT
+        GameState::with_size_and_variant(width, height, Variant::Standard)
+    }
+
+    pub fn with_size_and_variant(width : usize, height : usize, variant : Variant) -> Option<Self>{
+        if width == 0 || height == 0 || width > MAX_WIDTH || height > MAX_HEIGHT{
+            return None;
+        }
+        Some(GameState{
+            width,
+            height,
+            ..GameState::with_variant(variant)
+        })
+    }
+
+    pub fn with_variant(variant : Variant) -> Self{
+        GameState{
+            pieces : [0; 2],
+            width : BOARD_WIDTH,
+            height : BOARD_HEIGHT,
+            variant,
+            moves_played : 0,
+            last_drop : None,
             player : Color::White
         }
     }
 
-    pub fn place(&self, game_move : &Move) -> Self{
-        let mut copy = self.clone();
-        if !self.legal(&game_move){
-            return copy;
+    pub fn from_grid(grid : &[Vec<Option<Color>>], player : Color) -> Result<Self, GridError>{
+        //builds a position straight from its cells, rows from the top of the board to the bottom
+        GameState::from_grid_with_variant(grid, player, Variant::Standard)
+    }
+
+    pub fn from_grid_with_variant(grid : &[Vec<Option<Color>>], player : Color, variant : Variant) -> Result<Self, GridError>{
+        let height = grid.len();
+        let width = grid.first().map_or(0, |row| row.len());
+        let mut state = GameState::with_size_and_variant(width, height, variant).ok_or(GridError::Size{ width, height })?;
+        if let Some(row) = grid.iter().position(|row| row.len() != width){
+            return Err(GridError::Ragged{ row });
+        }
+        for (row, cells) in grid.iter().enumerate(){
+            for (column, &cell) in cells.iter().enumerate(){
+                //the bottom row stands on the floor
+                let supported = grid.get(row + 1).is_none_or(|next| next[column].is_some());
+                if cell.is_some() && !supported{
+                    return Err(GridError::Floating{ column, row });
+                }
+                state.set_cell(column, row, cell);
+            }
         }
+        //the history is unknown, so every piece counts as one move
+        state.moves_played = state.ply();
+        state.player = player;
+        Ok(state)
+    }
 
-        let mut column = copy.board[game_move.x];
-        let y = push_back(game_move.color, &mut column);
-        copy.board[game_move.x][y] = game_move.color;
-        //copy.board[game_move.y][game_move.x] = game_move.color;
-        let next_player = 
-        match game_move.color{
-            Color::White => Color::Black,
-            Color::Black => Color::White,
-            _ => Color::White 
+    pub fn parse_text(text : &str) -> Result<Self, String>{
+        //the fixture format: rows from the top of the board to the bottom, '.' empty, 'X' white and 'O' black
+        //as print draws them, then a line with the side to move, X or O. blank lines are skipped and
+        //every error names the line it is on, counting from 1
+        let lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())).filter(|&(_, line)| !line.is_empty()).collect::<Vec<_>>();
+        let (&(to_move_line, to_move), rows) = lines.split_last().ok_or("no board in the text")?;
+        let player = match to_move{
+            "X" => Color::White,
+            "O" => Color::Black,
+            _ => return Err(format!("line {}: expected the side to move, X or O, got {:?}", to_move_line, to_move))
+        };
+        let mut grid = Vec::new();
+        for &(line, row) in rows.iter(){
+            let cells = row.chars().map(|cell| match cell{
+                '.' => Ok(None),
+                'X' => Ok(Some(Color::White)),
+                'O' => Ok(Some(Color::Black)),
+                _ => Err(format!("line {}: unknown cell {:?}, expected '.', 'X' or 'O'", line, cell))
+            }).collect::<Result<Vec<_>, _>>()?;
+            grid.push(cells);
+        }
+        GameState::from_grid(&grid, player).map_err(|error|{
+            //point at the row the grid check failed on, or the last row for whole board problems
+            let row = match error{
+                GridError::Ragged{ row } | GridError::Floating{ row, .. } => row,
+                _ => rows.len().saturating_sub(1)
+            };
+            format!("line {}: {}", rows.get(row).map_or(to_move_line, |&(line, _)| line), error)
+        })
+    }
+
+    pub fn load<P : AsRef<Path>>(path : P) -> Result<Self, String>{
+        //a position from a fixture file in the parse_text format
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        GameState::parse_text(&text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn to_bitboards(&self) -> (u64, u64, Color){
+        //white's pieces, black's pieces and the player to move. cell (column, row), with rows counted up from
+        //the bottom, is bit column * MAX_HEIGHT + row, the same bit on every board size
+        let mut bitboards = [0u64; 2];
+        for column in 0..self.width{
+            for row in 0..self.height{
+                if let Some(color) = self.cell(column, self.height - 1 - row){
+                    bitboards[(color == Color::Black) as usize] |= 1 << (column * MAX_HEIGHT + row);
+                }
+            }
+        }
+        (bitboards[0], bitboards[1], self.player)
+    }
+
+    pub fn from_bitboards(white : u64, black : u64, player : Color) -> Result<Self, GridError>{
+        //a standard position from to_bitboards
+        GameState::from_bitboards_sized(BOARD_WIDTH, BOARD_HEIGHT, white, black, player)
+    }
+
+    pub fn from_bitboards_sized(width : usize, height : usize, white : u64, black : u64, player : Color) -> Result<Self, GridError>{
+        //the history is lost, so as with from_grid every piece counts as one move
+        let cell = |bit : usize| (bit / MAX_HEIGHT, bit % MAX_HEIGHT);
+        if white & black != 0{
+            let (column, row) = cell((white & black).trailing_zeros() as usize);
+            return Err(GridError::Overlap{ column, row });
+        }
+        let mut grid = vec![vec![None; width]; height];
+        for bit in (0..64).filter(|bit| (white | black) >> bit & 1 == 1){
+            let (column, row) = cell(bit);
+            if column >= width || row >= height{
+                return Err(GridError::OffBoard{ column, row });
+            }
+            grid[height - 1 - row][column] = Some(if white >> bit & 1 == 1 { Color::White } else { Color::Black });
+        }
+        GameState::from_grid(&grid, player)
+    }
+
+    pub fn handicap(self, color : Color, columns : &[usize]) -> Result<Self, GridError>{
+        //this position with extra pieces of color dropped in columns, in order, e.g. for a stronger player
+        //to give a weaker one a head start on the empty board. the player to move stays the same
+        let mut state = self;
+        for &column in columns.iter(){
+            let mv = Move::new(column, color);
+            if !state.legal(&mv){
+                return Err(GridError::Unplayable{ column });
+            }
+            state.place_mut(&mv);
+        }
+        if state.win().is_over(){
+            return Err(GridError::Finished);
+        }
+        state.player = self.player;
+        state.last_drop = None;
+        Ok(state)
+    }
+
+    pub fn variant(&self) -> Variant{
+        self.variant
+    }
+
+    pub fn width(&self) -> usize{
+        self.width
+    }
+
+    pub fn height(&self) -> usize{
+        self.height
+    }
+
+    pub fn cell(&self, column : usize, row : usize) -> Cell{
+        //row 0 is the top of the board, anything off the board is empty
+        if column >= self.width || row >= self.height{
+            return None;
+        }
+        let bit = self.bit(column, row);
+        if self.pieces[0] & bit != 0 { Some(Color::White) }
+        else if self.pieces[1] & bit != 0 { Some(Color::Black) }
+        else { None }
+    }
+
+    pub fn pieces(&self, color : Color) -> u64{
+        //the cells color has a piece on as a bit mask, laid out as STRIDE describes
+        self.pieces[side(color)]
+    }
+
+    pub fn column_height(&self, column : usize) -> usize{
+        //how many pieces are in column
+        (self.occupied() & self.column_mask(column)).count_ones() as usize
+    }
+
+    fn bit(&self, column : usize, row : usize) -> u64{
+        //the mask bit of cell (column, row), with row 0 at the top as everywhere else outside the masks
+        1u64 << (column * STRIDE + self.height - 1 - row)
+    }
+
+    fn column_mask(&self, column : usize) -> u64{
+        ((1u64 << self.height) - 1) << (column * STRIDE)
+    }
+
+    fn occupied(&self) -> u64{
+        self.pieces[0] | self.pieces[1]
+    }
+
+    fn set_cell(&mut self, column : usize, row : usize, cell : Cell){
+        //puts cell on (column, row) whatever was there, it is up to the caller to leave no piece floating
+        let bit = self.bit(column, row);
+        self.pieces[0] &= !bit;
+        self.pieces[1] &= !bit;
+        if let Some(color) = cell{
+            self.pieces[side(color)] |= bit;
+        }
+    }
+
+    fn remove_top(&mut self, column : usize){
+        let height = self.column_height(column);
+        if height > 0{
+            let bit = 1u64 << (column * STRIDE + height - 1);
+            self.pieces[0] &= !bit;
+            self.pieces[1] &= !bit;
+        }
+    }
+
+    fn pop_bottom(&mut self, column : usize){
+        //every piece falls down one cell, the bottom one falls out of the column's mask
+        let mask = self.column_mask(column);
+        for pieces in self.pieces.iter_mut(){
+            *pieces = (*pieces & !mask) | ((*pieces & mask) >> 1 & mask);
+        }
+    }
+
+    fn push_bottom(&mut self, column : usize, color : Color){
+        //undoes pop_bottom, every piece moves up one cell to make room at the bottom
+        let mask = self.column_mask(column);
+        for pieces in self.pieces.iter_mut(){
+            *pieces = (*pieces & !mask) | ((*pieces & mask) << 1 & mask);
+        }
+        self.pieces[side(color)] |= 1u64 << (column * STRIDE);
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, Cell)>{
+        //every cell of the board as (row, column, occupant), in reading order: the top row first, left to right
+        let state = *self;
+        (0..state.height).flat_map(move |row| (0..state.width).map(move |column| (row, column, state.cell(column, row))))
+    }
+
+    pub fn diff(&self, other : &GameState) -> Vec<(usize, usize, Color)>{
+        //the pieces of other that are not on self, as (column, row, color) in reading order: the one dropped piece
+        //after a drop, every new piece after several. a pop moves its column down, so the pieces
+        //above it count as new ones, and cells other leaves empty are not listed at all
+        other.cells().filter_map(|(row, column, tile)| match tile{
+            Some(color) if self.cell(column, row) != Some(color) => Some((column, row, color)),
+            _ => None
+        }).collect()
+    }
+
+    pub fn to_planes(&self, side_to_move : bool) -> Vec<f32>{
+        //the board as input for a network, height * width numbers per plane laid out like cells, one plane after
+        //another: 1 where the player to move has a piece, then 1 where the opponent has one, and with side_to_move
+        //a third plane that is all 1 when white is to move and all 0 when black is
+        let size = self.width * self.height;
+        let mut planes = vec![0f32; if side_to_move { 3 * size } else { 2 * size }];
+        for (i, (_, _, tile)) in self.cells().enumerate(){
+            match tile{
+                Some(color) if color == self.player => planes[i] = 1f32,
+                Some(_) => planes[size + i] = 1f32,
+                None => {}
+            }
+        }
+        if side_to_move && self.player == Color::White{
+            for value in planes[2 * size..].iter_mut(){
+                *value = 1f32;
+            }
+        }
+        planes
+    }
+
+    pub fn mirrored(&self) -> Self{
+        //the same position reflected left to right
+        let mut mirror = *self;
+        let column_bits = (1u64 << STRIDE) - 1;
+        for (mirrored, &pieces) in mirror.pieces.iter_mut().zip(self.pieces.iter()){
+            *mirrored = (0..self.width).fold(0, |mirrored, column|
+                mirrored | (pieces >> (column * STRIDE) & column_bits) << ((self.width - 1 - column) * STRIDE));
+        }
+        mirror.last_drop = self.last_drop.map(|(column, row)| (self.width - 1 - column, row));
+        mirror
+    }
+
+    pub fn canonical(&self) -> Self{
+        //whichever of this position and its mirror image comes first, comparing the columns from the left,
+        //so a position and its mirror have the same canonical form
+        //the search keys every node with it, so the two are compared cell by cell in place, without building either
+        let code = |tile : Cell| match tile{
+            None => 0u8,
+            Some(Color::White) => 1,
+            Some(Color::Black) => 2
+        };
+        for column in 0..self.width{
+            let reflected = self.width - 1 - column;
+            for row in 0..self.height{
+                match code(self.cell(reflected, row)).cmp(&code(self.cell(column, row))){
+                    Ordering::Less => return self.mirrored(),
+                    Ordering::Greater => return *self,
+                    Ordering::Equal => ()
+                }
+            }
+        }
+        *self
+    }
+
+    pub fn with_player(self, player : Color) -> Self{
+        //the same pieces with player to move, e.g. to set up a test position or look at the opponent's options.
+        //the player to move is part of the key, so this is a different position for the search unless nothing changed
+        GameState{ player, ..self }
+    }
+
+    pub fn flip_player(self) -> Self{
+        //the same pieces with the other player to move
+        self.with_player(self.player.opponent())
+    }
+
+    pub fn ply(&self) -> usize{
+        //number of pieces on the board
+        self.occupied().count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool{
+        //no pieces on the board
+        self.occupied() == 0
+    }
+
+    pub fn is_full(&self) -> bool{
+        //no column takes another piece. a full pop out board can still be played on
+        (0..self.width).all(|column| self.column_height(column) == self.height)
+    }
+
+    pub fn is_reachable(&self) -> bool{
+        //could drops in turn from the empty board, white first, have made this position. from_grid and deserializing
+        //only check the cells, this also checks them against the side to move and against a game that should
+        //already have ended: nobody wins twice, and a win was made by the last move of the player who is not to move.
+        //pops take pieces back off, so any pop out position passes, and so do none with a handicap
+        if self.variant == Variant::PopOut{
+            return true;
+        }
+        let count = |color| self.cells().filter(|&(_, _, cell)| cell == Some(color)).count();
+        let (white, black) = (count(Color::White), count(Color::Black));
+        let to_move = if white == black { Color::White } else { Color::Black };
+        if white < black || white > black + 1 || self.player != to_move{
+            return false;
+        }
+        let winner = match (self.color_win(Color::White), self.color_win(Color::Black)){
+            (false, false) => return true,
+            (true, true) => return false,
+            (true, false) => Color::White,
+            (false, true) => Color::Black
         };
+        //some top piece of the winner has to be one that all of their lines go through
+        winner != self.player && (0..self.width).any(|column|{
+            let height = self.column_height(column);
+            let mut before = *self;
+            before.remove_top(column);
+            height > 0 && self.cell(column, self.height - height) == Some(winner) && !before.color_win(winner)
+        })
+    }
+
+    pub fn move_count(&self) -> usize{
+        //number of moves played since the start, the same as ply except that in pop out a pop is a move too
+        self.moves_played
+    }
+
+    pub fn zobrist(&self) -> u64{
+        //a 64 bit key for the position: one pseudo random number per (column, row, color) xored over the pieces,
+        //plus one for black to move and one for the size and variant. states that are equal get the same key,
+        //different ones collide only by bad luck. the numbers come from a fixed mix rather than a table,
+        //so keys are the same from run to run
+        match self.player{
+            Color::White => self.layout_key(),
+            Color::Black => self.layout_key() ^ zobrist_key(ZOBRIST_BOARD_KEYS - 1)
+        }
+    }
+
+    pub fn layout_key(&self) -> u64{
+        //zobrist without the player to move, the same for both sides of a position. only for things that
+        //really do not depend on whose turn it is, the search itself has to tell the two apart
+        let mut key = zobrist_key((self.width * MAX_HEIGHT + self.height) * 2 + (self.variant == Variant::PopOut) as usize + ZOBRIST_BOARD_KEYS);
+        for column in 0..self.width{
+            for row in 0..self.height{
+                if let Some(color) = self.cell(column, row){
+                    key ^= zobrist_key((column * MAX_HEIGHT + row) * 2 + (color == Color::Black) as usize);
+                }
+            }
+        }
+        key
+    }
+
+    pub fn place(&self, game_move : &Move) -> Self{
+        let mut copy = *self;
+        copy.place_mut(game_move);
+        copy
+    }
+
+    pub fn play(&mut self, game_move : &Move) -> Option<End>{
+        //place_mut, then result, so a ui learns from the move itself whether it ended the game
+        self.place_mut(game_move);
+        self.result()
+    }
+
+    pub fn result(&self) -> Option<End>{
+        //how the game ended, None while it goes on
+        Some(self.win()).filter(End::is_over)
+    }
 
-        copy.player = next_player;
-        return copy;
+    pub fn place_mut(&mut self, game_move : &Move){
+        //the same as place, but changes this state instead of returning a new one
+        if !self.legal(game_move){
+            return;
+        }
+
+        match game_move.kind{
+            MoveKind::Drop => {
+                let y = self.height - 1 - self.column_height(game_move.x);
+                self.pieces[side(game_move.color)] |= self.bit(game_move.x, y);
+                self.last_drop = Some((game_move.x, y));
+            },
+            MoveKind::Pop => {
+                self.pop_bottom(game_move.x);
+                self.last_drop = None;
+            }
+        }
+        self.moves_played += 1;
+        self.player = game_move.color.opponent();
+    }
+
+    pub fn apply(&mut self, game_move : &Move) -> bool{
+        //plays game_move on this board for depth first searches that walk one board up and down the tree,
+        //returns false and changes nothing if it is illegal, only a move that returned true may be unapplied
+        let legal = self.legal(game_move);
+        self.place_mut(game_move);
+        legal
+    }
+
+    pub fn unapply(&mut self, game_move : &Move){
+        //takes back the last move played, which has to be game_move
+        match game_move.kind{
+            MoveKind::Drop => self.remove_top(game_move.x),
+            MoveKind::Pop => self.push_bottom(game_move.x, game_move.color)
+        }
+        self.moves_played = self.moves_played.saturating_sub(1);
+        self.last_drop = None;
+        self.player = game_move.color;
+    }
+
+    pub fn last_move_won(&self) -> bool{
+        //did the drop that made this position give its player four in a row: their mask has a line,
+        //and it had none without the new piece. always false after a pop, which moves a whole column,
+        //and for positions no drop made
+        match self.last_drop{
+            Some((column, row)) => match self.cell(column, row){
+                Some(color) => {
+                    let pieces = self.pieces[side(color)];
+                    has_four(pieces) && !has_four(pieces & !self.bit(column, row))
+                },
+                None => false
+            },
+            None => false
+        }
     }
 
     pub fn legal(&self, game_move: &Move) -> bool{
-        if !game_move.in_bounds(){
+        if game_move.x >= self.width{
             return false;
         }
 
-        let column = self.board[game_move.x];
-        column.iter().fold(0, |sum, next| if next == &Color::Empty { sum }  else { sum + 1 } ) < BOARD_HEIGHT
+        match game_move.kind{
+            MoveKind::Drop => self.column_height(game_move.x) < self.height,
+            //only your own piece on the bottom row can be popped
+            MoveKind::Pop => self.variant == Variant::PopOut && self.pieces[side(game_move.color)] >> (game_move.x * STRIDE) & 1 == 1
+        }
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move>{
+        //the moves of the player to move, the only moves that can come next.
+        //always drops from left to right, then pops from left to right, so a seeded search picks the same moves on every run
+        self.legal_moves_for(self.player)
+    }
+
+    pub fn legal_moves_into(&self, moves : &mut Vec<Move>){
+        //legal_moves written over the contents of moves, so a loop can keep reusing one buffer
+        self.legal_moves_for_into(self.player, moves);
+    }
+
+    pub fn legal_moves_for(&self, color : Color) -> Vec<Move>{
+        //the moves color could make if it were their turn, e.g. to look for the opponent's threats.
+        //drops are the same for both players, but in pop out each may only pop their own pieces
+        let mut moves = Vec::with_capacity(self.width);
+        self.legal_moves_for_into(color, &mut moves);
+        moves
     }
 
-    pub fn legal_moves(&self, color : Color) -> Vec<Move>{
-        let mut moves = Vec::new();
-        for x in 0..BOARD_WIDTH{
+    pub fn legal_moves_for_into(&self, color : Color, moves : &mut Vec<Move>){
+        moves.clear();
+        for x in 0..self.width{
             let potential_move = Move::new(x, color);
             if self.legal(&potential_move){
                 moves.push(potential_move);
             }
         }
-        return moves;
+        if self.variant == Variant::PopOut{
+            for x in 0..self.width{
+                let potential_move = Move::pop(x, color);
+                if self.legal(&potential_move){
+                    moves.push(potential_move);
+                }
+            }
+        }
+    }
+
+    pub fn winning_moves(&self, color : Color) -> Vec<Move>{
+        //moves that would win on the spot if color got to play now. a drop only adds its own bit to color's mask,
+        //a pop moves a whole column and is played out
+        let pieces = self.pieces[side(color)];
+        self.legal_moves_for(color).into_iter().filter(|mv| match mv.kind{
+            MoveKind::Drop => has_four(pieces | 1u64 << (mv.x * STRIDE + self.column_height(mv.x))),
+            MoveKind::Pop => self.place(mv).win().is_win_for(color)
+        }).collect()
     }
 
-    pub fn linear_match(&self, start_x : i32, start_y : i32, stepX : i32, stepY : i32, color : Color) -> bool{
+    pub fn linear_match(&self, start_x : i32, start_y : i32, step_x : i32, step_y : i32, color : Color) -> bool{
         for i in 0..4{
-            let x = (start_x + i * stepX) as usize;
-            let y = (start_y + i * stepY) as usize;
-            //bound checking
-            if x < 0 || x >= BOARD_HEIGHT || y < 0 || y >= BOARD_WIDTH{
+            let x = (start_x + i * step_x) as usize;
+            let y = (start_y + i * step_y) as usize;
+            //bound checking, negative values wrap around to huge ones
+            if x >= self.height || y >= self.width{
                 return false;
             }
-            let value = self.board[y][x];
-            if value != color{
+            if self.cell(y, x) != Some(color){
                 return false;
             }
         }
-        return true;
+        true
     }
 
     pub fn is_line_start(&self, x : i32, y : i32, color : Color) -> bool{
-        self.linear_match(x, y, 1, 0, color) 
-        || self.linear_match(x, y, 0, 1, color) 
+        self.linear_match(x, y, 1, 0, color)
+        || self.linear_match(x, y, 0, 1, color)
         || self.linear_match(x, y, 1, 1, color)
         || self.linear_match(x, y, 1, -1, color)
     }
 
-    fn color_win(&self, color : Color) -> bool{
-        for x in 0..BOARD_HEIGHT + 1{
-            for y in 0..BOARD_WIDTH + 1{
-                if self.is_line_start(y as i32, x as i32, color){
-                    return true;
+    fn run_length(&self, column : usize, row : usize, step_column : i32, step_row : i32, color : Color) -> usize{
+        //how many pieces of color follow (column, row) in one direction, not counting the cell itself
+        let mut length = 0;
+        let (mut x, mut y) = (column as i32 + step_column, row as i32 + step_row);
+        while x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+            && self.cell(x as usize, y as usize) == Some(color){
+            length += 1;
+            x += step_column;
+            y += step_row;
+        }
+        length
+    }
+
+    pub fn completes_line(&self, column : usize, row : usize, color : Color) -> bool{
+        //would a piece of color on this cell make four in a row
+        [(1, 0), (0, 1), (1, 1), (1, -1)].iter().any(|&(step_column, step_row)|
+            1 + self.run_length(column, row, step_column, step_row, color)
+            + self.run_length(column, row, -step_column, -step_row, color) >= 4)
+    }
+
+    pub fn threats(&self, color : Color) -> Vec<(usize, usize)>{
+        //every empty cell, as (column, row), that would give color four in a row, playable right now or not
+        let mut threats = Vec::new();
+        for column in 0..self.width{
+            for row in 0..self.height{
+                if self.cell(column, row).is_none() && self.completes_line(column, row, color){
+                    threats.push((column, row));
+                }
+            }
+        }
+        threats
+    }
+
+    pub fn winning_line(&self) -> Option<Vec<(usize, usize)>>{
+        //the (column, row) cells of the winner's line, for a frontend to highlight
+        //a line longer than four comes back whole
+        let winner = self.win().winner()?;
+        for column in 0..self.width{
+            for row in 0..self.height{
+                if self.cell(column, row) != Some(winner){
+                    continue;
+                }
+                for &(step_column, step_row) in [(1, 0), (0, 1), (1, 1), (1, -1)].iter(){
+                    //only start counting at the first piece of a run
+                    if self.run_length(column, row, -step_column, -step_row, winner) > 0{
+                        continue;
+                    }
+                    let length = 1 + self.run_length(column, row, step_column, step_row, winner);
+                    if length >= 4{
+                        return Some((0..length as i32).map(|i|
+                            ((column as i32 + i * step_column) as usize, (row as i32 + i * step_row) as usize)).collect());
+                    }
                 }
             }
         }
-        return false;
+        None
+    }
+
+    fn color_win(&self, color : Color) -> bool{
+        has_four(self.pieces[side(color)])
     }
 
 
     pub fn win(&self) -> End{
-        if self.color_win(Color::White) {
+        let white_win = self.color_win(Color::White);
+        let black_win = self.color_win(Color::Black);
+        if white_win && black_win {
+            //only possible after a pop, in which case the player who popped wins
+            return match self.player{
+                Color::White => End::Victory(Color::Black),
+                Color::Black => End::Victory(Color::White)
+            };
+        }
+        if white_win {
             return End::Victory(Color::White);
         }
-        if self.color_win(Color::Black) {
+        if black_win {
             return End::Victory(Color::Black);
         }
-        let mut no_empty = true;
-        for column in self.board.iter(){
-            for &tile in column{
-                if tile == Color::Empty{
-                    no_empty = false;
-                }
-            }
-        }
-        if no_empty {
+        //in pop out a full board goes on as long as the next player can pop
+        if self.is_full() && self.legal_moves().is_empty() {
             return End::Tie;
         }
-        return End::Ongoing;
+        End::Ongoing
+    }
+
+    pub fn is_terminal(&self) -> bool{
+        self.win().is_over()
     }
 
     pub fn print(&self) -> String{
         let mut string = String::from("\n");
-        for x in 0..BOARD_HEIGHT{
-            for y in 0..BOARD_WIDTH{
-                string.push_str("|");
-                let tile = self.board[y][x];
-                let tile_str =
-                    match tile {
-                        Color::Empty => " ",
-                        Color::White => "X",
-                        Color::Black => "O"
-                    };
-                string.push_str(tile_str);
-                string.push_str("|")
+        for (_, column, tile) in self.cells(){
+            string.push('|');
+            let tile_str =
+                match tile {
+                    None => " ",
+                    Some(Color::White) => "X",
+                    Some(Color::Black) => "O"
+                };
+            string.push_str(tile_str);
+            string.push('|');
+            if column + 1 == self.width{
+                string.push('\n');
+            }
+        }
+        for y in 0..self.width{
+            string.push_str(&format!("|{}|", y + 1));
+        }
+
+        string
+    }
+}
+
+pub fn enumerate_positions(ply : usize, fold_symmetry : bool) -> usize{
+    //how many distinct positions the standard board can reach after exactly ply moves, mirror images
+    //counted once when fold_symmetry. finished games are counted but not played on. the unfolded counts
+    //are known (1, 7, 49, 238, 1120, 4263, ...), so this checks the move generation and hashing together
+    let key = |state : GameState| if fold_symmetry { state.canonical() } else { state };
+    let mut positions = HashSet::new();
+    positions.insert(GameState::new());
+    for _ in 0..ply{
+        positions = positions.iter()
+            .filter(|state| !state.is_terminal())
+            .flat_map(|state| state.legal_moves().into_iter().map(move |mv| key(state.place(&mv))))
+            .collect();
+    }
+    positions.len()
+}
+
+//a game as the moves played from where it started, to save it and pick it up again later.
+//its notation is the one the protocol reads: one character per move, the column counting from 1 on the left,
+//with a 'p' in front of the column of a pop. the colors follow from whose turn it is
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord{
+    pub start : GameState,
+    pub moves : Vec<Move>
+}
+
+impl GameRecord{
+    pub fn new(start : GameState) -> Self{
+        GameRecord{ start, moves : Vec::new() }
+    }
+
+    pub fn position(&self) -> GameState{
+        //where the game stands after its moves
+        self.moves.iter().fold(self.start, |state, mv| state.place(mv))
+    }
+
+    pub fn play(&mut self, mv : Move) -> Result<Option<End>, String>{
+        //adds mv, if the player to move can play it in a game that is not over, and returns how it ended the game
+        let mut state = self.position();
+        if state.is_terminal(){
+            return Err("the game is already over".to_string());
+        }
+        if mv.color != state.player || !state.legal(&mv){
+            return Err(format!("{:?} can not be played here", mv));
+        }
+        self.moves.push(mv);
+        Ok(state.play(&mv))
+    }
+
+    pub fn to_notation(&self) -> String{
+        self.moves.iter().map(|mv| match mv.kind{
+            MoveKind::Drop => format!("{}", mv.x + 1),
+            MoveKind::Pop => format!("p{}", mv.x + 1)
+        }).collect()
+    }
+
+    pub fn from_notation(start : GameState, notation : &str) -> Result<Self, String>{
+        let mut record = GameRecord::new(start);
+        let mut pop = false;
+        for character in notation.chars(){
+            if character == 'p' && !pop{
+                pop = true;
+                continue;
             }
-            string.push_str("\n");
+            let column = match character.to_digit(10){
+                Some(column) if column >= 1 => column as usize - 1,
+                _ => return Err(format!("{:?} is not a column", character))
+            };
+            let player = record.position().player;
+            let mv = if pop { Move::pop(column, player) } else { Move::new(column, player) };
+            record.play(mv).map_err(|_| format!("{}{} can not be played after {:?}", if pop { "p" } else { "" }, character, record.to_notation()))?;
+            pop = false;
+        }
+        if pop{
+            return Err("a 'p' has to be followed by a column".to_string());
         }
-        string.push_str("|1||2||3||4||5||6||7|");
-        
-        return string;
+        Ok(record)
     }
 }
 
 #[cfg(test)]
 mod test{
-    use super::*; 
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+
+    fn hash_of(state : &GameState) -> u64{
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn play(state : GameState, columns : &[usize]) -> GameState{
+        columns.iter().fold(state, |state, &x| state.place(&Move::new(x, state.player)))
+    }
+
+    #[test]
+    fn standard_has_no_pop_moves(){
+        let state = play(GameState::new(), &[0, 1]);
+        assert!(!state.legal(&Move::pop(0, Color::White)));
+        assert!(state.legal_moves().iter().all(|mv| mv.kind == MoveKind::Drop));
+    }
+
+    #[test]
+    fn pop_out_only_pops_own_bottom_piece(){
+        let state = play(GameState::with_variant(Variant::PopOut), &[0, 1]);
+        assert!(state.legal(&Move::pop(0, Color::White)));
+        assert!(!state.legal(&Move::pop(1, Color::White)));
+        assert!(!state.legal(&Move::pop(2, Color::White)));
+        let pops = state.legal_moves().into_iter().filter(|mv| mv.kind == MoveKind::Pop).count();
+        assert_eq!(pops, 1);
+    }
+
+    #[test]
+    fn pop_shifts_column_down(){
+        //white at the bottom of column 0, black on top of it
+        let state = play(GameState::with_variant(Variant::PopOut), &[0, 0]);
+        let popped = state.place(&Move::pop(0, Color::White));
+        assert_eq!(popped.cell(0, BOARD_HEIGHT - 1), Some(Color::Black));
+        assert_eq!(popped.cell(0, BOARD_HEIGHT - 2), None);
+        assert_eq!(popped.player, Color::Black);
+    }
+
+    #[test]
+    fn pop_can_complete_a_line(){
+        //black has three on the bottom row with a gap filled by white, and a black piece resting on the white one
+        let mut state = GameState::with_variant(Variant::PopOut);
+        state.set_cell(0, BOARD_HEIGHT - 1, Some(Color::Black));
+        state.set_cell(1, BOARD_HEIGHT - 1, Some(Color::White));
+        state.set_cell(1, BOARD_HEIGHT - 2, Some(Color::Black));
+        state.set_cell(2, BOARD_HEIGHT - 1, Some(Color::Black));
+        state.set_cell(3, BOARD_HEIGHT - 1, Some(Color::Black));
+        assert_eq!(state.win(), End::Ongoing);
+        let popped = state.place(&Move::pop(1, Color::White));
+        assert_eq!(popped.win(), End::Victory(Color::Black));
+    }
+
+    #[test]
+    fn double_line_after_pop_goes_to_popper(){
+        let mut state = GameState::with_variant(Variant::PopOut);
+        for x in 0..4{
+            state.set_cell(x, BOARD_HEIGHT - 1, Some(Color::White));
+            state.set_cell(x, BOARD_HEIGHT - 2, Some(Color::Black));
+        }
+        state.player = Color::White;
+        assert_eq!(state.win(), End::Victory(Color::Black));
+    }
+
+    #[test]
+    fn transpositions_are_equal(){
+        let a = play(GameState::new(), &[0, 1, 2, 3]);
+        let b = play(GameState::new(), &[2, 3, 0, 1]);
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+    }
+
+    #[test]
+    fn different_pieces_are_not_equal(){
+        let a = play(GameState::new(), &[0, 1]);
+        let b = play(GameState::new(), &[1, 0]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn player_is_part_of_the_key(){
+        let a = play(GameState::new(), &[0, 1]);
+        let mut b = a;
+        b.player = Color::Black;
+        assert_ne!(a, b);
+        assert_ne!(hash_of(&a), hash_of(&b));
+    }
+
+    #[test]
+    fn variant_is_part_of_the_key(){
+        let a = play(GameState::new(), &[3]);
+        let b = play(GameState::with_variant(Variant::PopOut), &[3]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn states_work_as_set_keys(){
+        let mut seen = std::collections::HashSet::new();
+        seen.insert(play(GameState::new(), &[0, 1, 2, 3]));
+        assert!(seen.contains(&play(GameState::new(), &[2, 1, 0, 3])));
+        assert!(!seen.contains(&play(GameState::new(), &[0, 1, 2])));
+    }
+
+    #[test]
+    fn end_helpers(){
+        assert_eq!(End::Victory(Color::Black).winner(), Some(Color::Black));
+        assert_eq!(End::Tie.winner(), None);
+        assert_eq!(End::Ongoing.winner(), None);
+        assert!(End::Victory(Color::White).is_win_for(Color::White));
+        assert!(!End::Victory(Color::White).is_win_for(Color::Black));
+        assert!(!End::Tie.is_win_for(Color::White));
+        assert!(End::Tie.is_over());
+        assert!(!End::Ongoing.is_over());
+    }
+
+    #[test]
+    fn terminal_after_four_in_a_row(){
+        let state = play(GameState::new(), &[0, 1, 0, 1, 0, 1]);
+        assert!(!state.is_terminal());
+        assert!(state.place(&Move::white_new(0)).is_terminal());
+    }
+
+    #[test]
+    fn winning_moves_for_either_color(){
+        let state = play(GameState::new(), &[0, 0, 1, 1, 2, 2]);
+        let white = state.winning_moves(Color::White);
+        assert_eq!(white.len(), 1);
+        assert_eq!(white[0].x, 3);
+        //black's three are on the second row, so column 3 does not finish them yet
+        assert!(state.winning_moves(Color::Black).is_empty());
+    }
+
+    #[test]
+    fn empty_is_the_standard_start(){
+        let state = GameState::empty();
+        assert_eq!(state, GameState::new());
+        assert_eq!((state.width(), state.height()), (7, 6));
+        assert_eq!(state.player, Color::White);
+        assert_eq!(state.ply(), 0);
+        assert!(state.print().ends_with("|1||2||3||4||5||6||7|"));
+    }
+
+    #[test]
+    fn with_size_rejects_impossible_boards(){
+        assert!(GameState::with_size(0, 6).is_none());
+        assert!(GameState::with_size(7, 0).is_none());
+        assert!(GameState::with_size(MAX_WIDTH + 1, 6).is_none());
+        assert!(GameState::with_size(7, MAX_HEIGHT + 1).is_none());
+        assert_eq!(GameState::with_size(7, 6), Some(GameState::empty()));
+    }
+
+    #[test]
+    fn small_board_plays_by_its_own_size(){
+        let state = GameState::with_size(4, 4).unwrap();
+        assert_eq!(state.player, Color::White);
+        assert_eq!(state.legal_moves().len(), 4);
+        assert!(!state.legal(&Move::white_new(4)));
+        let full_column = play(state, &[0, 0, 0, 0]);
+        assert!(!full_column.legal(&Move::white_new(0)));
+        assert_eq!(full_column.legal_moves().len(), 3);
+        //four across the whole width
+        let won = play(state, &[0, 0, 1, 1, 2, 2, 3]);
+        assert_eq!(won.win(), End::Victory(Color::White));
+        assert_ne!(state, GameState::with_size(4, 5).unwrap());
+    }
+
+    #[test]
+    fn small_board_fills_up_to_a_tie(){
+        //a 3 by 3 board is too small for anyone to get four
+        let state = play(GameState::with_size(3, 3).unwrap(), &[0, 1, 2, 0, 1, 2, 0, 1, 2]);
+        assert_eq!(state.win(), End::Tie);
+        assert!(state.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn out_of_range_column_is_illegal(){
+        let state = GameState::new();
+        assert!(!state.legal(&Move::white_new(7)));
+        assert_eq!(state.place(&Move::white_new(7)), state);
+    }
+
+    #[test]
+    fn place_mut_matches_place(){
+        let mut state = GameState::with_variant(Variant::PopOut);
+        let mut copied = state;
+        let moves = [Move::white_new(3), Move::black_new(3), Move::white_new(2), Move::pop(2, Color::Black),
+            Move::pop(3, Color::Black), Move::black_new(9)];
+        for mv in moves.iter(){
+            copied = copied.place(mv);
+            state.place_mut(mv);
+            assert_eq!(state, copied);
+        }
+        assert_eq!(state.ply(), 3);
+    }
+
+    #[test]
+    fn unapply_walks_back_to_the_start(){
+        for &variant in &[Variant::Standard, Variant::PopOut]{
+            let start = GameState::with_variant(variant);
+            let mut state = start;
+            let mut history = vec![state];
+            let mut played = Vec::new();
+            for i in 0..60usize{
+                let moves = state.legal_moves();
+                if moves.is_empty() || state.is_terminal(){
+                    break;
+                }
+                //pops come last in the list, so this mixes in some of them
+                let mv = moves[(i * 5 + 3) % moves.len()];
+                assert!(state.apply(&mv));
+                played.push(mv);
+                history.push(state);
+            }
+            while let Some(mv) = played.pop(){
+                history.pop();
+                state.unapply(&mv);
+                assert_eq!(&state, history.last().unwrap());
+            }
+            assert_eq!(state, start);
+        }
+    }
+
+    #[test]
+    fn apply_refuses_illegal_moves(){
+        let mut state = play(GameState::new(), &[0, 0, 0, 0, 0, 0]);
+        let before = state;
+        assert!(!state.apply(&Move::white_new(0)));
+        assert!(!state.apply(&Move::pop(1, Color::White)));
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn threats_find_every_completing_cell(){
+        //white has three on the bottom row from column 1, black three on the row above
+        let state = play(GameState::new(), &[1, 1, 2, 2, 3, 3]);
+        assert_eq!(state.threats(Color::White), vec![(0, 5), (4, 5)]);
+        assert_eq!(state.threats(Color::Black), vec![(0, 4), (4, 4)]);
+        assert!(state.completes_line(0, 5, Color::White));
+        assert!(!state.completes_line(0, 5, Color::Black));
+        assert!(GameState::new().threats(Color::White).is_empty());
+        assert_eq!(Color::White.opponent(), Color::Black);
+        assert_eq!(Color::Black.opponent().opponent(), Color::Black);
+    }
+
+    #[test]
+    fn winning_line_marks_the_four(){
+        assert_eq!(GameState::new().winning_line(), None);
+        assert_eq!(play(GameState::new(), &[1, 1, 2, 2, 3, 3]).winning_line(), None);
+        let across = play(GameState::new(), &[1, 1, 2, 2, 3, 3, 4]);
+        assert_eq!(across.winning_line(), Some(vec![(1, 5), (2, 5), (3, 5), (4, 5)]));
+        let up = play(GameState::new(), &[0, 6, 0, 6, 0, 6, 5, 6]);
+        assert_eq!(up.winning_line(), Some(vec![(6, 2), (6, 3), (6, 4), (6, 5)]));
+        //filling the gap joins five in a row, all of which is returned
+        let five = play(GameState::new(), &[0, 0, 1, 1, 3, 3, 4, 4, 2]);
+        assert_eq!(five.winning_line().unwrap().len(), 5);
+        //a diagonal rising to the right goes up the board, so its rows count down
+        let diagonal = play(GameState::new(), &[0, 1, 1, 2, 2, 3, 2, 3, 3, 6, 3]);
+        assert_eq!(diagonal.winning_line(), Some(vec![(0, 5), (1, 4), (2, 3), (3, 2)]));
+    }
+
+    #[test]
+    fn deserializing_checks_the_board_size(){
+        let state = play(GameState::new(), &[3, 3, 2]);
+        let json = ::serde_json::to_string(&state).unwrap();
+        assert_eq!(::serde_json::from_str::<GameState>(&json).unwrap(), state);
+        assert!(::serde_json::from_str::<GameState>(&json.replace("\"width\":7", "\"width\":20")).is_err());
+        assert!(::serde_json::from_str::<GameState>(&json.replace("\"height\":6", "\"height\":0")).is_err());
+        assert!(::serde_json::from_str::<GameState>(&json.replace("\"last_drop\":[2,5]", "\"last_drop\":[9,5]")).is_err());
+        //the cells are still written column by column, and a piece lifted off the one under it is refused
+        let lifted = json.replacen("[null,null,null,null,null,\"White\",null]", "[null,null,null,null,\"White\",null,null]", 1);
+        assert_ne!(lifted, json);
+        assert!(::serde_json::from_str::<GameState>(&lifted).is_err());
+    }
+
+    #[test]
+    fn finds_lines_on_boards_wider_than_tall(){
+        //a line falling to the left from the rightmost column, and its mirror image, on boards where the
+        //columns outnumber the rows by more than one
+        let boards : [&[&str]; 2] = [
+            &["......x", ".....xo", "....xoo", "...xooo"],
+            &["........", "........", ".......x", "......xo", ".....xoo", "....xooo"]
+        ];
+        for rows in boards.iter(){
+            let mirrored = rows.iter().map(|row| row.chars().rev().collect::<String>()).collect::<Vec<_>>();
+            let mirrored = mirrored.iter().map(String::as_str).collect::<Vec<_>>();
+            for rows in [rows.to_vec(), mirrored].iter(){
+                let state = GameState::from_grid(&grid(rows), Color::Black).unwrap();
+                assert_eq!(state.win(), End::Victory(Color::White));
+                assert!(::bitboard::Bitboard::from_state(&state).unwrap().is_win_for(Color::White));
+            }
+        }
+    }
+
+    fn grid(rows : &[&str]) -> Vec<Vec<Option<Color>>>{
+        rows.iter().map(|row| row.chars().map(|cell| match cell{
+            'x' => Some(Color::White),
+            'o' => Some(Color::Black),
+            _ => None
+        }).collect()).collect()
+    }
+
+    #[test]
+    fn bitboards_round_trip(){
+        let state = play(GameState::new(), &[3, 3, 2, 6]);
+        let (white, black, player) = state.to_bitboards();
+        assert_eq!(white, 1 << (3 * MAX_HEIGHT) | 1 << (2 * MAX_HEIGHT));
+        assert_eq!(black, 1 << (3 * MAX_HEIGHT + 1) | 1 << (6 * MAX_HEIGHT));
+        assert_eq!(player, Color::White);
+        assert_eq!(GameState::from_bitboards(white, black, player), Ok(state));
+        assert_eq!(GameState::new().to_bitboards(), (0, 0, Color::White));
+        let small = play(GameState::with_size(4, 4).unwrap(), &[0, 1, 1]);
+        let (white, black, player) = small.to_bitboards();
+        assert_eq!(GameState::from_bitboards_sized(4, 4, white, black, player), Ok(small));
+
+        assert_eq!(GameState::from_bitboards(1, 1, Color::White), Err(GridError::Overlap{ column : 0, row : 0 }));
+        assert_eq!(GameState::from_bitboards(1 << 6, 0, Color::White), Err(GridError::OffBoard{ column : 0, row : 6 }));
+        assert!(matches!(GameState::from_bitboards(1 << 1, 0, Color::White), Err(GridError::Floating{ .. })));
+    }
+
+    #[test]
+    fn play_reports_the_end_of_the_game(){
+        let mut state = play(GameState::new(), &[0, 6, 1, 6, 2]);
+        assert_eq!(state.result(), None);
+        assert_eq!(state.play(&Move::new(6, Color::Black)), None);
+        assert_eq!(state.play(&Move::white_new(3)), Some(End::Victory(Color::White)));
+        assert_eq!(state.result(), Some(state.win()));
+        let mut small = play(GameState::with_size(2, 2).unwrap(), &[0, 0, 1]);
+        assert_eq!(small.play(&Move::new(1, Color::Black)), Some(End::Tie));
+    }
+
+    #[test]
+    fn legal_moves_come_in_a_fixed_order(){
+        let state = play(GameState::new(), &[3, 3, 3, 3, 3, 3, 0]);
+        let columns = state.legal_moves().iter().map(|mv| mv.x).collect::<Vec<_>>();
+        assert_eq!(columns, vec![0, 1, 2, 4, 5, 6]);
+        let mut buffer = vec![Move::white_new(5)];
+        state.legal_moves_into(&mut buffer);
+        assert_eq!(buffer, state.legal_moves());
+
+        let pop_out = play(GameState::with_variant(Variant::PopOut), &[4, 1, 2]);
+        let moves = pop_out.legal_moves().iter().map(|mv| (mv.kind, mv.x)).collect::<Vec<_>>();
+        let mut expected = (0..7).map(|x| (MoveKind::Drop, x)).collect::<Vec<_>>();
+        expected.push((MoveKind::Pop, 1));
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn empty_and_full_boards(){
+        let empty = GameState::new();
+        assert!(empty.is_empty() && !empty.is_full());
+        let one = play(empty, &[6]);
+        assert!(!one.is_empty() && !one.is_full());
+        //two columns of a 2 by 2 board, filled without four in a row
+        let small = play(GameState::with_size(2, 2).unwrap(), &[0, 0, 1, 1]);
+        assert!(small.is_full() && !small.is_empty());
+        assert_eq!(small.win(), End::Tie);
+        let column = play(empty, &[0, 0, 0, 0, 0, 0]);
+        assert!(!column.is_full());
+    }
+
+    #[test]
+    fn game_records_round_trip_their_notation(){
+        let record = GameRecord::from_notation(GameState::new(), "4453").unwrap();
+        assert_eq!(record.position(), play(GameState::new(), &[3, 3, 4, 2]));
+        assert_eq!(record.to_notation(), "4453");
+        let pop_out = GameRecord::from_notation(GameState::with_variant(Variant::PopOut), "45p4").unwrap();
+        assert_eq!(pop_out.moves[2], Move::pop(3, Color::White));
+        assert_eq!(GameRecord::from_notation(pop_out.start, &pop_out.to_notation()), Ok(pop_out));
+        //no pops in the standard game, no eighth column, no moves once it is won
+        assert!(GameRecord::from_notation(GameState::new(), "4p4").is_err());
+        assert!(GameRecord::from_notation(GameState::new(), "8").is_err());
+        assert!(GameRecord::from_notation(GameState::new(), "1212121").is_ok());
+        assert!(GameRecord::from_notation(GameState::new(), "12121212").is_err());
+        assert!(GameRecord::from_notation(GameState::new(), "4p").is_err());
+        let mut record = GameRecord::new(GameState::new());
+        assert_eq!(record.play(Move::black_new(3)).map_err(|_| ()), Err(()));
+        assert_eq!(record.play(Move::white_new(3)), Ok(None));
+    }
 
+    #[test]
+    fn checks_positions_are_reachable(){
+        assert!(GameState::new().is_reachable());
+        assert!(play(GameState::new(), &[3, 3, 2]).is_reachable());
+        //white's win in the bottom row, made by its last drop
+        let won = play(GameState::new(), &[0, 0, 1, 1, 2, 2, 3]);
+        assert!(won.win() == End::Victory(Color::White) && won.is_reachable());
+        assert!(!won.with_player(Color::White).is_reachable());
+        assert!(!GameState::new().with_player(Color::Black).is_reachable());
+        assert!(!GameState::new().handicap(Color::White, &[0, 6]).unwrap().is_reachable());
+        //a win that would have ended the game before black covered every piece of it
+        let late = GameState::parse_text(".......\n.......\n.......\n...O...\nOOOX...\nXXXX...\nO").unwrap();
+        assert!(!late.is_reachable());
+        //both players with four in a row
+        let grid = (0..6).map(|row| (0..7).map(|column| match (row, column){
+            (5, 0..=3) => Some(Color::White),
+            (4, 0..=3) => Some(Color::Black),
+            _ => None
+        }).collect::<Vec<_>>()).collect::<Vec<_>>();
+        assert!(!GameState::from_grid(&grid, Color::White).unwrap().is_reachable());
+        assert!(GameState::with_variant(Variant::PopOut).with_player(Color::Black).is_reachable());
+    }
+
+    #[test]
+    fn enumerates_the_known_position_counts(){
+        let counts = (0..7).map(|ply| enumerate_positions(ply, false)).collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 7, 49, 238, 1120, 4263, 16422]);
+        //a position and its mirror are one position, unless it is its own mirror
+        assert_eq!(enumerate_positions(1, true), 4);
+        assert_eq!(enumerate_positions(2, true), 25);
+        assert!(enumerate_positions(5, true) * 2 > enumerate_positions(5, false));
+    }
+
+    #[test]
+    fn parses_text_positions(){
+        let state = GameState::parse_text(".......\n.......\n\n.......\n.......\n...O...\n..XX...\nO\n").unwrap();
+        assert_eq!(state, play(GameState::new(), &[3, 3, 2]));
+        let fixture = GameState::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/white_wins_in_one.txt")).unwrap();
+        assert_eq!(fixture, play(GameState::new(), &[0, 6, 1, 6, 2, 6]));
+
+        let error = |text : &str| GameState::parse_text(text).unwrap_err();
+        assert!(error("").contains("no board"));
+        assert!(error("...\n.x.\nX").starts_with("line 2:"));
+        assert!(error("...\n..\nX").starts_with("line 2:"));
+        assert!(error(".X.\n...\nX").starts_with("line 1:"));
+        assert!(error("...\n...\nwhite").starts_with("line 3:"));
+        assert!(GameState::load("no/such/fixture.txt").unwrap_err().starts_with("no/such/fixture.txt"));
+    }
+
+    #[test]
+    fn from_grid_builds_the_position(){
+        let rows = grid(&[".......", ".......", ".......", ".......", "...o...", "..xx..."]);
+        let state = GameState::from_grid(&rows, Color::Black).unwrap();
+        let mut expected = play(GameState::new(), &[3, 3, 2]);
+        assert_eq!(state, expected);
+        expected.player = Color::White;
+        assert_eq!(GameState::try_from((rows, Color::White)), Ok(expected));
+
+        let small = GameState::from_grid_with_variant(&grid(&["...", "x.o"]), Color::White, Variant::PopOut).unwrap();
+        assert_eq!((small.width(), small.height(), small.variant()), (3, 2, Variant::PopOut));
+        assert_eq!(small.cell(0, 1), Some(Color::White));
+        assert_eq!(small.cell(2, 1), Some(Color::Black));
+        assert_eq!(small.ply(), 2);
+    }
+
+    #[test]
+    fn from_grid_rejects_impossible_grids(){
+        assert_eq!(GameState::from_grid(&[], Color::White), Err(GridError::Size{ width : 0, height : 0 }));
+        assert_eq!(GameState::from_grid(&grid(&["........."]), Color::White), Err(GridError::Size{ width : 9, height : 1 }));
+        assert_eq!(GameState::from_grid(&grid(&["....", "...", "...."]), Color::White), Err(GridError::Ragged{ row : 1 }));
+        assert_eq!(GameState::from_grid(&grid(&["..x.", "....", "...."]), Color::White), Err(GridError::Floating{ column : 2, row : 0 }));
+        assert!(GridError::Floating{ column : 2, row : 0 }.to_string().contains("column 2"));
+    }
+
+    #[test]
+    fn mirrored_reflects_the_columns(){
+        assert_eq!(GameState::new().mirrored(), GameState::new());
+        assert_eq!(play(GameState::new(), &[0, 1]).mirrored(), play(GameState::new(), &[6, 5]));
+        assert_eq!(play(GameState::new(), &[3, 3]).mirrored(), play(GameState::new(), &[3, 3]));
+        let small = play(GameState::with_size(4, 4).unwrap(), &[0]);
+        assert_eq!(small.mirrored(), play(GameState::with_size(4, 4).unwrap(), &[3]));
+        assert_eq!(small.mirrored().mirrored(), small);
+    }
+
+    #[test]
+    fn mirror_images_share_a_canonical_form(){
+        let left = play(GameState::new(), &[0, 1, 1]);
+        assert_eq!(left.canonical(), left.mirrored().canonical());
+        assert!(left.canonical() == left || left.canonical() == left.mirrored());
+        assert_eq!(CanonicalState(left), CanonicalState(left.mirrored()));
+        assert!(CanonicalState(left) != CanonicalState(play(GameState::new(), &[0, 1, 2])));
+        let mut keys = HashSet::new();
+        keys.insert(CanonicalState(left));
+        assert!(keys.contains(&CanonicalState(left.mirrored())));
+        assert_eq!(GameState::new().canonical(), GameState::new());
+        //the first column that differs decides, and an empty cell comes before a piece
+        assert_eq!(play(GameState::new(), &[0]).canonical(), play(GameState::new(), &[6]));
+        assert_eq!(play(GameState::new(), &[5, 1, 1]).canonical(), play(GameState::new(), &[1, 5, 5]));
+    }
+
+    #[test]
+    fn move_count_follows_the_game(){
+        let state = play(GameState::new(), &[3, 3, 2]);
+        assert_eq!((state.ply(), state.move_count()), (3, 3));
+        assert_eq!(GameState::new().move_count(), 0);
+        //an illegal move is not played
+        assert_eq!(play(GameState::new(), &[9]).move_count(), 0);
+
+        //a pop is a move that takes a piece away
+        let popped = play(GameState::with_variant(Variant::PopOut), &[3, 4]).place(&Move::pop(3, Color::White));
+        assert_eq!((popped.ply(), popped.move_count()), (1, 3));
+        let mut undone = popped;
+        undone.unapply(&Move::pop(3, Color::White));
+        assert_eq!(undone.move_count(), 2);
+    }
+
+    #[test]
+    fn move_count_is_not_part_of_the_key(){
+        //after a pop the same pieces can be reached with more moves, it is still the same node
+        let mut state = play(GameState::new(), &[3, 3]);
+        let fewer = state;
+        state.moves_played += 2;
+        assert_eq!(state, fewer);
+        assert_eq!(hash_of(&state), hash_of(&fewer));
+    }
+
+    #[test]
+    fn legal_moves_are_the_player_to_move_s(){
+        let state = play(GameState::with_variant(Variant::PopOut), &[0, 1]);
+        assert_eq!(state.legal_moves(), state.legal_moves_for(Color::White));
+        assert!(state.legal_moves().iter().all(|mv| mv.color == Color::White));
+        //black may pop its own piece in column 1 but not white's in column 0
+        let black_pops = state.legal_moves_for(Color::Black).into_iter().filter(|mv| mv.kind == MoveKind::Pop).collect::<Vec<_>>();
+        assert_eq!(black_pops, vec![Move::pop(1, Color::Black)]);
+    }
+
+    #[test]
+    fn last_move_won_agrees_with_win(){
+        let won = play(GameState::new(), &[0, 6, 1, 6, 2, 6, 3]);
+        assert!(won.last_move_won());
+        assert_eq!(won.win(), End::Victory(Color::White));
+        //a diagonal ending on the piece that completes it from the top
+        let diagonal = play(GameState::new(), &[0, 1, 1, 2, 2, 3, 2, 3, 3, 6, 3]);
+        assert!(diagonal.last_move_won());
+        assert_eq!(diagonal.win(), End::Victory(Color::White));
+        //a win that is not the last move's
+        let later = play(GameState::new(), &[0, 6, 1, 6, 2, 6, 3, 5]);
+        assert!(!later.last_move_won());
+        assert!(!play(GameState::new(), &[0, 6, 1, 6, 2, 6]).last_move_won());
+        assert!(!GameState::new().last_move_won());
+        let mut undone = won;
+        undone.unapply(&Move::white_new(3));
+        assert!(!undone.last_move_won());
+        //last_drop is not part of the position
+        assert_eq!(undone, play(GameState::new(), &[0, 6, 1, 6, 2, 6]));
+        assert!(won.mirrored().last_move_won());
+        assert!(!play(GameState::new(), &[0, 6, 1, 6, 2, 6, 5]).mirrored().last_move_won());
+    }
+
+    #[test]
+    fn pops_never_count_as_the_last_move_winning(){
+        let state = play(GameState::with_variant(Variant::PopOut), &[0, 1]);
+        let popped = state.place(&Move::pop(0, Color::White));
+        assert!(!popped.last_move_won());
+    }
+
+    fn full_tie() -> Vec<Vec<Option<Color>>>{
+        //a full standard board without four in a row anywhere, 21 pieces each
+        grid(&["xoxxoxo", "xoxxoxo", "xoxxoxo", "oxooxox", "oxooxox", "oxooxox"])
+    }
+
+    #[test]
+    fn a_full_board_without_a_line_is_a_tie(){
+        let state = GameState::from_grid(&full_tie(), Color::White).unwrap();
+        assert!(state.legal_moves().is_empty());
+        assert!(state.winning_line().is_none());
+        assert_eq!(state.win(), End::Tie);
+        assert!(state.is_terminal());
+        //the last piece in makes the tie
+        let mut almost = full_tie();
+        almost[0][0] = None;
+        let last = GameState::from_grid(&almost, Color::White).unwrap();
+        assert_eq!(last.win(), End::Ongoing);
+        assert_eq!(last.legal_moves(), vec![Move::white_new(0)]);
+        assert_eq!(last.place(&Move::white_new(0)).win(), End::Tie);
+    }
+
+    #[test]
+    fn a_full_pop_out_board_plays_on(){
+        let state = GameState::from_grid_with_variant(&full_tie(), Color::White, Variant::PopOut).unwrap();
+        assert_eq!(state.win(), End::Ongoing);
+        assert!(state.legal_moves().iter().all(|mv| mv.kind == MoveKind::Pop));
+        assert_eq!(state.legal_moves().len(), 3);
+    }
+
+    #[test]
+    fn with_player_only_changes_the_side_to_move(){
+        let state = play(GameState::new(), &[3, 3, 2]);
+        let flipped = state.with_player(Color::White);
+        assert_eq!(state.player, Color::Black);
+        assert_eq!(flipped.player, Color::White);
+        assert_eq!(flipped, state.flip_player());
+        assert_eq!(flipped.pieces, state.pieces);
+        assert_eq!(flipped.move_count(), state.move_count());
+        assert_ne!(flipped, state);
+        assert_ne!(hash_of(&flipped), hash_of(&state));
+        assert_eq!(state.with_player(Color::Black), state);
+        assert_eq!(hash_of(&state.flip_player().flip_player()), hash_of(&state));
+        assert!(flipped.legal_moves().iter().all(|mv| mv.color == Color::White));
+    }
+
+    #[test]
+    fn zobrist_keys_follow_equality(){
+        //transpositions share a key, while the player to move, the size and the variant all change it
+        let state = play(GameState::new(), &[3, 2, 4]);
+        assert_eq!(state.zobrist(), play(GameState::new(), &[4, 2, 3]).zobrist());
+        assert_ne!(state.zobrist(), state.flip_player().zobrist());
+        assert_ne!(state.zobrist(), play(GameState::new(), &[3, 2, 5]).zobrist());
+        assert_ne!(GameState::new().zobrist(), GameState::with_size(6, 6).unwrap().zobrist());
+        assert_ne!(GameState::new().zobrist(), GameState::with_variant(Variant::PopOut).zobrist());
+        //popping white's only piece leaves the same key as never having dropped it
+        let mut popped = GameState::with_variant(Variant::PopOut);
+        popped.place_mut(&Move::new(0, Color::White));
+        popped.place_mut(&Move::new(6, Color::Black));
+        popped.place_mut(&Move::pop(0, Color::White));
+        let dropped = GameState::with_variant(Variant::PopOut).place(&Move::new(6, Color::Black)).with_player(Color::Black);
+        assert_eq!(popped.zobrist(), dropped.zobrist());
+    }
+
+    #[test]
+    fn legal_moves_into_reuses_the_buffer(){
+        let state = play(GameState::with_variant(Variant::PopOut), &[0, 0, 0, 0, 0, 0, 1]);
+        let mut moves = vec![Move::new(6, Color::Black); 20];
+        state.legal_moves_into(&mut moves);
+        assert_eq!(moves, state.legal_moves());
+        state.legal_moves_for_into(Color::White, &mut moves);
+        assert_eq!(moves, state.legal_moves_for(Color::White));
+        assert!(moves.contains(&Move::pop(1, Color::White)) && !moves.contains(&Move::new(0, Color::White)));
+    }
+
+    #[test]
+    fn layout_keys_ignore_the_player(){
+        let state = play(GameState::new(), &[3, 2, 4]);
+        assert_eq!(state.layout_key(), state.flip_player().layout_key());
+        assert_ne!(state.zobrist(), state.flip_player().zobrist());
+        assert_eq!(state.layout_key(), play(GameState::new(), &[4, 2, 3]).layout_key());
+        //the colors of the pieces still count
+        let swapped = play(GameState::new(), &[2, 3, 4]);
+        assert_ne!(state.layout_key(), swapped.layout_key());
+        assert_eq!(GameState::new().layout_key(), GameState::new().zobrist());
+    }
+
+    #[test]
+    fn cells_go_in_reading_order(){
+        let state = play(GameState::with_size(5, 4).unwrap(), &[0, 4]);
+        let cells = state.cells().collect::<Vec<_>>();
+        assert_eq!(cells.len(), 20);
+        assert_eq!(cells[0], (0, 0, None));
+        assert_eq!(cells[4], (0, 4, None));
+        assert_eq!(cells[5], (1, 0, None));
+        assert_eq!(&cells[15..], &[(3, 0, Some(Color::White)), (3, 1, None), (3, 2, None), (3, 3, None), (3, 4, Some(Color::Black))]);
+        assert!(cells.iter().all(|&(row, column, tile)| state.cell(column, row) == tile));
+        assert_eq!(state.cells().filter(|&(_, _, tile)| tile.is_some()).count(), state.ply());
+    }
+
+    #[test]
+    fn planes_are_from_the_player_to_move_s_side(){
+        //white then black on the bottom row of a 5 by 4 board, white to move
+        let state = play(GameState::with_size(5, 4).unwrap(), &[0, 4]);
+        let planes = state.to_planes(true);
+        assert_eq!(planes.len(), 60);
+        assert_eq!(planes[15], 1f32);
+        assert_eq!(planes[20 + 19], 1f32);
+        assert_eq!(planes[..40].iter().sum::<f32>(), 2f32);
+        assert!(planes[40..].iter().all(|&value| value == 1f32));
+        //black to move sees the same pieces with the planes swapped
+        let flipped = state.flip_player().to_planes(true);
+        assert_eq!((flipped[19], flipped[20 + 15]), (1f32, 1f32));
+        assert!(flipped[40..].iter().all(|&value| value == 0f32));
+        assert_eq!(state.to_planes(false), planes[..40].to_vec());
+    }
+
+    #[test]
+    fn diff_lists_the_new_pieces(){
+        let state = play(GameState::new(), &[3, 3]);
+        assert_eq!(state.diff(&state.place(&Move::white_new(2))), vec![(2, 5, Color::White)]);
+        assert_eq!(state.diff(&play(state, &[2, 4, 3])), vec![(3, 3, Color::White), (2, 5, Color::White), (4, 5, Color::Black)]);
+        assert!(state.diff(&state).is_empty());
+        assert!(state.place(&Move::white_new(2)).diff(&state).is_empty());
+        //popping white's piece from under black's moves black's down a row
+        let popped = play(GameState::with_variant(Variant::PopOut), &[3, 3]);
+        assert_eq!(popped.diff(&popped.place(&Move::pop(3, Color::White))), vec![(3, 5, Color::Black)]);
+    }
+
+    #[test]
+    fn handicap_pieces_stack_up(){
+        let state = GameState::new().handicap(Color::Black, &[3, 3, 0]).unwrap();
+        assert_eq!(state.player, Color::White);
+        assert_eq!((state.cell(3, 5), state.cell(3, 4), state.cell(0, 5)), (Some(Color::Black), Some(Color::Black), Some(Color::Black)));
+        assert_eq!(state.ply(), 3);
+        assert!(!state.last_move_won());
+        assert_eq!(GameState::new().handicap(Color::White, &[]), Ok(GameState::new()));
+        assert_eq!(GameState::new().handicap(Color::Black, &[7]), Err(GridError::Unplayable{ column : 7 }));
+        assert_eq!(GameState::new().handicap(Color::Black, &[1; 7]), Err(GridError::Unplayable{ column : 1 }));
+        assert_eq!(GameState::new().handicap(Color::Black, &[0, 1, 2, 3]), Err(GridError::Finished));
+    }
 }
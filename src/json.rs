@@ -0,0 +1,234 @@
+use game_state;
+use monte_carlo;
+use serde::{Serialize, Deserialize};
+use serde_json;
+use std::fs;
+use std::path::Path;
+
+//a position is sent and returned as
+//{"width" : 7, "height" : 6, "variant" : "standard", "to_move" : "white", "rows" : [".......", ...]}
+//rows go from the top of the board to the bottom, one character per column:
+//'.' is empty, 'w' is a white piece and 'b' is a black piece
+//width, height and variant can be left out for a standard 7 by 6 board, variant is "standard" or "pop_out"
+//to_move is "white" or "black"
+//
+//handle_request takes a position and answers with the engine's move
+//{"column" : 3, "pop" : false, "win_estimate" : 0.61}
+//column counts from 0 on the left, pop is true when the bottom piece is popped instead of a piece dropped,
+//and win_estimate is the win-or-tie rate of the side to move
+//
+//a saved game is
+//{"start" : {...}, "moves" : "4453", "position" : {...}}
+//with start and position in the format above, where the game began and where it stands now, and the moves
+//in GameRecord's notation. position is only there for people and other programs, the moves decide the game
+
+#[derive(Serialize, Deserialize)]
+struct Position{
+    #[serde(default = "standard_width")]
+    width : usize,
+    #[serde(default = "standard_height")]
+    height : usize,
+    #[serde(default = "standard_variant")]
+    variant : String,
+    to_move : String,
+    rows : Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedGame{
+    start : Position,
+    moves : String,
+    position : Position
+}
+
+#[derive(Serialize, Deserialize)]
+struct Reply{
+    column : usize,
+    pop : bool,
+    win_estimate : f64
+}
+
+fn standard_width() -> usize{
+    game_state::GameState::new().width()
+}
+
+fn standard_height() -> usize{
+    game_state::GameState::new().height()
+}
+
+fn standard_variant() -> String{
+    "standard".to_string()
+}
+
+fn color_from_name(name : &str) -> Result<game_state::Color, String>{
+    match name{
+        "white" => Ok(game_state::Color::White),
+        "black" => Ok(game_state::Color::Black),
+        _ => Err(format!("to_move must be \"white\" or \"black\", not {:?}", name))
+    }
+}
+
+fn color_from_cell(cell : char) -> Result<game_state::Cell, String>{
+    match cell{
+        '.' => Ok(None),
+        'w' => Ok(Some(game_state::Color::White)),
+        'b' => Ok(Some(game_state::Color::Black)),
+        _ => Err(format!("unknown cell {:?}, expected '.', 'w' or 'b'", cell))
+    }
+}
+
+pub fn state_from_json(json : &str) -> Result<game_state::GameState, String>{
+    state_from_position(serde_json::from_str(json).map_err(|error| error.to_string())?)
+}
+
+fn state_from_position(position : Position) -> Result<game_state::GameState, String>{
+    let variant = match position.variant.as_str(){
+        "standard" => game_state::Variant::Standard,
+        "pop_out" => game_state::Variant::PopOut,
+        other => return Err(format!("variant must be \"standard\" or \"pop_out\", not {:?}", other))
+    };
+    let mut grid = Vec::new();
+    for row in position.rows.iter(){
+        grid.push(row.chars().map(color_from_cell).collect::<Result<Vec<_>, _>>()?);
+    }
+    if grid.len() != position.height || grid.iter().any(|row| row.len() != position.width){
+        return Err(format!("expected {} rows of {} cells", position.height, position.width));
+    }
+    let player = color_from_name(&position.to_move)?;
+    game_state::GameState::from_grid_with_variant(&grid, player, variant).map_err(|error| error.to_string())
+}
+
+pub fn state_to_json(state : &game_state::GameState) -> String{
+    serde_json::to_string(&position_of(state)).unwrap()
+}
+
+fn position_of(state : &game_state::GameState) -> Position{
+    let rows = (0..state.height()).map(|y| (0..state.width()).map(|x| match state.cell(x, y){
+        Some(game_state::Color::White) => 'w',
+        Some(game_state::Color::Black) => 'b',
+        None => '.'
+    }).collect()).collect();
+    Position{
+        width : state.width(),
+        height : state.height(),
+        variant : match state.variant(){
+            game_state::Variant::Standard => standard_variant(),
+            game_state::Variant::PopOut => "pop_out".to_string()
+        },
+        to_move : match state.player{
+            game_state::Color::White => "white",
+            game_state::Color::Black => "black"
+        }.to_string(),
+        rows
+    }
+}
+
+pub fn game_to_json(record : &game_state::GameRecord) -> String{
+    let game = SavedGame{ start : position_of(&record.start), moves : record.to_notation(), position : position_of(&record.position()) };
+    serde_json::to_string(&game).unwrap()
+}
+
+pub fn game_from_json(json : &str) -> Result<game_state::GameRecord, String>{
+    let game : SavedGame = serde_json::from_str(json).map_err(|error| error.to_string())?;
+    let record = game_state::GameRecord::from_notation(state_from_position(game.start)?, &game.moves)?;
+    if state_from_position(game.position)? != record.position(){
+        return Err("the position does not follow from the moves".to_string());
+    }
+    Ok(record)
+}
+
+pub fn save_game<P : AsRef<Path>>(record : &game_state::GameRecord, path : P) -> Result<(), String>{
+    fs::write(path, game_to_json(record)).map_err(|error| error.to_string())
+}
+
+pub fn load_game<P : AsRef<Path>>(path : P) -> Result<game_state::GameRecord, String>{
+    game_from_json(&fs::read_to_string(path).map_err(|error| error.to_string())?)
+}
+
+pub fn handle_request(request : &str, config : &monte_carlo::MctsConfig) -> Result<String, String>{
+    //searches the position in the request and replies with the chosen move
+    let state = state_from_json(request)?;
+    if state.is_terminal(){
+        return Err("the game in this position is already over".to_string());
+    }
+    let mut engine = monte_carlo::Mcts::new(state, *config);
+    let best_move = engine.search();
+    let win_estimate = if state.place(&best_move).win().is_win_for(state.player){
+        1f64
+    }
+    else{
+        engine.win_probability()
+    };
+    let reply = Reply{
+        column : best_move.x,
+        pop : best_move.kind == game_state::MoveKind::Pop,
+        win_estimate
+    };
+    Ok(serde_json::to_string(&reply).unwrap())
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn play(moves : &[usize]) -> game_state::GameState{
+        moves.iter().fold(game_state::GameState::new(), |state, &x| state.place(&game_state::Move::new(x, state.player)))
+    }
+
+    #[test]
+    fn round_trips_a_position(){
+        let state = play(&[3, 3, 2, 4, 3]);
+        assert_eq!(state_from_json(&state_to_json(&state)), Ok(state));
+        let small = game_state::GameState::with_size_and_variant(4, 5, game_state::Variant::PopOut).unwrap()
+            .place(&game_state::Move::white_new(1));
+        assert_eq!(state_from_json(&state_to_json(&small)), Ok(small));
+    }
+
+    #[test]
+    fn reads_the_documented_schema(){
+        let json = r#"{"to_move" : "black", "rows" : [".......", ".......", ".......", ".......", "...b...", "...w..."]}"#;
+        let mut expected = play(&[3, 3]);
+        expected.player = game_state::Color::Black;
+        assert_eq!(state_from_json(json), Ok(expected));
+        assert_eq!(state_from_json(json).unwrap().variant(), game_state::Variant::Standard);
+    }
+
+    #[test]
+    fn rejects_bad_positions(){
+        let rows = r#"".......", ".......", ".......", ".......", "...b...", "...w...""#;
+        assert!(state_from_json("{").is_err());
+        assert!(state_from_json(&format!(r#"{{"to_move" : "red", "rows" : [{}]}}"#, rows)).is_err());
+        assert!(state_from_json(&format!(r#"{{"to_move" : "white", "variant" : "pop", "rows" : [{}]}}"#, rows)).is_err());
+        assert!(state_from_json(&format!(r#"{{"to_move" : "white", "width" : 9, "rows" : [{}]}}"#, rows)).is_err());
+        assert!(state_from_json(r#"{"to_move" : "white", "rows" : [".......", "...w..."]}"#).is_err());
+        assert!(state_from_json(r#"{"to_move" : "white", "rows" : [".......", ".......", ".......", ".......", "...x...", "...w..."]}"#).is_err());
+        assert!(state_from_json(r#"{"to_move" : "white", "rows" : [".......", ".......", ".......", ".......", "...b...", "......"]}"#).is_err());
+        //a piece floating over an empty cell
+        assert!(state_from_json(r#"{"to_move" : "white", "rows" : [".......", ".......", ".......", "...b...", ".......", "...w..."]}"#).is_err());
+    }
+
+    #[test]
+    fn saves_and_loads_games(){
+        let record = game_state::GameRecord::from_notation(game_state::GameState::new(), "44536").unwrap();
+        let json = game_to_json(&record);
+        assert_eq!(game_from_json(&json), Ok(record.clone()));
+        //a board that disagrees with the moves is refused
+        let tampered = json.replacen("44536", "44537", 1);
+        assert!(game_from_json(&tampered).is_err());
+        let path = std::env::temp_dir().join(format!("connect_three_saved_game_{}.json", std::process::id()));
+        save_game(&record, &path).unwrap();
+        let loaded = load_game(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Ok(record));
+        assert!(load_game(&path).is_err());
+    }
+
+    #[test]
+    fn answers_with_the_winning_column(){
+        let request = state_to_json(&play(&[0, 6, 1, 6, 2, 5]));
+        let reply = handle_request(&request, &monte_carlo::MctsConfig::default()).unwrap();
+        assert_eq!(reply, r#"{"column":3,"pop":false,"win_estimate":1.0}"#);
+        let finished = state_to_json(&play(&[0, 6, 1, 6, 2, 5, 3]));
+        assert!(handle_request(&finished, &monte_carlo::MctsConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,23 @@
+pub mod game_state;
+pub mod monte_carlo;
+pub mod parallel;
+pub mod json;
+pub mod evaluation;
+pub mod solver;
+pub mod self_play;
+pub mod archive;
+pub mod bitboard;
+pub mod game;
+pub mod protocol;
+extern crate rand;
+extern crate time;
+extern crate serde;
+extern crate serde_json;
+extern crate bincode;
+
+//what most users need, so `use connect_three::*;` is enough to set up a position and search it
+//without knowing which module each part lives in. everything else stays under its module
+pub use game_state::{GameState, GameRecord, Move, MoveKind, Color, End, Variant, CanonicalState, GridError};
+pub use game::Game;
+pub use monte_carlo::{Mcts, MctsConfig, Budget, Parallelism, FinalSelection, Difficulty, Exploration, RewardScale, RolloutPolicy,
+    SearchResult, RootChild, UCTData, Outcomes, tree_search, tree_search_with, best_move, estimate_win_probability};
@@ -1,32 +1,137 @@
-pub mod game_state;
-pub mod monte_carlo;
-extern crate rand;
-extern crate time;
+extern crate connect_three;
+extern crate serde_json;
+use connect_three::*;
+use std::fs;
 use std::io;
 
 fn main() {
-    play();
+    //arena <games> [first.json] [second.json] plays two configs against each other instead, see arena
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("arena"){
+        if let Err(error) = arena(&args[2..]){
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    //--engine drops the prompt for the line protocol in connect_three::protocol, for guis and scripts
+    if args.iter().any(|arg| arg == "--engine"){
+        connect_three::protocol::run();
+        return;
+    }
+    //pass --pop-out to play the variant where you may pop your own pieces off the bottom
+    let variant = if std::env::args().any(|arg| arg == "--pop-out") {
+        Variant::PopOut
+    } else {
+        Variant::Standard
+    };
+    match game_config(&args){
+        Ok(config) => play(variant, config),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn difficulty(args : &[String]) -> Result<Difficulty, String>{
+    //--difficulty easy, medium or hard, hard when left out
+    match args.iter().position(|arg| arg == "--difficulty"){
+        Some(at) => args.get(at + 1).and_then(|name| Difficulty::from_name(name))
+            .ok_or_else(|| "--difficulty needs easy, medium or hard after it".to_string()),
+        None => Ok(Difficulty::Hard)
+    }
 }
 
-fn play(){
-    let mut board = game_state::GameState::new();
-    while !monte_carlo::victory(board.win()){
+fn flag_value(args : &[String], flag : &str) -> Result<Option<u64>, String>{
+    //the number after flag, None when flag is not there
+    match args.iter().position(|arg| arg == flag){
+        Some(at) => args.get(at + 1).and_then(|value| value.parse::<u64>().ok()).map(Some)
+            .ok_or_else(|| format!("{} needs a number after it", flag)),
+        None => Ok(None)
+    }
+}
+
+fn game_config(args : &[String]) -> Result<MctsConfig, String>{
+    //--difficulty sets how hard the engine plays, --seed N fixes every random choice the engine makes and
+    //--iterations N searches that many iterations instead of the difficulty's budget. a seeded engine on an
+    //iteration budget, which easy and medium have already, replies to the same moves with the same moves every game
+    let mut config = MctsConfig::default().with_difficulty(difficulty(args)?);
+    if let Some(seed) = flag_value(args, "--seed")?{
+        config = config.with_seed(seed);
+    }
+    if let Some(iterations) = flag_value(args, "--iterations")?{
+        config.budget = Budget::Iterations(iterations);
+    }
+    Ok(config)
+}
+
+fn play(variant : Variant, config : MctsConfig){
+    let mut board = GameState::with_variant(variant);
+    //one search for the whole game, every move re-roots it so what it learned about the reply is kept
+    let mut engine = Mcts::new(board, config);
+    loop{
         print_board(&board);
         let mut input = String::new();
         io::stdin().read_line(&mut input).expect("rip");
-        let x = input.trim().parse::<usize>().unwrap();
-        let mv = game_state::Move::white_new(x - 1);
-        board = board.place(&mv);
-        print_board(&board);
-        let best_move = monte_carlo::tree_search(board);
-        println!("{:?}", best_move);
-        board = board.place(&best_move);
+        let input = input.trim();
+        //a leading p pops the bottom piece of that column instead of dropping one
+        let mv = if let Some(column) = input.strip_prefix('p') {
+            let x = column.parse::<usize>().unwrap();
+            Move::pop(x - 1, Color::White)
+        } else {
+            let x = input.parse::<usize>().unwrap();
+            Move::white_new(x - 1)
+        };
+        let mut result = board.play(&mv);
+        engine.advance(&mv);
+        if result.is_none(){
+            print_board(&board);
+            let best_move = engine.search();
+            println!("{:?}", best_move);
+            result = board.play(&best_move);
+            engine.advance(&best_move);
+            //keep searching while the human thinks, the next search picks up where this one stops.
+            //an iteration budget, from --iterations or a lower difficulty, is a fixed amount of work that pondering would add to
+            if result.is_none() && !matches!(config.budget, Budget::Iterations(_)){
+                engine.ponder_all();
+            }
+        }
+        if let Some(result) = result{
+            print_board(&board);
+            println!("Result: {:?}", result);
+            break;
+        }
     }
-    print_board(&board);
-    println!("Result: {:?}", board.win());
     io::stdin().read_line(&mut String::new()).expect("idk");
 }
 
-fn print_board(board : &game_state::GameState){
+fn read_config(path : Option<&String>) -> Result<MctsConfig, String>{
+    //a config saved as json, where only the settings that differ from the defaults need to be written out
+    match path{
+        Some(path) => serde_json::from_str(&fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?)
+            .map_err(|error| format!("{}: {}", path, error)),
+        None => Ok(MctsConfig::default())
+    }
+}
+
+fn arena(args : &[String]) -> Result<(), String>{
+    //the game count, then the two configs, both the defaults when left out
+    let games = match args.first(){
+        Some(games) => games.parse::<usize>().map_err(|_| format!("{:?} is not a number of games", games))?,
+        None => 10
+    };
+    let quiet = |config : MctsConfig| MctsConfig{ quiet : true, ..config };
+    let first = quiet(read_config(args.get(1))?);
+    let second = quiet(read_config(args.get(2))?);
+    let summary = connect_three::self_play::arena(GameState::new(), &first, &second, games);
+    println!("first config: {} wins, {} losses, {} ties, scoring {:.3}", summary.wins, summary.losses, summary.ties, summary.score());
+    for (side, name) in ["first", "second"].iter().enumerate(){
+        println!("{}: {:.3}s and {:.0} iterations per move", name, summary.move_time(side), summary.iterations_per_move(side));
+    }
+    Ok(())
+}
+
+fn print_board(board : &GameState){
     println!("{}", board.print());
-}
\ No newline at end of file
+}
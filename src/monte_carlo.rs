@@ -1,267 +1,4622 @@
 use game_state;
 use std;
 use rand;
+use rand::{Rng, SeedableRng, StdRng};
 use time;
 use std::collections::HashSet;
 use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::thread;
+use parallel;
+use evaluation;
+use game::Game;
+use serde::{Serialize, Deserialize};
+use bincode;
+use std::fs::File;
+use std::io;
+use std::path::Path;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct UCTData{
     //represents data used by UCB1 to choose the best choice to explored
     //the win-tie field is for better statistics, not actually used
     pub wins : f64,
     pub num_plays : i32,
-    pub win_tie : i32
+    pub win_tie : i32,
+    //the visits that ended in a tie, also counted in win_tie
+    pub ties : i32,
+    //sum of every reward squared, so the spread of the results can be reported next to their mean
+    pub sum_squares : f64,
+    //visits claimed by threads that are still running a simulation below this node
+    //counted as losses by ucb1 so other threads spread out instead of piling onto the same line
+    pub virtual_loss : f64,
+    //the result of the game from this node with perfect play, once MctsConfig.solver has proven it. None while open.
+    //not saved with the tree, a loaded tree proves its results again as the search goes on
+    #[serde(skip)]
+    pub proven : Option<game_state::End>
 }
 
 impl UCTData{
-    fn new(w : f64, n : i32) -> UCTData{
+    pub fn new(w : f64, n : i32) -> UCTData{
         UCTData{
             wins : w,
             num_plays : n,
-            win_tie : 0
+            win_tie : 0,
+            ties : 0,
+            sum_squares : 0f64,
+            virtual_loss : 0f64,
+            proven : None
+        }
+    }
+
+    pub fn win_percentage(&self) -> f64{
+        self.win_tie as f64 / self.num_plays as f64
+    }
+
+    pub fn outcomes(&self) -> Outcomes{
+        //the visits split by how they ended for the player who moved into this node
+        Outcomes{
+            wins : self.win_tie - self.ties,
+            ties : self.ties,
+            losses : self.num_plays - self.win_tie,
+            visits : self.num_plays
+        }
+    }
+
+    pub fn reported_win_rate(&self, min_visits : i32) -> Option<f64>{
+        //win_percentage once there are at least min_visits visits, and at least one, to show to people.
+        //None says the move was not tried often enough to tell
+        if self.num_plays < min_visits.max(1){
+            return None;
         }
+        Some(self.win_percentage())
     }
 
-    fn win_percentage(&self) -> f64{
-        (self.win_tie as f64 / self.num_plays as f64)
+    pub fn variance(&self) -> f64{
+        //variance of the rewards, high for a sharp move that wins or loses outright, low for a solid one
+        if self.num_plays == 0{
+            return 0f64;
+        }
+        let mean = self.wins / self.num_plays as f64;
+        (self.sum_squares / self.num_plays as f64 - mean * mean).max(0f64)
     }
 }
 
-pub struct TreePolicyResult{
-    pub path : Vec<game_state::GameState>,
-    pub expanded_node : game_state::GameState
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Outcomes{
+    //see UCTData::outcomes, wins + ties + losses is always visits
+    pub wins : i32,
+    pub ties : i32,
+    pub losses : i32,
+    pub visits : i32
 }
 
-impl TreePolicyResult{
-    pub fn new(path : Vec<game_state::GameState>, 
-        expanded_node : game_state::GameState) -> TreePolicyResult{
-            TreePolicyResult{
-                path : path,
-                expanded_node : expanded_node
-            }
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Budget{
+    //think for a fixed number of seconds on every move
+    TimeSeconds(f64),
+    //seconds left on the clock for the whole game, split up by think_time_for
+    GameClock(f64),
+    //this many iterations, however long they take, shared out between the threads.
+    //together with rollouts_per_leaf it fixes how the work is split between the tree and the rollouts
+    Iterations(u64),
+    //until SearchHandle::stop for a search started by spawn_search, or until the flag from Mcts::stop_handle is set.
+    //a search nobody stops never returns
+    UntilStopped
+}
+
+impl Budget{
+    pub fn think_time(&self, root : &game_state::GameState) -> f64{
+        //the seconds a search of root may take, infinite when something other than the clock ends it
+        match *self{
+            Budget::TimeSeconds(seconds) => seconds,
+            Budget::GameClock(remaining) => think_time_for(root, remaining),
+            Budget::Iterations(_) | Budget::UntilStopped => f64::INFINITY
+        }
     }
 }
 
-fn ucb1(win_value : f64, number_played : f64, total_played : f64) -> f64{
-    //weighs exploration and expected output
-    ((2f64 * total_played.ln()) / number_played).sqrt() + win_value / number_played
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Parallelism{
+    //all threads grow one shared tree, spread out by virtual loss
+    Tree,
+    //every thread grows its own tree from the root, the trees are summed at the end
+    Root
 }
 
-pub fn victory(end : game_state::End) -> bool{
-    //simple helper function
-    match end{
-        game_state::End::Victory(_) => true,
-        game_state::End::Tie => true,
-        _ => false
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Widening{
+    //a node with n visits may have ceil(coefficient * n^exponent) children, and always at least one
+    pub coefficient : f64,
+    pub exponent : f64
+}
+
+impl Widening{
+    pub fn children(&self, visits : i32) -> usize{
+        ((self.coefficient * (visits.max(0) as f64).powf(self.exponent)).ceil() as usize).max(1)
     }
 }
 
-pub fn choose_random(possible_moves : &Vec<game_state::Move>) -> game_state::Move{
-    let random_number = rand::random::<usize>() % possible_moves.len();
-    let random_move = possible_moves[random_number].clone();
-    return random_move;
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FinalSelection{
+    //the most simulated root child, the usual choice
+    MostVisited,
+    //the root child with the largest total reward, on the scale of config.reward_scale
+    HighestWin,
+    //the root child maximizing value - A / sqrt(visits), which avoids well-valued but barely tried moves.
+    //risk averse, for when a blunder costs more than a missed chance. A is on the scale of the rewards,
+    //so on MinusOneToOne it takes twice the A to penalize as much
+    SecureChild(f64),
+    //the best valued of the root children visited nearly as often as the most visited one, within this
+    //fraction of its visits. the default, a visit or two between two moves says little about which is better
+    Robust(f64),
+    //a root child drawn at random with odds of visits^(1 / t), to play weaker on purpose. near 0 it is most visited,
+    //at 1 in proportion to the visits and above 1 closer to uniform. drawn from the selection stream, see MctsConfig.selection_seed
+    Temperature(f64)
 }
 
-pub fn run_simulation(state : game_state::GameState, player : game_state::Color) -> game_state::End{ 
-    //from a given state, it will continue to choose random legitimate options until one player wins or ties
-    let mut current_state = state;
-    while !victory(current_state.win()){
-        let current_player = current_state.player;
-        let possible_moves = state.legal_moves(current_player);
-        if possible_moves.len() < 1{
-            break;
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty{
+    //a short search without tactics that picks loosely among the moves it looked at, a beginner can win
+    Easy,
+    //a medium search that still blunders now and then by not always playing its best move
+    Medium,
+    //the full strength search, the config as it is
+    Hard
+}
+
+impl Difficulty{
+    pub fn from_name(name : &str) -> Option<Difficulty>{
+        match name{
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None
         }
-        let random_move = choose_random(&possible_moves);
-        current_state = current_state.place(&random_move);
     }
+}
 
-    current_state.win()
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Exploration{
+    //the same constant for the whole search, 2 is the textbook ucb1
+    Constant(f64),
+    //moves in a straight line from start to end over the first iterations, then stays at end
+    Linear{ start : f64, end : f64, iterations : i32 },
+    //starts at start and halves its distance to end every half_life iterations,
+    //explores broadly early and settles into exploiting, a bit like annealing
+    Exponential{ start : f64, end : f64, half_life : f64 }
 }
 
-fn get_result_value(result : game_state::End, player : game_state::Color) -> f64{
-    //returns the "reward" of each multi-armed bandit
-    //a tie is better than a loss, but not as good as a win
-    match result{
-        game_state::End::Tie => 0.5f64,
-        game_state::End::Victory(color) =>{
-            if color == player{
-                1f64
-            }
-            else{
-                0f64
-            }
-        },
-        _ => 0f64
+impl Exploration{
+    pub fn constant(&self, iteration : i32) -> f64{
+        let iteration = iteration.max(0);
+        match *self{
+            Exploration::Constant(c) => c,
+            Exploration::Linear{ start, end, iterations } => {
+                let progress = if iterations <= 0 { 1f64 } else { (iteration as f64 / iterations as f64).min(1f64) };
+                start + (end - start) * progress
+            },
+            Exploration::Exponential{ start, end, half_life } =>
+                end + (start - end) * 0.5f64.powf(iteration as f64 / half_life)
+        }
     }
 }
 
-fn get_tie_or_win(result : game_state::End, player : game_state::Color) -> i32{
-    //same function, but ties are also one. for the tie-win statistic
-    match result{
-        game_state::End::Tie => 1,
-        game_state::End::Victory(color) =>{
-            if color == player{
-                1
-            }
-            else{
-                0
-            }
-        },
-        _ => 0
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RolloutPolicy{
+    //any move, chosen at random
+    Random,
+    //any of safe_moves, chosen at random
+    Safe,
+    //wins when it can and otherwise blocks the other side's win, or else any move at random
+    Tactical,
+    //always the move closest to the center, see tie_break
+    CenterFirst,
+    //Tactical, except that a move that neither wins nor blocks is drawn with a probability in proportion to its
+    //column's weight, e.g. center_priors to play the central columns more. with no weight on any move any is drawn
+    Heavy([f64; game_state::MAX_WIDTH])
+}
+
+impl RolloutPolicy{
+    pub fn choose(&self, state : &game_state::GameState, moves : &[game_state::Move], u : f64) -> game_state::Move{
+        //which of moves, the rollout's choices in state, is played. u is a number in [0, 1] for the random choices,
+        //as in rollout_with_draws
+        let pick = |moves : &[game_state::Move]| moves[((u * moves.len() as f64) as usize).min(moves.len() - 1)];
+        let available = |found : Vec<game_state::Move>| found.into_iter().find(|mv| moves.contains(mv));
+        let tactic = || available(state.winning_moves(state.player))
+            .or_else(|| available(state.winning_moves(state.player.opponent())
+                .into_iter().map(|mv| game_state::Move{ color : state.player, ..mv }).collect()));
+        match *self{
+            RolloutPolicy::Random => pick(moves),
+            RolloutPolicy::Safe => pick(&safe_moves(state, moves.to_vec())),
+            RolloutPolicy::Tactical => tactic().unwrap_or_else(|| pick(moves)),
+            RolloutPolicy::CenterFirst => *moves.iter().max_by(|a, b| tie_break(a, b, state.width())).unwrap(),
+            RolloutPolicy::Heavy(weights) => tactic().unwrap_or_else(||{
+                let weight = |mv : &game_state::Move| weights[mv.x].max(0f64);
+                let total = moves.iter().map(weight).sum::<f64>();
+                if total <= 0f64{
+                    return pick(moves);
+                }
+                //the first move whose share of the total takes the running sum past u
+                let mut left = u * total;
+                *moves.iter().find(|mv|{
+                    left -= weight(mv);
+                    left < 0f64
+                }).unwrap_or_else(|| moves.iter().rev().find(|mv| weight(mv) > 0f64).unwrap())
+            })
+        }
     }
 }
 
-fn state_previous_player(state : &game_state::GameState) -> game_state::Color{
-    //helper function
-    //the board member player represents the player who goes next
-    match state.player{
-        game_state::Color::White => game_state::Color::Black,
-        game_state::Color::Black => game_state::Color::White,
-        _ => game_state::Color::White
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExpansionOrder{
+    //any unexplored child, chosen at random
+    Random,
+    //the unexplored child closest to the center, see tie_break
+    CenterFirst,
+    //the unexplored child the heuristic evaluation likes best for the player making the move
+    Evaluation,
+    //the unexplored child in the column with the highest prior probability, a random one of them on a tie.
+    //a tight budget then spends its first rollouts on the moves most likely to be good, see center_priors
+    Priors([f64; game_state::MAX_WIDTH])
+}
+
+pub fn center_priors(width : usize) -> [f64; game_state::MAX_WIDTH]{
+    //prior probabilities for the columns of a board width wide, falling off linearly from the center
+    //the way the number of lines through a column does. columns past width get 0
+    let mut priors = [0f64; game_state::MAX_WIDTH];
+    let center = (width as f64 - 1f64) / 2f64;
+    for (x, prior) in priors.iter_mut().enumerate().take(width){
+        *prior = center + 1f64 - (x as f64 - center).abs();
     }
+    let total = priors.iter().sum::<f64>();
+    for prior in priors.iter_mut(){
+        *prior /= total;
+    }
+    priors
 }
 
-pub fn tree_search(root : game_state::GameState) -> game_state::Move{
+impl ExpansionOrder{
+    pub fn choose<R : Rng>(&self, state : &game_state::GameState, moves : &[game_state::Move], rng : &mut R) -> game_state::Move{
+        //which of moves, the unexplored children of state, is opened first
+        let center_first = |a : &&game_state::Move, b : &&game_state::Move| tie_break(a, b, state.width());
+        match *self{
+            ExpansionOrder::Random => choose_random_with(moves, rng),
+            ExpansionOrder::CenterFirst => *moves.iter().max_by(center_first).unwrap(),
+            ExpansionOrder::Evaluation => {
+                let value = |mv : &game_state::Move| evaluation::evaluate(&state.place(mv), mv.color);
+                *moves.iter().max_by(|a, b| value(a).partial_cmp(&value(b)).unwrap_or(Ordering::Equal).then(center_first(a, b))).unwrap()
+            },
+            ExpansionOrder::Priors(priors) => {
+                let best = moves.iter().map(|mv| priors[mv.x]).fold(f64::NEG_INFINITY, f64::max);
+                let favourites = moves.iter().cloned().filter(|mv| priors[mv.x] >= best).collect::<Vec<_>>();
+                choose_random_with(&favourites, rng)
+            }
+        }
+    }
+}
 
-    //keeps track of visisted states so we know if current state is a leaf
-    let mut visited_states : HashSet<game_state::GameState> = std::collections::HashSet::new();
-    visited_states.insert(root);
-    let mut statistics : HashMap<game_state::GameState, UCTData> = HashMap::new();
-    statistics.insert(root, UCTData::new(0f64, 0));
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RewardScale{
+    //a loss is 0, a tie 0.5 and a win 1, the scale of get_result_value
+    ZeroToOne,
+    //a loss is -1, a tie 0 and a win 1
+    MinusOneToOne
+}
+
+impl RewardScale{
+    pub fn value(&self, result : game_state::End, player : game_state::Color) -> f64{
+        //the reward of result for player on this scale
+        self.from_unit(get_result_value(result, player))
+    }
+
+    pub fn value_with(&self, result : game_state::End, player : game_state::Color, tie_winner : Option<game_state::Color>) -> f64{
+        //value, with a tie scored as a win for tie_winner
+        self.from_unit(result_value(result, player, tie_winner))
+    }
+
+    pub fn loss(&self) -> f64{
+        self.from_unit(0f64)
+    }
+
+    pub fn width(&self) -> f64{
+        //how far a win is from a loss, the exploration term of ucb1 is stretched by as much
+        //so the same exploration constant explores as much on either scale. the constant sits
+        //under the square root, so selection multiplies it by the square of this
+        self.from_unit(1f64) - self.from_unit(0f64)
+    }
+
+    pub fn from_unit(&self, reward : f64) -> f64{
+        //a reward on the 0 to 1 scale moved onto this one
+        match *self{
+            RewardScale::ZeroToOne => reward,
+            RewardScale::MinusOneToOne => 2f64 * reward - 1f64
+        }
+    }
+
+    pub fn to_unit(&self, reward : f64) -> f64{
+        //a reward on this scale moved back onto the 0 to 1 scale, e.g. to report it as a probability
+        match *self{
+            RewardScale::ZeroToOne => reward,
+            RewardScale::MinusOneToOne => (reward + 1f64) / 2f64
+        }
+    }
+}
+
+//read from json, e.g. for the arena, fields left out keep their defaults
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MctsConfig{
+    pub budget : Budget,
+    //0, the default, uses every core the machine reports, see thread_count. 1 searches on the calling thread alone.
+    //the parallel searches leave out solver, confidence_target, time_phases and history_weight, so setting any of
+    //them searches on one thread whatever this says, as does a search under Mcts::restrict. with_seed sets it to 1
+    pub threads : usize,
+    //how the threads share the work when there is more than one
+    pub parallelism : Parallelism,
+    //how many losing visits a thread adds to each node on its path while its simulation runs
+    pub virtual_loss : f64,
+    //deepen promising lines before opening every child, for boards with a large branching factor
+    pub progressive_widening : Option<Widening>,
+    //how the move is picked from the root's children once the search is over
+    pub final_selection : FinalSelection,
+    //the exploration constant of ucb1, which can change as the search goes on
+    pub exploration : Exploration,
+    //the stages search goes through, in this order, each can be turned off on its own:
+    //the opening book, then an immediate win or forced block, then the tree search itself
+    pub use_book : bool,
+    pub use_tactics : bool,
+    pub use_search : bool,
+    //pair every rollout with a mirrored one that makes the opposite random choices and back up both
+    pub antithetic : bool,
+    //when an iteration runs more than one rollout, back them up as one visit with their mean reward
+    //instead of one visit each, see rollout_rewards
+    pub aggregate_rollouts : bool,
+    //stop thinking early once the best move wins or ties at least this often, the budget becomes a cap.
+    //only checked by single threaded searches
+    pub confidence_target : Option<f64>,
+    //tree_search keeps its taunts to itself, e.g. in tests. searches on a Budget::Iterations never taunt,
+    //a fixed number of iterations is for tests and harnesses rather than someone playing
+    pub quiet : bool,
+    //search only one of every pair of mirrored root moves when the root is symmetric
+    pub fold_symmetry : bool,
+    //measure how long each phase of an iteration takes, reported in SearchResult.
+    //only single threaded searches are measured
+    pub time_phases : bool,
+    //how many moves below the root selection may go before it rolls out from where it is,
+    //even from a node with children left to open. depths under 1 count as 1, the root's children
+    pub max_depth : Option<usize>,
+    //seeds for the random choices, one for which unexplored child selection opens and one for the rollouts,
+    //so either can be held fixed while the other varies. None draws a fresh seed.
+    //with more threads each derives its own from these, see parallel::thread_seed, which replays
+    //root parallel searches with an iteration budget but not tree parallel ones
+    pub selection_seed : Option<u64>,
+    pub rollout_seed : Option<u64>,
+    //rollouts skip moves that hand the opponent a win on the next turn, see safe_moves
+    pub safe_rollouts : bool,
+    //rollouts stop as soon as the side to move has a move that wins on the spot and score it as that win,
+    //which is the result playing it out would give once the win is taken, see RolloutOptions
+    pub stop_at_win : bool,
+    //a node's first visit adds all of its children to the tree at once, with no visits yet, rather than
+    //one child per visit. selection then tries each of those children once before ucb1 chooses between them
+    pub full_expansion : bool,
+    //the rewards backed up into UCTData.wins. ucb1 and the final selections work on either scale,
+    //but SecureChild's penalty is in the units of the scale
+    pub reward_scale : RewardScale,
+    //how many iterations run between looks at the clock, the stop flag and the confidence target.
+    //larger saves a little time per iteration and makes the search slower to notice it should stop, 0 counts as 1
+    pub check_interval : usize,
+    //which unexplored child selection opens when a node has several
+    pub expansion_order : ExpansionOrder,
+    //self_play gives up a game for the player whose best move has won or tied less often than this
+    //for self_play::RESIGN_PLIES of their moves in a row. None plays every game out
+    pub resign_threshold : Option<f64>,
+    //moves with fewer visits report no win rate in root_children, iter_search and explanations,
+    //a move that won both of its two tries is not a 100% move
+    pub min_visits_for_winrate : i32,
+    //how many heuristic evaluations Mcts::evaluate remembers, 0 turns the cache off, see EvalCache
+    pub eval_cache_size : usize,
+    //debug builds panic on any move the search is about to play, select or roll out that is not legal
+    //for the side to move, see check_legal. release builds skip the check
+    pub validate : bool,
+    //tree_search and search_traced report the wins, ties and losses of the moves they talk about,
+    //not just how often they won or tied, see UCTData::outcomes
+    pub report_outcomes : bool,
+    //when every root move the search tried has won or tied less often than this, the game is taken as lost and
+    //the move swindle_move picks is played instead of the final selection's, dragging the game out so the opponent
+    //gets chances to go wrong. None always plays the final selection's move
+    pub swindle : Option<f64>,
+    //the player to move at the root scores a tie like a win, to play for the draw when a draw is all they need.
+    //their opponent still scores it as a tie, see result_value
+    pub ties_as_wins : bool,
+    //a position and its mirror image share their statistics, see node_key. the search looks at a position's
+    //children the same way whether it reached it or its mirror, so only boards whose rules are the same left to right
+    //may use it, which is all of them for now
+    pub mirror_keys : bool,
+    //how many rollouts every iteration runs from the node it selects, pairs of them with antithetic.
+    //more makes each visit's estimate better at the cost of fewer visits, see aggregate_rollouts. 0 counts as 1
+    pub rollouts_per_leaf : usize,
+    //the most moves a rollout plays before it gives up and scores a tie, see RolloutOptions::move_cap.
+    //None uses the default cap, which standard games never reach
+    pub max_rollout_moves : Option<usize>,
+    //how much of a leaf's value comes from the heuristic evaluation rather than its rollouts,
+    //lambda * evaluation + (1 - lambda) * rollout, see Blend. 0 uses the rollouts alone
+    pub lambda : f64,
+    //an exponentially weighted backup, each visit moves a node's value this fraction of the way to its reward
+    //instead of averaging all of them, so the later and better informed rollouts count for more. None averages
+    pub backup_alpha : Option<f64>,
+    //how the opponent of the side to move at the root plays in rollouts, to search for the moves that beat
+    //a known, maybe weak, opponent rather than the best play. the side to move keeps the usual random moves.
+    //None plays both sides the same
+    pub opponent_policy : Option<RolloutPolicy>,
+    //how both sides play in rollouts, e.g. RolloutPolicy::Heavy to see the wins and blocks a random playout
+    //misses. opponent_policy still plays for the opponent when it is set. None chooses uniformly at random
+    pub rollout_policy : Option<RolloutPolicy>,
+    //after searching, work out from the tree whether the root's result is already certain, see Mcts::proven_value.
+    //a proven win or tie is then played whatever the statistics say, and SearchResult.proven is set
+    pub prove : bool,
+    //progressive history, a bonus of weight * the move's history value / (visits + 1) added to each child's ucb1 score.
+    //the history value is what the move has scored wherever the search played it, see HistoryTable, so it helps
+    //tell apart children with few visits and fades as their own statistics take over. None leaves it out
+    pub history_weight : Option<f64>,
+    //mcts-solver: every iteration marks the nodes whose result the tree settles, a terminal position, a child the
+    //player to move wins with, or children that are all proven. selection stops at proven nodes and backs up their
+    //result instead of a rollout, never walks into a proven loss, and the search ends once the root is proven.
+    //a proven win at the root is played and a proven loss only when every move is one. the search runs on one thread
+    pub solver : bool
+}
 
-    let current_time = time::precise_time_s();
-    //temp
-    while time::precise_time_s() - current_time < 3.5f64{
-        let current_state = root;
+impl MctsConfig{
+    pub fn thread_count(&self) -> usize{
+        //how many threads a search runs on, falling back to 1 when the core count cannot be found out
+        if self.needs_one_thread(){
+            return 1;
+        }
+        if self.threads > 0{
+            return self.threads;
+        }
+        thread::available_parallelism().map_or(1, |cores| cores.get())
+    }
 
-        //selection
-        let selected_state = tree_policy(&current_state, &visited_states, &statistics);
+    pub fn tie_winner<G : Game>(&self, root : &G) -> Option<game_state::Color>{
+        //the player a tie counts as a win for in a search from root, see ties_as_wins
+        if self.ties_as_wins { Some(root.current_player()) } else { None }
+    }
 
-        //expand
-        if !visited_states.contains(&selected_state.expanded_node){
-            statistics.insert(selected_state.expanded_node, UCTData::new(0f64, 0));
-            visited_states.insert(selected_state.expanded_node);
+    pub fn iteration_limit(&self) -> Option<u64>{
+        //the most iterations a search may run, None when only the clock stops it
+        match self.budget{
+            Budget::Iterations(iterations) => Some(iterations),
+            _ => None
         }
+    }
+
+    fn needs_one_thread(&self) -> bool{
+        //options only the single threaded loop carries out, the parallel searches would quietly leave them out
+        self.confidence_target.is_some() || self.time_phases || self.history_weight.is_some() || self.solver
+    }
 
-        //simulate
-        let result = run_simulation(selected_state.expanded_node, root.player);
+    pub fn check_every(&self) -> u64{
+        //check_interval, at least 1
+        self.check_interval.max(1) as u64
+    }
 
-        //backpropogate
-        back_propogate(result, &mut statistics, &selected_state.path);
+    pub fn with_seed(self, seed : u64) -> MctsConfig{
+        //this config with both random streams fixed by one seed, so a search on an iteration budget plays the same
+        //move every time. the rollout seed is scrambled so the two streams do not draw the same numbers.
+        //threads sharing a tree interleave their iterations differently on every run, so it searches on one thread
+        MctsConfig{ selection_seed : Some(seed), rollout_seed : Some(seed ^ 0x9e37_79b9_7f4a_7c15), threads : 1, ..self }
     }
 
-    let possible_moves = root.legal_moves(root.player).into_iter().map(|x| (x, statistics.get(&root.place(&x)).unwrap())).collect::<Vec<_>>();
-    let best_move = optimal_move_most_visisted(&possible_moves);
-    let data = statistics.get(&root.place(&best_move)).unwrap();
-    println!("Puny human, I have thought through {} variations of this pitiful game, and won or tied in {}% of them", data.num_plays, data.win_percentage() * 100f64);
-    return best_move;
+    pub fn with_difficulty(self, difficulty : Difficulty) -> MctsConfig{
+        //this config held back to difficulty, by capping the iterations and sampling the move played
+        match difficulty{
+            Difficulty::Easy => MctsConfig{ budget : Budget::Iterations(200), final_selection : FinalSelection::Temperature(1f64),
+                use_book : false, use_tactics : false, ..self },
+            Difficulty::Medium => MctsConfig{ budget : Budget::Iterations(3000), final_selection : FinalSelection::Temperature(0.25f64),
+                use_book : false, ..self },
+            Difficulty::Hard => self
+        }
+    }
 }
 
-fn optimal_move_highest_win(possible_moves : &Vec<(game_state::Move, &UCTData)>) -> game_state::Move{
-    //selects the highest winning node as optimal
-    let mut highest_win = 0f64;
-    let mut best_move = game_state::Move::white_new(0);
-    for &(mv, data) in possible_moves{
-        if data.wins > highest_win{
-            highest_win = data.wins;
-            best_move = mv;
+//how often think looks at the confidence target, and how many visits the best move needs before it is believed
+const CONFIDENCE_CHECK_EVERY : u64 = 256;
+const MIN_CONFIDENT_VISITS : i32 = 200;
+
+impl Default for MctsConfig{
+    fn default() -> Self{
+        MctsConfig{
+            budget : Budget::TimeSeconds(3.5f64),
+            threads : 0,
+            parallelism : Parallelism::Tree,
+            virtual_loss : 1f64,
+            progressive_widening : None,
+            final_selection : FinalSelection::Robust(ROBUST_MARGIN),
+            exploration : Exploration::Constant(2f64),
+            use_book : true,
+            use_tactics : true,
+            use_search : true,
+            antithetic : false,
+            aggregate_rollouts : false,
+            confidence_target : None,
+            quiet : false,
+            fold_symmetry : true,
+            time_phases : false,
+            max_depth : None,
+            selection_seed : None,
+            rollout_seed : None,
+            safe_rollouts : false,
+            stop_at_win : false,
+            full_expansion : false,
+            reward_scale : RewardScale::ZeroToOne,
+            check_interval : 1,
+            expansion_order : ExpansionOrder::Random,
+            resign_threshold : None,
+            min_visits_for_winrate : 10,
+            eval_cache_size : 1 << 16,
+            validate : false,
+            report_outcomes : false,
+            swindle : None,
+            ties_as_wins : false,
+            mirror_keys : false,
+            rollouts_per_leaf : 1,
+            max_rollout_moves : None,
+            lambda : 0f64,
+            backup_alpha : None,
+            opponent_policy : None,
+            rollout_policy : None,
+            prove : false,
+            history_weight : None,
+            solver : false
         }
     }
-    return best_move;
 }
 
-fn optimal_move_most_visisted(possible_moves : &Vec<(game_state::Move, &UCTData)>) -> game_state::Move{
-    //selects the most visited node as optimal
-    let mut most_played = 0;
-    let mut best_move = game_state::Move::white_new(0);
-    for &(mv, data) in possible_moves{
-        if data.num_plays > most_played{
-            most_played = data.num_plays;
-            best_move = mv;
+//how close to the most visited child's visits a child must come to be in the running for Robust
+pub const ROBUST_MARGIN : f64 = 0.05;
+
+//a known good move for some positions, played without searching
+pub type OpeningBook = HashMap<game_state::GameState, game_state::Move>;
+
+pub fn opening_book() -> OpeningBook{
+    //the center column is the only winning first move on the standard board, and the best reply to it is the center as well
+    let mut book = HashMap::new();
+    let start = game_state::GameState::new();
+    book.insert(start, game_state::Move::white_new(3));
+    let center = start.place(&game_state::Move::white_new(3));
+    book.insert(center, game_state::Move::black_new(3));
+    book
+}
+
+pub fn node_key<G : Game>(state : &G, config : &MctsConfig) -> G{
+    //the key state's statistics are kept under: its canonical form with config.mirror_keys, see
+    //game_state::CanonicalState, and state itself otherwise. legal moves are always cached under state itself
+    if config.mirror_keys { state.canonical() } else { *state }
+}
+
+pub trait NodeStore<G : Game = game_state::GameState>{
+    //what selection needs to know about the tree, so the same policy runs on plain and concurrent statistics
+    fn visited(&self, state : &G) -> bool;
+    fn data(&self, state : &G) -> Option<UCTData>;
+    //legal moves worked out when the node was expanded, None means selection has to compute them
+    fn legal_moves(&self, _state : &G) -> Option<&[G::Move]>{
+        None
+    }
+    //the mean reward of mv wherever it was played, see HistoryTable
+    fn history(&self, _mv : &G::Move) -> Option<f64>{
+        None
+    }
+}
+
+//what every move has scored for the player making it, aggregated over all the nodes it was played from.
+//moves are kept apart by color and kind as well as column, a column that wins for one player may not for the other
+#[derive(Debug, Clone)]
+pub struct HistoryTable<M = game_state::Move>{
+    moves : HashMap<M, (f64, u32)>
+}
+
+impl<M : Copy + Eq + Hash> Default for HistoryTable<M>{
+    fn default() -> Self{
+        HistoryTable{ moves : HashMap::new() }
+    }
+}
+
+impl<M : Copy + Eq + Hash> HistoryTable<M>{
+    pub fn new() -> Self{
+        HistoryTable::default()
+    }
+
+    pub fn record(&mut self, mv : M, reward : f64){
+        let entry = self.moves.entry(mv).or_insert((0f64, 0));
+        entry.0 += reward;
+        entry.1 += 1;
+    }
+
+    pub fn value(&self, mv : &M) -> Option<f64>{
+        self.moves.get(mv).map(|&(total, count)| total / count as f64)
+    }
+
+    pub fn visits(&self, mv : &M) -> u32{
+        self.moves.get(mv).map_or(0, |&(_, count)| count)
+    }
+
+    pub fn clear(&mut self){
+        self.moves.clear();
+    }
+
+    pub fn record_path<G : Game<Move = M>>(&mut self, path : &[(G, game_state::Color)], results : &[game_state::End], backup : Backup){
+        //the moves along an iteration's path get the same rewards back_propogate_all gives the nodes they lead to
+        for step in path.windows(2){
+            let (parent, (child, mover)) = (step[0].0, step[1]);
+            if let Some(mv) = parent.legal_moves().into_iter().find(|mv| parent.place(mv) == child){
+                for (reward, _, _) in rollout_rewards(results, mover, backup){
+                    self.record(mv, reward);
+                }
+            }
         }
     }
-    return best_move;
 }
 
+//the legal moves of every expanded node, they never change so they are only computed once
+//child states are not kept, they are a place away and would make every entry several times bigger
+pub type MoveCache<G = game_state::GameState> = HashMap<G, Vec<<G as Game>::Move>>;
 
+struct MapStore<'a, G : 'a + Game>{
+    visited_states : &'a HashSet<G>,
+    stats : &'a HashMap<G, UCTData>,
+    moves : Option<&'a MoveCache<G>>,
+    history : Option<&'a HistoryTable<G::Move>>
+}
 
-pub fn tree_policy(
-    current_state : &game_state::GameState,
-    visisted_states : &HashSet<game_state::GameState>,
-    stats : &HashMap<game_state::GameState, UCTData>
-    ) -> TreePolicyResult{
-    
-    //represents the states we went through to get to the selected node
-    //used for backpropogation without an actual tree structure
-    let mut path : Vec<game_state::GameState> = Vec::new();
+impl<'a, G : Game> NodeStore<G> for MapStore<'a, G>{
+    fn visited(&self, state : &G) -> bool{
+        self.visited_states.contains(state)
+    }
 
-    let mut current_node = current_state.clone();
+    fn data(&self, state : &G) -> Option<UCTData>{
+        self.stats.get(state).cloned()
+    }
 
-    loop{
+    fn legal_moves(&self, state : &G) -> Option<&[G::Move]>{
+        self.moves.and_then(|moves| moves.get(state)).map(|moves| &moves[..])
+    }
 
-        path.push(current_node);
+    fn history(&self, mv : &G::Move) -> Option<f64>{
+        self.history.and_then(|history| history.value(mv))
+    }
+}
 
-        let possible_moves = current_node.legal_moves(current_node.player);
+pub struct TreePolicyResult<G = game_state::GameState>{
+    //every state we went through, paired with the player whose move led into it
+    pub path : Vec<(G, game_state::Color)>,
+    pub expanded_node : G
+}
 
-        if possible_moves.len() < 1 || victory(current_node.win()){
-            //no legal moves or game ends
-            return TreePolicyResult::new(path, current_node);
+impl<G> TreePolicyResult<G>{
+    pub fn new(path : Vec<(G, game_state::Color)>, 
+        expanded_node : G) -> TreePolicyResult<G>{
+            TreePolicyResult{
+                path,
+                expanded_node
+            }
+    }
+}
+
+fn ucb1(win_value : f64, number_played : f64, total_played : f64, exploration : f64) -> f64{
+    //weighs exploration and expected output. exploration = 2 is the textbook sqrt(2 ln N / n).
+    //an unplayed child is always tried first, whatever the constant, rather than scoring 0 / 0
+    if number_played <= 0f64{
+        return f64::INFINITY;
+    }
+    (exploration * total_played.ln() / number_played).sqrt() + win_value / number_played
+}
+
+pub fn victory(end : game_state::End) -> bool{
+    //simple helper function
+    end.is_over()
+}
+
+pub fn choose_random(possible_moves : &[game_state::Move]) -> game_state::Move{
+    choose_random_with(possible_moves, &mut rand::thread_rng())
+}
+
+pub fn choose_random_with<M : Copy, R : Rng>(possible_moves : &[M], rng : &mut R) -> M{
+    possible_moves[rng.gen_range(0, possible_moves.len())]
+}
+
+pub struct SearchRng{
+    //the two independent sources of randomness in an iteration, see MctsConfig.selection_seed
+    pub selection : StdRng,
+    pub rollout : StdRng,
+    //what they were seeded with, drawn fresh for the seeds config leaves as None
+    pub seeds : SearchSeeds
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchSeeds{
+    pub selection : u64,
+    pub rollout : u64
+}
+
+impl SearchSeeds{
+    pub fn apply(&self, config : &MctsConfig) -> MctsConfig{
+        //config with these seeds held fixed, to replay a search
+        MctsConfig{ selection_seed : Some(self.selection), rollout_seed : Some(self.rollout), ..*config }
+    }
+}
+
+fn seeded_rng(seed : Option<u64>) -> StdRng{
+    let seed = seed.unwrap_or_else(rand::random::<u64>);
+    StdRng::from_seed(&[seed as usize, (seed >> 32) as usize])
+}
+
+impl SearchRng{
+    pub fn new(config : &MctsConfig) -> SearchRng{
+        let seeds = SearchSeeds{
+            selection : config.selection_seed.unwrap_or_else(rand::random::<u64>),
+            rollout : config.rollout_seed.unwrap_or_else(rand::random::<u64>)
+        };
+        SearchRng{ selection : seeded_rng(Some(seeds.selection)), rollout : seeded_rng(Some(seeds.rollout)), seeds }
+    }
+}
+
+pub fn safe_moves<G : Game>(state : &G, moves : Vec<G::Move>) -> Vec<G::Move>{
+    //the moves that do not let the opponent win straight away, or all of them if every move does.
+    //purely random playouts keep handing over such wins, which makes a position look worse than it is
+    let safe = moves.iter().cloned().filter(|mv| Game::legal(state, mv) && Game::place(state, mv).immediate_wins().is_empty()).collect::<Vec<_>>();
+    if safe.is_empty() { moves } else { safe }
+}
+
+pub fn no_moves_result<G : Game>(state : &G) -> game_state::End{
+    //how a rollout that finds no move to play ends. nobody can move, so unless someone already won it is a tie,
+    //even if win would call the position ongoing
+    match state.win(){
+        game_state::End::Ongoing => game_state::End::Tie,
+        result => result
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct RolloutOptions{
+    //only choose from safe_moves
+    pub safe : bool,
+    //end the playout once the side to move can win on the spot, scored as that side's win.
+    //the win is as good as played, so this only saves the moves a random playout would spend missing it
+    pub stop_at_win : bool,
+    //check every move played with check_legal
+    pub validate : bool,
+    //see MctsConfig.max_rollout_moves
+    pub max_moves : Option<usize>,
+    //the player whose moves config.opponent_policy chooses, and the policy
+    pub opponent : Option<(game_state::Color, RolloutPolicy)>,
+    //see MctsConfig.rollout_policy
+    pub rollout_policy : Option<RolloutPolicy>
+}
+
+impl RolloutOptions{
+    pub fn from_config(config : &MctsConfig) -> RolloutOptions{
+        RolloutOptions{ safe : config.safe_rollouts, stop_at_win : config.stop_at_win, validate : config.validate, max_moves : config.max_rollout_moves,
+            opponent : None, rollout_policy : config.rollout_policy }
+    }
+
+    pub fn for_search<G : Game>(config : &MctsConfig, root : &G) -> RolloutOptions{
+        //from_config, with config.opponent_policy playing for the side not to move at root
+        RolloutOptions{ opponent : config.opponent_policy.map(|policy| (root.previous_player(), policy)), ..RolloutOptions::from_config(config) }
+    }
+
+    fn is_plain(&self) -> bool{
+        //uniformly random moves to the end of the game, with nothing to check along the way
+        !self.safe && !self.stop_at_win && !self.validate && self.max_moves.is_none() && self.opponent.is_none() && self.rollout_policy.is_none()
+    }
+
+    fn policy<G : Game>(&self, state : &G) -> Option<RolloutPolicy>{
+        //the policy choosing the next move in state, None for a random choice
+        self.opponent.filter(|&(color, _)| color == state.current_player()).map(|(_, policy)| policy).or(self.rollout_policy)
+    }
+
+    pub fn move_cap<G : Game>(&self, state : &G) -> usize{
+        //a safety net so that no position, whatever a bug in the rules makes of it, keeps a rollout going forever.
+        //by default the game's own, see Game::move_cap
+        self.max_moves.unwrap_or_else(|| state.move_cap())
+    }
+
+    fn proven_win<G : Game>(&self, state : &G) -> Option<game_state::End>{
+        if self.stop_at_win && !state.immediate_wins().is_empty(){
+            Some(game_state::End::Victory(state.current_player()))
         }
-        
-        //has every possible move been explored?
-        let fully_explored = possible_moves.iter().fold(true, 
-            |acc, x| 
-            acc && visisted_states.contains(&current_node.place(x))
-        );
+        else{
+            None
+        }
+    }
+}
 
-        //if not, exploration
-        if !fully_explored {
-            //for a node with number played of 0, ucb1 returns infinity
-            //in other words unexplored child nodes are always explored at least once
-            let not_explored = possible_moves.into_iter().filter(
-                |x| !visisted_states.contains(&current_node.place(x))
-                ).collect::<Vec<_>>();
-            let random_choice = choose_random(&not_explored);
-            let chosen_node = current_node.place(&random_choice);
-            path.push(chosen_node);
-            let result = TreePolicyResult::new(path, chosen_node);
-            return result; 
+pub fn check_legal<G : Game>(validate : bool, state : &G, mv : &G::Move, source : &str){
+    //with validate on in a debug build, panics unless mv is a legal move for the player to move in state.
+    //source names what came up with the move, for the message
+    if cfg!(debug_assertions) && validate && !Game::legal(state, mv){
+        panic!("{} chose {:?}, which {:?} can not play in\n{}", source, mv, state.current_player(), Game::print(state));
+    }
+}
+
+pub fn run_simulation<G : Game>(state : G, player : game_state::Color) -> game_state::End{
+    run_simulation_with(state, player, RolloutOptions::default(), &mut rand::thread_rng())
+}
+
+pub fn run_simulation_with<G : Game, R : Rng>(state : G, _player : game_state::Color, options : RolloutOptions, rng : &mut R) -> game_state::End{
+    //from a given state, it will continue to choose random legitimate options until one player wins or ties
+    if options.is_plain(){
+        //the game's own faster rollout, a bitboard for connect four. validate keeps the checked moves below
+        if let Some(result) = state.fast_rollout(rng){
+            return result;
+        }
+    }
+    let mut current_state = state;
+    let mut possible_moves = Vec::new();
+    let mut over = current_state.is_terminal();
+    let cap = options.move_cap(&state);
+    let mut played = 0;
+    while !over{
+        if let Some(result) = options.proven_win(&current_state){
+            return result;
+        }
+        if played >= cap{
+            return game_state::End::Tie;
+        }
+        played += 1;
+        current_state.legal_moves_into(&mut possible_moves);
+        if options.safe{
+            possible_moves = safe_moves(&current_state, possible_moves);
         }
+        if possible_moves.is_empty(){
+            return no_moves_result(&current_state);
+        }
+        let random_move = match options.policy(&current_state){
+            Some(policy) => current_state.rollout_move(policy, &possible_moves, rng.gen::<f64>()),
+            None => choose_random_with(&possible_moves, rng)
+        };
+        check_legal(options.validate, &current_state, &random_move, "run_simulation");
+        current_state.place_mut(&random_move);
+        over = current_state.ended_by(&random_move);
+    }
 
-        //all child nodes have been simulated at least once, so use ucb1 to select best
+    current_state.win()
+}
+
+pub fn rollout_with_draws<G : Game, F : FnMut() -> f64>(state : G, options : RolloutOptions, draw : &mut F) -> game_state::End{
+    //a random playout where every choice comes from draw, a number in [0, 1] picking among the legal moves in order
+    //or among the safe_moves when options.safe is set
+    let mut current_state = state;
+    let mut possible_moves = Vec::new();
+    let mut over = current_state.is_terminal();
+    let cap = options.move_cap(&state);
+    let mut played = 0;
+    while !over{
+        if let Some(result) = options.proven_win(&current_state){
+            return result;
+        }
+        if played >= cap{
+            return game_state::End::Tie;
+        }
+        played += 1;
+        current_state.legal_moves_into(&mut possible_moves);
+        if options.safe{
+            possible_moves = safe_moves(&current_state, possible_moves);
+        }
+        if possible_moves.is_empty(){
+            return no_moves_result(&current_state);
+        }
+        let chosen = current_state.rollout_move(options.policy(&current_state).unwrap_or(RolloutPolicy::Random), &possible_moves, draw());
+        check_legal(options.validate, &current_state, &chosen, "rollout_with_draws");
+        current_state.place_mut(&chosen);
+        over = current_state.ended_by(&chosen);
+    }
+    current_state.win()
+}
+
+pub fn antithetic_simulations<G : Game, F : FnMut() -> f64>(state : G, options : RolloutOptions, draw : &mut F) -> (game_state::End, game_state::End){
+    //the second playout uses 1 - u wherever the first drew u, so where one leans left the other leans right
+    //the pair's errors partly cancel, which gives a lower variance estimate than two independent playouts
+    //once the first playout's draws run out the second just draws fresh ones
+    let mut draws = Vec::new();
+    let first = rollout_with_draws(state, options, &mut ||{
+        let u = draw();
+        draws.push(u);
+        u
+    });
+    let mut replay = draws.into_iter();
+    let second = rollout_with_draws(state, options, &mut || replay.next().map_or_else(&mut *draw, |u| 1f64 - u));
+    (first, second)
+}
+
+pub fn leaf_results<G : Game, R : Rng>(root : &G, state : G, config : &MctsConfig, rng : &mut R) -> Vec<game_state::End>{
+    //the config.rollouts_per_leaf rollouts, or antithetic pairs of them, that one iteration of a search of root runs from state
+    let options = RolloutOptions::for_search(config, root);
+    let mut results = Vec::new();
+    for _ in 0..config.rollouts_per_leaf.max(1){
+        if config.antithetic{
+            let (first, second) = antithetic_simulations(state, options, &mut || rng.gen::<f64>());
+            results.push(first);
+            results.push(second);
+        }
         else{
-            //sort 
-            let mut best_move = possible_moves.last().unwrap();
-            let mut best_uct = 0f64;
-            let total_played = stats.get(&current_node).unwrap().num_plays;
-            for possibility in possible_moves.iter(){
-                
-                //TODO: switch to pattern matching
-                let data = stats.get(&current_node.place(&possibility)).unwrap();
-                let uct = ucb1(data.wins, data.num_plays as f64, total_played as f64);
-                if(uct > best_uct){
-                    best_uct = uct;
-                    best_move = possibility;
-                }
-            }
-            let chosen_node = current_node.place(&best_move);
-            current_node = chosen_node;
+            results.push(run_simulation_with(state, state.current_player(), options, rng));
         }
     }
+    results
 }
 
+//the heuristic evaluation of a leaf, mixed into the rewards of its rollouts
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Blend{
+    //the weight of the evaluation, between 0 and 1
+    pub lambda : f64,
+    //evaluation::evaluate of the leaf for each player, on the 0 to 1 scale
+    pub white : f64,
+    pub black : f64
+}
 
-pub fn back_propogate(win_value : game_state::End, stats : &mut HashMap<game_state::GameState, UCTData>,
-    path : &Vec<game_state::GameState>){
-        for node in path.iter(){
-            match stats.get_mut(node){
-                Some(ref mut stat) =>{
-                    stat.wins += get_result_value(win_value, state_previous_player(&node));
-                    stat.num_plays += 1;
-                    stat.win_tie += get_tie_or_win(win_value, state_previous_player(&node));
-                }
-                None => ()
-            }
+impl Blend{
+    pub fn at<G : Game>(leaf : &G, config : &MctsConfig) -> Option<Blend>{
+        //None when config.lambda leaves the rollouts alone, so the evaluation is only paid for when it is used,
+        //or when the game has no evaluation to blend in
+        if config.lambda <= 0f64{
+            return None;
+        }
+        Some(Blend{
+            lambda : config.lambda.min(1f64),
+            white : leaf.evaluate(game_state::Color::White)?,
+            black : leaf.evaluate(game_state::Color::Black)?
+        })
+    }
+
+    pub fn mix(&self, reward : f64, mover : game_state::Color, scale : RewardScale) -> f64{
+        //reward, already on scale, blended with the evaluation for mover
+        let value = match mover{
+            game_state::Color::White => self.white,
+            game_state::Color::Black => self.black
+        };
+        self.lambda * scale.from_unit(value) + (1f64 - self.lambda) * reward
+    }
+}
+
+//how the rollout results of one iteration become visits of the nodes on its path
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Backup{
+    //see MctsConfig.aggregate_rollouts
+    pub aggregate : bool,
+    pub scale : RewardScale,
+    //see MctsConfig::tie_winner
+    pub tie_winner : Option<game_state::Color>,
+    pub blend : Option<Blend>,
+    //see MctsConfig.backup_alpha
+    pub alpha : Option<f64>
+}
+
+impl Default for Backup{
+    fn default() -> Self{
+        Backup{ aggregate : false, scale : RewardScale::ZeroToOne, tie_winner : None, blend : None, alpha : None }
+    }
+}
+
+impl Backup{
+    pub fn from_config<G : Game>(config : &MctsConfig, root : &G, leaf : &G) -> Backup{
+        Backup{
+            aggregate : config.aggregate_rollouts,
+            scale : config.reward_scale,
+            tie_winner : config.tie_winner(root),
+            blend : Blend::at(leaf, config),
+            alpha : config.backup_alpha
         }
+    }
+
+    pub fn wins_added(&self, wins : f64, num_plays : i32, reward : f64) -> f64{
+        //how much a visit worth reward adds to a node's wins. with alpha the node's value moves alpha of the way
+        //to reward, and is kept as wins so wins / num_plays still reads it. a node's first visit is its value either way
+        match self.alpha{
+            Some(alpha) if num_plays > 0 => {
+                let value = wins / num_plays as f64;
+                value + alpha * (reward - value) * (num_plays + 1) as f64
+            },
+            _ => reward
+        }
+    }
+}
+
+pub fn get_result_value(result : game_state::End, player : game_state::Color) -> f64{
+    //returns the "reward" of each multi-armed bandit
+    //a tie is better than a loss, but not as good as a win
+    if result.is_win_for(player){
+        1f64
+    }
+    else if result == game_state::End::Tie{
+        0.5f64
+    }
+    else{
+        0f64
+    }
+}
+
+pub fn result_value(result : game_state::End, player : game_state::Color, tie_winner : Option<game_state::Color>) -> f64{
+    //get_result_value, except that a tie is worth a win to tie_winner, see MctsConfig.ties_as_wins
+    if result == game_state::End::Tie && tie_winner == Some(player){
+        1f64
+    }
+    else{
+        get_result_value(result, player)
+    }
+}
+
+pub fn get_tie(result : game_state::End) -> i32{
+    //1 for a tie, for the ties statistic
+    (result == game_state::End::Tie) as i32
+}
+
+pub fn get_tie_or_win(result : game_state::End, player : game_state::Color) -> i32{
+    //same function, but ties are also one. for the tie-win statistic
+    (result.is_win_for(player) || result == game_state::End::Tie) as i32
+}
+
+pub fn state_previous_player(state : &game_state::GameState) -> game_state::Color{
+    //helper function
+    //the board member player represents the player who goes next
+    state.player.opponent()
+}
+
+pub fn think_time_for(state : &game_state::GameState, total_budget_remaining : f64) -> f64{
+    //splits what is left on the clock over the moves we still expect to make,
+    //spending more of it in the middle game and in positions with many options
+    let cells = (state.width() * state.height()) as f64;
+    let ply = state.ply() as f64;
+    let moves_left = ((cells - ply) / 2f64).max(1f64);
+
+    //0.5 on an empty or full board, 1.5 halfway through
+    let phase_weight = 0.5f64 + (std::f64::consts::PI * (ply / cells).min(1f64)).sin();
+    //1.0 when every column is open, less once columns fill up
+    let complexity = state.legal_moves().len() as f64 / state.width() as f64;
+
+    let slice = total_budget_remaining / moves_left * phase_weight * complexity.max(0.25f64);
+    //never bet more than half the remaining clock on a single move
+    slice.min(total_budget_remaining / 2f64).max(0f64)
+}
+
+pub fn tree_search(root : game_state::GameState) -> game_state::Move{
+    tree_search_with(root, &MctsConfig::default())
+}
+
+pub fn tree_search_with(root : game_state::GameState, config : &MctsConfig) -> game_state::Move{
+    let mut engine = Mcts::new(root, *config);
+    let best_move = engine.search();
+    if config.quiet || matches!(config.budget, Budget::Iterations(_)){
+        return best_move;
+    }
+    if engine.book.contains_key(&root) && engine.config.use_book{
+        println!("Puny human, I knew this opening before you were born");
+        return best_move;
+    }
+    match engine.node(&root.place(&best_move)){
+        Some(data) if config.report_outcomes => {
+            let outcomes = data.outcomes();
+            println!("Puny human, I have thought through {} variations of this pitiful game, and won {}, tied {} and lost only {} of them", outcomes.visits, outcomes.wins, outcomes.ties, outcomes.losses)
+        },
+        Some(data) => println!("Puny human, I have thought through {} variations of this pitiful game, and won or tied in {}% of them", data.num_plays, data.win_percentage() * 100f64),
+        None => println!("Puny human, did you really think I would miss that?")
+    }
+    best_move
+}
+
+pub fn tree_search_restricted(root : game_state::GameState, candidates : &[game_state::Move], config : &MctsConfig) -> game_state::Move{
+    //the best of candidates, searching nothing else at the root, see Mcts::restrict
+    let mut engine = Mcts::new(root, *config);
+    engine.restrict(candidates);
+    engine.search()
+}
+
+pub fn tree_search_as(root : game_state::GameState, color : game_state::Color, config : &MctsConfig) -> game_state::Move{
+    //what color would play here if it were their move, whoever is actually to move
+    tree_search_with(root.with_player(color), config)
+}
+
+pub fn replay_search(root : game_state::GameState, config : &MctsConfig, seeds : &SearchSeeds, iterations : u64) -> Mcts{
+    //runs a reported search again, step for step, from the SearchResult's seeds and iteration count.
+    //the original must have started from a fresh tree too, not one kept from earlier moves
+    let mut engine = Mcts::new(root, seeds.apply(config));
+    for _ in 0..iterations{
+        engine.step();
+    }
+    engine
+}
+
+pub fn prime_with(engine : &mut Mcts, lines : &[&[usize]]) -> Result<(), game_state::GridError>{
+    //adds lines of columns, played from the engine's root, to its tree before it searches, see Mcts::prime.
+    //stops at the first line that can not be played, the lines before it stay in the tree
+    for line in lines{
+        engine.prime(line)?;
+    }
+    Ok(())
+}
+
+pub fn best_move(engine : &mut Mcts, state : game_state::GameState) -> game_state::Move{
+    //the whole decision for one position: book, then win or block, then search, as engine.config allows
+    if engine.root() != state{
+        engine.set_position(state);
+    }
+    engine.search()
+}
+
+pub fn estimate_win_probability(state : game_state::GameState, config : &MctsConfig) -> f64{
+    //how likely state.player is to win or tie, for evaluation bars and the like
+    if state.is_terminal(){
+        return get_tie_or_win(state.win(), state.player) as f64;
+    }
+    let mut engine = Mcts::new(state, *config);
+    engine.think();
+    engine.win_probability()
+}
+
+pub fn most_defensive_move(root : game_state::GameState, config : &MctsConfig) -> Option<game_state::Move>{
+    //the root move after which the opponent's own search gives them the lowest chance to win or tie,
+    //see estimate_win_probability. every child is searched with the whole budget, one after another
+    let opponent_chances = |mv : &game_state::Move| estimate_win_probability(root.place(mv), config);
+    let mut best : Option<(game_state::Move, f64)> = None;
+    for mv in root.legal_moves(){
+        let chances = opponent_chances(&mv);
+        let better = best.is_none_or(|(best_move, lowest)|
+            chances < lowest || (chances == lowest && tie_break(&mv, &best_move, root.width()) == Ordering::Greater));
+        if better{
+            best = Some((mv, chances));
+        }
+    }
+    //None on a finished root, which has no move to defend with
+    best.map(|(mv, _)| mv)
+}
+
+pub fn swindle_move(state : &game_state::GameState, moves : &[game_state::Move]) -> Option<game_state::Move>{
+    //the move of moves that puts a lost game's end furthest off, as far as looking one reply ahead tells:
+    //the fewest immediate wins left to the opponent first, then the most of our own threats kept for them
+    //to overlook, then tie_break. None without moves
+    let player = state.player;
+    let opponent = state_previous_player(state);
+    let hope = |mv : &game_state::Move|{
+        let after = state.place(mv);
+        (-(after.winning_moves(opponent).len() as i32), evaluation::count_threats(&after, player))
+    };
+    moves.iter().cloned().max_by(|a, b| hope(a).cmp(&hope(b)).then(tie_break(a, b, state.width())))
+}
+
+pub struct PlyReview{
+    //one move of a finished game next to what the engine would have played instead
+    pub played : game_state::Move,
+    pub best : game_state::Move,
+    //the expected reward of the player who moved, after the best move and after the move actually played
+    pub eval_before : f64,
+    pub eval_after : f64
+}
+
+impl PlyReview{
+    pub fn is_blunder(&self, margin : f64) -> bool{
+        //the played move gave away more than margin compared to the best one
+        self.eval_before - self.eval_after > margin
+    }
+}
+
+fn mover_value(state : game_state::GameState, config : &MctsConfig) -> f64{
+    //the reward the player who just moved into state can expect from here on
+    let mover = state_previous_player(&state);
+    if state.is_terminal(){
+        return get_result_value(state.win(), mover);
+    }
+    //the opponent is assumed to find their best reply, rather than averaging over everything the search tried
+    let mut engine = Mcts::new(state, *config);
+    engine.think();
+    match engine.current_best(){
+        Some((_, data)) => 1f64 - config.reward_scale.to_unit(data.wins / data.num_plays as f64),
+        None => 0.5f64
+    }
+}
+
+pub fn review_game(moves : &[usize], config : &MctsConfig) -> Vec<PlyReview>{
+    //replays a game given as the column of every move from the start, searching each ply with config
+    //stops at the end of the game or at the first move that is not legal
+    let mut state = game_state::GameState::new();
+    let mut reviews = Vec::new();
+    for &x in moves.iter(){
+        let played = game_state::Move::new(x, state.player);
+        if state.is_terminal() || !state.legal(&played){
+            break;
+        }
+        let best = Mcts::new(state, *config).search();
+        let eval_before = mover_value(state.place(&best), config);
+        let eval_after = if played == best { eval_before } else { mover_value(state.place(&played), config) };
+        reviews.push(PlyReview{ played, best, eval_before, eval_after });
+        state.place_mut(&played);
+    }
+    reviews
+}
+
+pub fn child_stats(root : &game_state::GameState, statistics : &HashMap<game_state::GameState, UCTData>, column : usize) -> Option<UCTData>{
+    //what the search knows about dropping a piece in column from root, None for a column that is full,
+    //off the board or was never visited
+    let mv = game_state::Move::new(column, root.player);
+    if !root.legal(&mv){
+        return None;
+    }
+    statistics.get(&root.place(&mv)).cloned()
+}
+
+//the best root move and its statistics, then the runner up if there is one
+pub type BestTwo = (game_state::Move, UCTData, Option<(game_state::Move, UCTData)>);
+
+pub fn best_two(root : &game_state::GameState, statistics : &HashMap<game_state::GameState, UCTData>) -> Option<BestTwo>{
+    //the two most visited root moves with their statistics, equal visits ordered by tie_break. how far apart they are
+    //says whether the position is clear or close. None when no root move was visited, and no runner up with only one
+    let children = root.legal_moves().into_iter().filter_map(|mv| statistics.get(&root.place(&mv)).map(|&data| (mv, data))).collect::<Vec<_>>();
+    top_two(children, root.width())
+}
+
+fn top_two(mut children : Vec<(game_state::Move, UCTData)>, width : usize) -> Option<BestTwo>{
+    children.retain(|&(_, data)| data.num_plays > 0);
+    children.sort_by(|a, b| b.1.num_plays.cmp(&a.1.num_plays).then(tie_break(&b.0, &a.0, width)));
+    let mut children = children.into_iter();
+    children.next().map(|(mv, data)| (mv, data, children.next()))
+}
+
+pub fn explain_move(root : game_state::GameState, config : &MctsConfig) -> (game_state::Move, String){
+    //the move search would play here, with a short reason for it that a learner can follow
+    if let Some(&mv) = root.winning_moves(root.player).first(){
+        return (mv, format!("wins immediately in column {}", mv.x + 1));
+    }
+    let mut engine = Mcts::new(root, *config);
+    let mv = engine.search();
+    (mv, explain(&engine, mv))
+}
+
+fn move_name(mv : &game_state::Move) -> String{
+    //the column counting from 1, with a p in front for a pop, as typed into the cli
+    match mv.kind{
+        game_state::MoveKind::Drop => (mv.x + 1).to_string(),
+        game_state::MoveKind::Pop => format!("p{}", mv.x + 1)
+    }
+}
+
+fn explain(engine : &Mcts, mv : game_state::Move) -> String{
+    //columns count from 1, like the ones typed into the cli
+    let root = engine.root();
+    let player = root.player;
+    let opponent = state_previous_player(&root);
+    let after = root.place(&mv);
+    let mut reasons = Vec::new();
+
+    let mut blocked = root.winning_moves(opponent).iter().map(|threat| threat.x).collect::<Vec<_>>();
+    blocked.sort();
+    blocked.dedup();
+    if !blocked.is_empty() && after.winning_moves(opponent).is_empty(){
+        let columns = blocked.iter().map(|x| (x + 1).to_string()).collect::<Vec<_>>();
+        reasons.push(format!("blocks opponent's threat in column {}", columns.join(" and ")));
+    }
+    if evaluation::creates_fork(&root, &mv, player){
+        reasons.push("creates a double threat".to_string());
+    }
+
+    if engine.config.use_book && engine.book.get(&root) == Some(&mv){
+        reasons.push("plays the opening book move".to_string());
+    }
+    else{
+        match engine.node(&after){
+            Some(data) if data.num_plays > 0 => {
+                let searched = match engine.config.final_selection{
+                    FinalSelection::MostVisited | FinalSelection::Robust(_) => "most-searched",
+                    FinalSelection::Temperature(_) => "sampled",
+                    _ => "best searched"
+                };
+                match data.reported_win_rate(engine.config.min_visits_for_winrate){
+                    Some(win_rate) => reasons.push(format!("{} move with {:.0}% win rate", searched, win_rate * 100f64)),
+                    None => reasons.push(format!("{} move, too little searched for a win rate", searched))
+                }
+                let line = engine.principal_variation(4).iter().skip(1).map(move_name).collect::<Vec<_>>();
+                if !line.is_empty(){
+                    reasons.push(format!("expecting {} to follow", line.join(" ")));
+                }
+            },
+            _ => if reasons.is_empty(){
+                reasons.push("nothing stands out, so it is picked at random".to_string());
+            }
+        }
+    }
+    reasons.join(", ")
+}
+
+pub fn tactical_move(state : &game_state::GameState) -> Option<game_state::Move>{
+    //moves that are right no matter what the statistics say: an immediate win, or else the only block
+    match state.winning_moves(state.player).first(){
+        Some(&mv) => Some(mv),
+        None => forced_block(state)
+    }
+}
+
+pub fn forced_block(state : &game_state::GameState) -> Option<game_state::Move>{
+    //when the opponent threatens to win next turn and we cannot win first, only moves that leave them
+    //no immediate win are worth anything. random rollouts undersample that, so if there is exactly one
+    //such move it is played without searching at all
+    let player = state.player;
+    let opponent = state_previous_player(state);
+    if !state.winning_moves(player).is_empty() || state.winning_moves(opponent).is_empty(){
+        return None;
+    }
+    let safe = state.legal_moves().into_iter().filter(
+        |mv| state.place(mv).winning_moves(opponent).is_empty()
+        ).collect::<Vec<_>>();
+    if safe.len() == 1{
+        Some(safe[0])
+    }
+    else{
+        None
+    }
+}
+
+fn expand<G : Game>(node : G, visited_states : &mut HashSet<G>, statistics : &mut HashMap<G, UCTData>, moves : &mut MoveCache<G>){
+    if !visited_states.contains(&node){
+        statistics.insert(node, UCTData::new(0f64, 0));
+        visited_states.insert(node);
+    }
+    moves.entry(node).or_insert_with(|| node.legal_moves());
+}
+
+pub fn child_states<G : Game>(node : &G) -> Vec<G>{
+    //every position one legal move away, none once the game is over
+    if node.is_terminal(){
+        return Vec::new();
+    }
+    node.legal_moves().iter().map(|mv| node.place(mv)).collect()
+}
+
+fn expand_children<G : Game>(node : G, visited_states : &mut HashSet<G>, statistics : &mut HashMap<G, UCTData>, config : &MctsConfig){
+    //adds node's children as unvisited leaves, for config.full_expansion
+    for child in child_states(&node){
+        let key = node_key(&child, config);
+        if visited_states.insert(key){
+            statistics.insert(key, UCTData::new(0f64, 0));
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct PhaseTimes{
+    //seconds spent in each phase of the iterations, summed over the search
+    pub selection : f64,
+    pub expansion : f64,
+    pub simulation : f64,
+    pub backpropagation : f64
+}
+
+impl PhaseTimes{
+    pub fn total(&self) -> f64{
+        self.selection + self.expansion + self.simulation + self.backpropagation
+    }
+}
+
+fn lap(since : &mut Option<f64>) -> f64{
+    //seconds since the last lap, always 0 when the phases are not being timed
+    match *since{
+        Some(start) => {
+            let now = time::precise_time_s();
+            *since = Some(now);
+            now - start
+        },
+        None => 0f64
+    }
+}
+
+//what an iteration keeps track of besides the tree, a pondering thread keeps neither
+struct IterationRecords<'a, M : 'a = game_state::Move>{
+    phases : Option<&'a mut PhaseTimes>,
+    history : Option<&'a mut HistoryTable<M>>
+}
+
+impl<'a, M> Default for IterationRecords<'a, M>{
+    fn default() -> Self{
+        IterationRecords{ phases : None, history : None }
+    }
+}
+
+fn iterate<G : Game>(root : G, visited_states : &mut HashSet<G>, statistics : &mut HashMap<G, UCTData>, moves : &mut MoveCache<G>,
+    config : &MctsConfig, rng : &mut SearchRng, records : IterationRecords<G::Move>){
+    let IterationRecords{ phases, mut history } = records;
+    let current_state = root;
+    let mut since = phases.as_ref().map(|_| time::precise_time_s());
+
+    //selection
+    let store = MapStore{ visited_states, stats : statistics, moves : Some(moves), history : history.as_deref() };
+    let selected_state = select_with(&current_state, &store, config, &mut rng.selection);
+    let selection = lap(&mut since);
+
+    //expand
+    expand(node_key(&selected_state.expanded_node, config), visited_states, statistics, moves);
+    if config.full_expansion{
+        expand_children(selected_state.expanded_node, visited_states, statistics, config);
+    }
+    let expansion = lap(&mut since);
+
+    //simulate, unless the solver already knows how the leaf ends
+    let proven = if config.solver { statistics.get(&node_key(&selected_state.expanded_node, config)).and_then(|data| data.proven) } else { None };
+    let results = match proven{
+        Some(result) => vec![result],
+        None => leaf_results(&root, selected_state.expanded_node, config, &mut rng.rollout)
+    };
+    let backup = Backup::from_config(config, &root, &selected_state.expanded_node);
+    let simulation = lap(&mut since);
+
+    //backpropogate
+    let path = if config.mirror_keys{
+        Cow::Owned(selected_state.path.iter().map(|&(node, mover)| (node_key(&node, config), mover)).collect::<Vec<_>>())
+    }
+    else{
+        Cow::Borrowed(&selected_state.path)
+    };
+    back_propogate_all(&results, statistics, &path, backup);
+    if config.solver{
+        solve_path(&selected_state.path, statistics, moves, config);
+    }
+    if let Some(history) = history.as_mut(){
+        history.record_path(&selected_state.path, &results, backup);
+    }
+    let backpropagation = lap(&mut since);
+
+    if let Some(phases) = phases{
+        phases.selection += selection;
+        phases.expansion += expansion;
+        phases.simulation += simulation;
+        phases.backpropagation += backpropagation;
+    }
+}
+
+fn solve_path<G : Game>(path : &[(G, game_state::Color)], statistics : &mut HashMap<G, UCTData>, moves : &MoveCache<G>, config : &MctsConfig){
+    //marks what the iteration's path proves, from the leaf up. a node that stays open leaves everything above it open
+    for (depth, &(node, _)) in path.iter().enumerate().rev(){
+        let key = node_key(&node, config);
+        if statistics.get(&key).is_none_or(|data| data.proven.is_some()){
+            continue;
+        }
+        match solve_node(&node, depth == 0, statistics, moves, config){
+            Some(result) => statistics.get_mut(&key).unwrap().proven = Some(result),
+            None => break
+        }
+    }
+}
+
+fn solve_node<G : Game>(node : &G, is_root : bool, statistics : &HashMap<G, UCTData>, moves : &MoveCache<G>, config : &MctsConfig) -> Option<game_state::End>{
+    //node's result from its children's proofs, the moves are the ones selection chooses from
+    let mut possible_moves = moves.get(node).cloned().unwrap_or_else(|| node.legal_moves());
+    if possible_moves.is_empty() || node.is_terminal(){
+        return Some(no_moves_result(node));
+    }
+    if is_root && config.fold_symmetry{
+        possible_moves = node.fold_symmetric(possible_moves);
+    }
+    let player = node.current_player();
+    let mut best : Option<game_state::End> = None;
+    let mut open = false;
+    for mv in possible_moves.iter(){
+        match statistics.get(&node_key(&node.place(mv), config)).and_then(|data| data.proven){
+            Some(result) if result.is_win_for(player) => return Some(result),
+            Some(result) => if best.is_none_or(|best| get_result_value(result, player) > get_result_value(best, player)){
+                best = Some(result);
+            },
+            None => open = true
+        }
+    }
+    if open { None } else { best }
+}
+
+fn solver_choices(children : Vec<(game_state::Move, &UCTData)>, player : game_state::Color) -> Vec<(game_state::Move, &UCTData)>{
+    //the root moves config.solver lets the final selection pick from: the proven wins if there are any,
+    //otherwise everything but the proven losses, unless nothing else is left
+    let proven_for = |color : game_state::Color| children.iter().cloned().filter(|&(_, data)| data.proven.is_some_and(|result| result.is_win_for(color))).collect::<Vec<_>>();
+    let wins = proven_for(player);
+    if !wins.is_empty(){
+        return wins;
+    }
+    let open = children.iter().cloned().filter(|&(_, data)| !data.proven.is_some_and(|result| result.is_win_for(player.opponent()))).collect::<Vec<_>>();
+    if open.is_empty() { children } else { open }
+}
+
+pub type Tree = (HashSet<game_state::GameState>, HashMap<game_state::GameState, UCTData>);
+
+#[derive(Debug, Copy, Clone)]
+pub struct RootChild{
+    //one row of Mcts::root_children
+    pub mv : game_state::Move,
+    //all zeros when visited is false
+    pub data : UCTData,
+    //None until data has config.min_visits_for_winrate visits
+    pub win_rate : Option<f64>,
+    pub visited : bool
+}
+
+pub struct SearchResult{
+    pub best_move : game_state::Move,
+    //iterations run in total, over every thread
+    pub iterations : u64,
+    //seconds spent searching, 0 when the move came from the book or tactics
+    pub elapsed : f64,
+    //distinct positions in the tree afterwards, see Mcts::nodes_explored
+    pub nodes : usize,
+    //the search proved the root's result, see MctsConfig.prove and MctsConfig.solver. a proven win or tie is kept by best_move
+    pub proven : bool,
+    //where those seconds went, when config.time_phases asks for it
+    pub phases : Option<PhaseTimes>,
+    //the seeds the search ran on, for replay_search. None when nothing was searched or the search
+    //was shared between threads, which no seed can replay
+    pub seeds : Option<SearchSeeds>,
+    //every legal root move with its statistics, most visited first, see Mcts::ranked_children
+    pub moves : Vec<RootChild>
+}
+
+impl SearchResult{
+    pub fn nps(&self) -> f64{
+        //search throughput in iterations per second
+        if self.elapsed <= 0f64{
+            return 0f64;
+        }
+        self.iterations as f64 / self.elapsed
+    }
+
+    pub fn iterations_per_node(&self) -> f64{
+        //how often the search came back to the same positions, well above 1 when transpositions are
+        //shared a lot or the tree kept from earlier moves already covered the search
+        if self.nodes == 0{
+            return 0f64;
+        }
+        self.iterations as f64 / self.nodes as f64
+    }
+}
+
+struct Ponder{
+    stop : Arc<AtomicBool>,
+    handle : thread::JoinHandle<(Tree, MoveCache)>
+}
+
+pub struct Mcts{
+    //a search that keeps its statistics between moves
+    //everything is keyed by GameState, so re-rooting on a child keeps that child's whole subtree
+    root : game_state::GameState,
+    pub config : MctsConfig,
+    //keeps track of visisted states so we know if current state is a leaf
+    visited_states : HashSet<game_state::GameState>,
+    statistics : HashMap<game_state::GameState, UCTData>,
+    moves : MoveCache,
+    pub book : OpeningBook,
+    ponder : Option<Ponder>,
+    //the phase timings of the current think, see MctsConfig.time_phases
+    phases : PhaseTimes,
+    rng : SearchRng,
+    //the only root moves searched, see restrict
+    candidates : Option<Vec<game_state::Move>>,
+    eval_cache : evaluation::EvalCache,
+    //filled while config.history_weight is set
+    history : HistoryTable,
+    //ends the running think early once set, see stop_handle
+    stop : Arc<AtomicBool>
+}
+
+impl Mcts{
+    pub fn new(root : game_state::GameState, config : MctsConfig) -> Mcts{
+        let mut engine = Mcts{
+            root,
+            config,
+            visited_states : HashSet::new(),
+            statistics : HashMap::new(),
+            moves : HashMap::new(),
+            book : opening_book(),
+            ponder : None,
+            phases : PhaseTimes::default(),
+            rng : SearchRng::new(&config),
+            candidates : None,
+            eval_cache : evaluation::EvalCache::new(config.eval_cache_size),
+            history : HistoryTable::new(),
+            stop : Arc::new(AtomicBool::new(false))
+        };
+        engine.insert_root();
+        engine
+    }
+
+    fn insert_root(&mut self){
+        expand(node_key(&self.root, &self.config), &mut self.visited_states, &mut self.statistics, &mut self.moves);
+        if self.config.full_expansion{
+            expand_children(self.root, &mut self.visited_states, &mut self.statistics, &self.config);
+        }
+    }
+
+    pub fn root(&self) -> game_state::GameState{
+        self.root
+    }
+
+    pub fn statistics(&self) -> &HashMap<game_state::GameState, UCTData>{
+        //keyed by node_key, look positions up with node when config.mirror_keys may be on
+        &self.statistics
+    }
+
+    pub fn history(&self) -> &HistoryTable{
+        //the progressive history gathered since the last reset, empty unless config.history_weight is set
+        &self.history
+    }
+
+    pub fn node(&self, state : &game_state::GameState) -> Option<&UCTData>{
+        //what the search knows about state, found under its node_key
+        self.statistics.get(&node_key(state, &self.config))
+    }
+
+    pub fn evaluate(&mut self, state : &game_state::GameState, color : game_state::Color) -> f64{
+        //the heuristic evaluation of state for color, remembered until the next reset
+        self.eval_cache.evaluate(state, color)
+    }
+
+    pub fn prime(&mut self, line : &[usize]) -> Result<(), game_state::GridError>{
+        //puts every position along line, a column per move from the root, into the tree. each one new to the tree
+        //starts with a single visit worth its evaluation, which is also added to the nodes above it, so the search
+        //begins with the line considered but is free to drop it. positions already in the tree keep their statistics
+        let mut path = vec![(self.root, state_previous_player(&self.root))];
+        let mut state = self.root;
+        for &column in line{
+            if state.is_terminal(){
+                return Err(game_state::GridError::Finished);
+            }
+            let mv = game_state::Move::new(column, state.player);
+            if !state.legal(&mv){
+                return Err(game_state::GridError::Unplayable{ column });
+            }
+            state = state.place(&mv);
+            path.push((state, mv.color));
+        }
+        let path = path.into_iter().map(|(node, mover)| (node_key(&node, &self.config), mover)).collect::<Vec<_>>();
+        for depth in 1..path.len(){
+            let node = path[depth].0;
+            if self.visited_states.contains(&node){
+                continue;
+            }
+            expand(node, &mut self.visited_states, &mut self.statistics, &mut self.moves);
+            if self.config.full_expansion{
+                expand_children(node, &mut self.visited_states, &mut self.statistics, &self.config);
+            }
+            let white = self.eval_cache.evaluate(&node, game_state::Color::White);
+            for &(ancestor, mover) in &path[..=depth]{
+                let unit = if mover == game_state::Color::White { white } else { 1f64 - white };
+                let reward = self.config.reward_scale.from_unit(unit);
+                if let Some(stat) = self.statistics.get_mut(&ancestor){
+                    stat.wins += reward;
+                    stat.sum_squares += reward * reward;
+                    stat.num_plays += 1;
+                    stat.win_tie += if unit >= 0.5f64 { 1 } else { 0 };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self){
+        //a single selection/expansion/simulation/backpropagation pass
+        let records = IterationRecords{
+            phases : if self.config.time_phases { Some(&mut self.phases) } else { None },
+            history : if self.config.history_weight.is_some() { Some(&mut self.history) } else { None }
+        };
+        iterate(self.root, &mut self.visited_states, &mut self.statistics, &mut self.moves, &self.config, &mut self.rng, records);
+    }
+
+    pub fn search(&mut self) -> game_state::Move{
+        self.search_result().best_move
+    }
+
+    pub fn search_result(&mut self) -> SearchResult{
+        //search, plus how much work went into the move
+        self.ponder_stop();
+        if let Some(best_move) = self.instant_move(){
+            self.check_result(&best_move);
+            return SearchResult{ best_move, iterations : 0, elapsed : 0f64, nodes : self.nodes_explored(), proven : false, phases : None, seeds : None,
+                moves : self.ranked_children() };
+        }
+        let (iterations, elapsed) = self.think();
+        let proof = if self.config.prove { self.proof() } else { None };
+        let best_move = match (proof, self.swindle()){
+            (Some((_, Some(mv))), _) | (_, Some(mv)) => mv,
+            _ => self.best_move()
+        };
+        self.check_result(&best_move);
+        SearchResult{
+            best_move,
+            iterations,
+            elapsed,
+            nodes : self.nodes_explored(),
+            proven : proof.is_some() || self.is_solved(),
+            phases : self.measured_phases(),
+            seeds : if self.thread_count() <= 1 { Some(self.rng.seeds) } else { None },
+            moves : self.ranked_children()
+        }
+    }
+
+    pub fn proven_value(&self) -> Option<game_state::End>{
+        //the result of the game from the root with perfect play, when the tree already settles it: a position is
+        //decided once one of its children is a proven win for the player to move there, or every child is proven
+        //and in the tree. None while the search has not reached that far
+        self.node(&self.root).and_then(|data| data.proven).or_else(|| self.proof().map(|(result, _)| result))
+    }
+
+    fn proof(&self) -> Option<(game_state::End, Option<game_state::Move>)>{
+        //proven_value, with the move that keeps a proven win or tie, the most visited one if several do
+        let root = self.root;
+        let player = root.player;
+        let mut solved = HashMap::new();
+        let children = self.root_moves().into_iter().map(|mv| (mv, self.prove_node(&root.place(&mv), &mut solved))).collect::<Vec<_>>();
+        //a winning move decides the root by itself, anything less needs every move proven
+        let won = children.iter().any(|&(_, result)| result.is_some_and(|result| result.is_win_for(player)));
+        if !won && children.iter().any(|&(_, result)| result.is_none()){
+            return None;
+        }
+        let children = children.into_iter().filter_map(|(mv, result)| result.map(|result| (mv, result))).collect::<Vec<_>>();
+        let value = |result : game_state::End| get_result_value(result, player);
+        let best = children.iter().map(|&(_, result)| value(result)).fold(f64::NEG_INFINITY, f64::max);
+        let visits = |mv : &game_state::Move| self.node(&root.place(mv)).map_or(0, |data| data.num_plays);
+        let (best_move, result) = *children.iter().filter(|&&(_, result)| value(result) == best)
+            .max_by(|a, b| visits(&a.0).cmp(&visits(&b.0)).then(tie_break(&a.0, &b.0, root.width())))?;
+        Some((result, if result.is_win_for(player.opponent()) { None } else { Some(best_move) }))
+    }
+
+    fn prove_node(&self, state : &game_state::GameState, solved : &mut HashMap<game_state::GameState, Option<game_state::End>>) -> Option<game_state::End>{
+        let result = state.win();
+        if result.is_over(){
+            return Some(result);
+        }
+        if let Some(&known) = solved.get(state){
+            return known;
+        }
+        //positions the search never reached are open, and so is every position above them
+        self.node(state)?;
+        let player = state.player;
+        let mut best : Option<game_state::End> = None;
+        let mut open = false;
+        for mv in state.legal_moves(){
+            match self.prove_node(&state.place(&mv), solved){
+                Some(result) if result.is_win_for(player) => {
+                    best = Some(result);
+                    open = false;
+                    break;
+                },
+                Some(result) => if best.is_none_or(|best| get_result_value(result, player) > get_result_value(best, player)){
+                    best = Some(result);
+                },
+                None => open = true
+            }
+        }
+        let proven = if open { None } else { best };
+        solved.insert(*state, proven);
+        proven
+    }
+
+    fn swindle(&self) -> Option<game_state::Move>{
+        //swindle_move when every visited root move falls short of config.swindle
+        let threshold = self.config.swindle?;
+        let root = self.root;
+        let moves = self.root_moves();
+        let visited = moves.iter().filter_map(|mv| self.node(&root.place(mv))).filter(|data| data.num_plays > 0).collect::<Vec<_>>();
+        if visited.is_empty() || visited.iter().any(|data| data.win_percentage() >= threshold){
+            return None;
+        }
+        swindle_move(&root, &moves)
+    }
+
+    fn check_result(&self, best_move : &game_state::Move){
+        //a finished root has no legal move to return, whatever comes back is only a placeholder
+        if !self.root.is_terminal(){
+            check_legal(self.config.validate, &self.root, best_move, "search");
+        }
+    }
+
+    fn instant_move(&self) -> Option<game_state::Move>{
+        //the stages before the tree search, whichever of them config turns on
+        //a book or tactical move that restrict ruled out is not played
+        let allowed = |mv : &game_state::Move| self.candidates.as_ref().is_none_or(|candidates| candidates.contains(mv));
+        if self.config.use_book{
+            if let Some(&mv) = self.book.get(&self.root){
+                if allowed(&mv){
+                    return Some(mv);
+                }
+            }
+        }
+        if self.config.use_tactics{
+            if let Some(mv) = tactical_move(&self.root){
+                if allowed(&mv){
+                    return Some(mv);
+                }
+            }
+        }
+        if !self.config.use_search{
+            //nothing left that knows better
+            return Some(choose_random(&self.root_moves()));
+        }
+        None
+    }
+
+    fn root_moves(&self) -> Vec<game_state::Move>{
+        //the root moves that are searched and chosen from
+        match self.candidates{
+            Some(ref candidates) if self.config.fold_symmetry => fold_symmetric(&self.root, candidates.clone()),
+            Some(ref candidates) => candidates.clone(),
+            None => root_moves(&self.root, &self.config)
+        }
+    }
+
+    pub fn restrict(&mut self, candidates : &[game_state::Move]){
+        //searches only these root moves and picks the best of them, the tree below them is searched as usual.
+        //moves that are not legal at the root are left out, and with none left every move is searched again.
+        //lasts until the root changes. the parallel searches can not follow it, so until then the search runs on one thread
+        self.unrestrict();
+        let mut legal : Vec<game_state::Move> = Vec::new();
+        for mv in candidates.iter(){
+            if self.root.legal(mv) && !legal.contains(mv){
+                legal.push(*mv);
+            }
+        }
+        if legal.is_empty(){
+            return;
+        }
+        //select reads the root's moves from the cache, so that is where the restriction takes effect
+        self.moves.insert(self.root, legal.clone());
+        self.candidates = Some(legal);
+    }
+
+    fn thread_count(&self) -> usize{
+        //config.thread_count, or 1 while restrict is in force
+        if self.candidates.is_some() { 1 } else { self.config.thread_count() }
+    }
+
+    pub fn unrestrict(&mut self){
+        if self.candidates.take().is_some(){
+            self.moves.insert(self.root, self.root.legal_moves());
+        }
+    }
+
+    fn think_time(&self) -> f64{
+        self.config.budget.think_time(&self.root)
+    }
+
+    pub fn think(&mut self) -> (u64, f64){
+        //runs the configured budget without choosing a move
+        //returns how many iterations were run and how many seconds that took
+        self.ponder_stop();
+        self.phases = PhaseTimes::default();
+        //every think starts its streams over, so the seeds it reports are all a replay needs
+        self.rng = SearchRng::new(&self.config);
+        let think_time = self.think_time();
+
+        let current_time = time::precise_time_s();
+        let iterations = if self.thread_count() > 1{
+            self.search_shared(think_time)
+        }
+        else{
+            let limit = self.config.iteration_limit();
+            let mut iterations = 0u64;
+            while !self.stop.load(AtomicOrdering::Relaxed) && time::precise_time_s() - current_time < think_time
+                && limit.is_none_or(|limit| iterations < limit){
+                let every = limit.map_or(self.config.check_every(), |limit| self.config.check_every().min(limit - iterations));
+                for _ in 0..every{
+                    self.step();
+                }
+                iterations += every;
+                //the confidence target needs a look at every child of the root, so it waits for
+                //a multiple of CONFIDENCE_CHECK_EVERY to have gone by
+                let passed = iterations / CONFIDENCE_CHECK_EVERY > (iterations - every) / CONFIDENCE_CHECK_EVERY;
+                if passed && self.is_confident() || self.is_solved(){
+                    break;
+                }
+            }
+            iterations
+        };
+        self.stop.store(false, AtomicOrdering::Relaxed);
+        (iterations, time::precise_time_s() - current_time)
+    }
+
+    pub fn stop_handle(&self) -> Arc<AtomicBool>{
+        //a flag another thread can set to end the running think early, on any number of threads, the way
+        //SearchHandle::stop ends a spawn_search. it is cleared again once that think returns
+        self.stop.clone()
+    }
+
+    fn measured_phases(&self) -> Option<PhaseTimes>{
+        //shared searches run their iterations in parallel and are not timed
+        if self.config.time_phases && self.thread_count() <= 1 { Some(self.phases) } else { None }
+    }
+
+    fn is_solved(&self) -> bool{
+        //config.solver has proven the root's result, more iterations would only revisit the proof
+        self.config.solver && self.node(&self.root).is_some_and(|data| data.proven.is_some())
+    }
+
+    fn is_confident(&self) -> bool{
+        //the budget is only a cap when the best move is already as good as config.confidence_target asks for
+        match self.config.confidence_target{
+            Some(target) => self.current_best().is_some_and(|(_, data)|
+                data.num_plays >= MIN_CONFIDENT_VISITS && data.win_percentage() >= target),
+            None => false
+        }
+    }
+
+    pub fn win_probability(&self) -> f64{
+        //win-or-tie rate of the player to move, over every simulation that went through the root's children
+        let root = self.root;
+        let (win_tie, num_plays) = self.root_moves().iter()
+            .filter_map(|mv| self.node(&root.place(mv)))
+            .fold((0, 0), |(win_tie, num_plays), data| (win_tie + data.win_tie, num_plays + data.num_plays));
+        if num_plays == 0{
+            return 0.5f64;
+        }
+        win_tie as f64 / num_plays as f64
+    }
+
+    fn search_shared(&mut self, think_time : f64) -> u64{
+        let tree : Tree = (std::mem::take(&mut self.visited_states), std::mem::take(&mut self.statistics));
+        let (tree, iterations) = match self.config.parallelism{
+            Parallelism::Tree => parallel::tree_parallel(self.root, tree, &self.config, think_time, &self.stop),
+            Parallelism::Root => parallel::root_parallel(self.root, tree, &self.config, think_time, &self.stop)
+        };
+        self.visited_states = tree.0;
+        self.statistics = tree.1;
+        iterations
+    }
+
+    pub fn best_move(&self) -> game_state::Move{
+        //children the search never reached count as unvisited, and with none visited any root move will do
+        let root = self.root;
+        let unvisited = UCTData::new(0f64, 0);
+        let root_moves = self.root_moves();
+        let possible_moves = root_moves.iter().map(|&x| (x, self.node(&root.place(&x)).unwrap_or(&unvisited))).collect::<Vec<_>>();
+        //draws come from a copy of the selection stream as the search left it, so a seed fixes them
+        //and asking again without searching in between gives the same move
+        let mut rng = self.rng.selection;
+        if !root_moves.is_empty() && possible_moves.iter().all(|&(_, data)| data.num_plays == 0){
+            return choose_random_with(&root_moves, &mut rng);
+        }
+        let possible_moves = if self.config.solver { solver_choices(possible_moves, root.player) } else { possible_moves };
+        //a finished root has no move at all, the placeholder is only for check_result to ignore
+        optimal_move(&possible_moves, self.config.final_selection, root.width(), &mut rng)
+            .unwrap_or_else(|| game_state::Move::new(0, root.player))
+    }
+
+    pub fn current_best(&self) -> Option<(game_state::Move, UCTData)>{
+        //the move best_move would pick right now, looking only at children that have been visited so far
+        let root = self.root;
+        let possible_moves = self.root_moves().into_iter()
+            .filter_map(|x| self.node(&root.place(&x)).map(|data| (x, data)))
+            .collect::<Vec<_>>();
+        if possible_moves.iter().all(|&(_, data)| data.num_plays == 0){
+            return None;
+        }
+        let possible_moves = if self.config.solver { solver_choices(possible_moves, root.player) } else { possible_moves };
+        let best_move = optimal_move(&possible_moves, self.config.final_selection, root.width(), &mut self.rng.selection.clone())?;
+        Some((best_move, *self.node(&root.place(&best_move)).unwrap()))
+    }
+
+    pub fn root_children(&self) -> Vec<RootChild>{
+        //every legal move at the root with what the search knows about it, in legal_moves order, for tables
+        //that should list every column however short the search was. a move left out by restrict is unvisited,
+        //and one folded away on a symmetric root shows the statistics of its mirror image
+        let root = self.root;
+        let searched = self.root_moves();
+        root.legal_moves().into_iter().map(|mv|{
+            let mirror = game_state::Move{ x : root.width() - 1 - mv.x, ..mv };
+            let stand_in = if !searched.contains(&mv) && searched.contains(&mirror) && root.mirrored() == root { mirror } else { mv };
+            let data = if searched.contains(&stand_in) { self.node(&root.place(&stand_in)).cloned() } else { None };
+            match data{
+                Some(data) if data.num_plays > 0 =>
+                    RootChild{ mv, data, win_rate : data.reported_win_rate(self.config.min_visits_for_winrate), visited : true },
+                _ => RootChild{ mv, data : UCTData::new(0f64, 0), win_rate : None, visited : false }
+            }
+        }).collect()
+    }
+
+    pub fn ranked_children(&self) -> Vec<RootChild>{
+        //root_children, most visited first, moves with as many visits stay in legal_moves order
+        let mut children = self.root_children();
+        children.sort_by_key(|child| std::cmp::Reverse(child.data.num_plays));
+        children
+    }
+
+    pub fn nodes_explored(&self) -> usize{
+        //how many distinct positions have statistics, the tree kept from earlier moves included
+        self.statistics.len()
+    }
+
+    pub fn best_two(&self) -> Option<BestTwo>{
+        //best_two over the root moves that are searched
+        let root = self.root;
+        let children = self.root_moves().into_iter().filter_map(|mv| self.node(&root.place(&mv)).map(|&data| (mv, data))).collect();
+        top_two(children, root.width())
+    }
+
+    pub fn root_outcomes(&self) -> Vec<(game_state::Move, Outcomes)>{
+        //the wins, ties and losses of every legal move at the root, listed like root_children
+        self.root_children().into_iter().map(|child| (child.mv, child.data.outcomes())).collect()
+    }
+
+    pub fn principal_variation(&self, max_length : usize) -> Vec<game_state::Move>{
+        //the line the search expects, following the most visited child from the root
+        //capped at max_length, since in pop out a line can come back to a position it already went through
+        let mut line = Vec::new();
+        self.extend_line(&mut line, self.root, max_length);
+        line
+    }
+
+    pub fn principal_variation_from(&self, first : game_state::Move, max_length : usize) -> Vec<game_state::Move>{
+        //principal_variation starting with first instead of the most visited root move, e.g. the move
+        //the final selection played, then following the most visited children below it
+        if max_length == 0{
+            return Vec::new();
+        }
+        let mut line = vec![first];
+        self.extend_line(&mut line, self.root.place(&first), max_length);
+        line
+    }
+
+    fn extend_line(&self, line : &mut Vec<game_state::Move>, mut state : game_state::GameState, max_length : usize){
+        //adds the most visited child of state to line, and of that one's, until max_length or the tree runs out
+        while line.len() < max_length && !state.is_terminal(){
+            let moves = if line.is_empty() { self.root_moves() } else { state.legal_moves() };
+            let next = moves.into_iter()
+                .filter_map(|mv| self.node(&state.place(&mv)).map(|data| (mv, data.num_plays)))
+                .filter(|&(_, num_plays)| num_plays > 0)
+                .max_by_key(|&(_, num_plays)| num_plays);
+            match next{
+                Some((mv, _)) => {
+                    line.push(mv);
+                    state = state.place(&mv);
+                },
+                None => break
+            }
+        }
+    }
+
+    pub fn iter_search(&mut self, every : usize) -> IterSearch<'_>{
+        //searches in chunks of every iterations, yielding the best move so far after each chunk
+        //nothing stops it but the caller, so take() or break out when it is good enough
+        self.ponder_stop();
+        IterSearch{
+            engine : self,
+            every : every.max(1),
+            iterations : 0
+        }
+    }
+
+    pub fn search_traced<W : io::Write>(&mut self, out : &mut W) -> io::Result<SearchResult>{
+        //search_result, writing to out the root child every iteration went through and then what each root child
+        //ended up with. for offline debugging, it always runs single threaded for the whole budget and
+        //the bookkeeping makes every iteration slower. search itself keeps no trace at all
+        self.ponder_stop();
+        if let Some(best_move) = self.instant_move(){
+            writeln!(out, "nothing searched, {} came before the search", move_name(&best_move))?;
+            return Ok(SearchResult{ best_move, iterations : 0, elapsed : 0f64, nodes : self.nodes_explored(), proven : false, phases : None, seeds : None,
+                moves : self.ranked_children() });
+        }
+        self.phases = PhaseTimes::default();
+        self.rng = SearchRng::new(&self.config);
+        let root = self.root;
+        let moves = self.root_moves();
+        let visits = |engine : &Mcts| moves.iter()
+            .map(|mv| engine.node(&root.place(mv)).map_or(0, |data| data.num_plays)).collect::<Vec<_>>();
+        let think_time = self.think_time();
+        let current_time = time::precise_time_s();
+        let mut before = visits(self);
+        let mut iterations = 0u64;
+        let limit = self.config.iteration_limit();
+        while !self.stop.load(AtomicOrdering::Relaxed) && time::precise_time_s() - current_time < think_time
+            && limit.is_none_or(|limit| iterations < limit){
+            self.step();
+            iterations += 1;
+            let after = visits(self);
+            match (0..moves.len()).find(|&i| after[i] != before[i]){
+                Some(i) => writeln!(out, "iteration {}: {}", iterations, move_name(&moves[i]))?,
+                //only when the root is already over
+                None => writeln!(out, "iteration {}: root", iterations)?
+            }
+            before = after;
+        }
+        self.stop.store(false, AtomicOrdering::Relaxed);
+        let elapsed = time::precise_time_s() - current_time;
+        for mv in moves.iter(){
+            let data = self.node(&root.place(mv)).cloned().unwrap_or_else(|| UCTData::new(0f64, 0));
+            write!(out, "{}: {} visits, {:.3} reward, {} wins or ties", move_name(mv), data.num_plays, data.wins, data.win_tie)?;
+            if self.config.report_outcomes{
+                let outcomes = data.outcomes();
+                write!(out, " ({} wins, {} ties, {} losses)", outcomes.wins, outcomes.ties, outcomes.losses)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(SearchResult{
+            best_move : self.best_move(),
+            iterations,
+            elapsed,
+            nodes : self.nodes_explored(),
+            proven : false,
+            phases : self.measured_phases(),
+            seeds : Some(self.rng.seeds),
+            moves : self.ranked_children()
+        })
+    }
+
+    pub fn advance(&mut self, mv : &game_state::Move){
+        //re-roots on the position after mv, whatever was learned about it is kept
+        //and the rest of the tree, which no later search can reach, is dropped
+        self.ponder_stop();
+        self.unrestrict();
+        self.root = self.root.place(mv);
+        self.prune();
+        self.insert_root();
+    }
+
+    fn prune(&mut self){
+        //keeps only the nodes the tree reaches from the root, under their positions and their keys
+        let mut reachable = HashSet::new();
+        let mut stack = vec![self.root];
+        reachable.insert(self.root);
+        reachable.insert(node_key(&self.root, &self.config));
+        while let Some(state) = stack.pop(){
+            for child in child_states(&state){
+                let key = node_key(&child, &self.config);
+                if self.visited_states.contains(&key) && reachable.insert(child){
+                    reachable.insert(key);
+                    stack.push(child);
+                }
+            }
+        }
+        self.visited_states.retain(|state| reachable.contains(state));
+        self.statistics.retain(|state, _| reachable.contains(state));
+        self.moves.retain(|state, _| reachable.contains(state));
+    }
+
+    pub fn reset(&mut self){
+        //forgets everything, e.g. before a new game, so old statistics cannot leak into it
+        self.ponder_stop();
+        self.candidates = None;
+        self.eval_cache.clear();
+        self.visited_states.clear();
+        self.statistics.clear();
+        self.moves.clear();
+        self.history.clear();
+        self.insert_root();
+    }
+
+    pub fn set_position(&mut self, root : game_state::GameState){
+        self.ponder_stop();
+        self.root = root;
+        self.reset();
+    }
+
+    pub fn ponder_start(&mut self, expected_move : game_state::Move){
+        //searches the position after the expected reply on a background thread until ponder_stop or search
+        //if the reply turns out to be different, advance just re-roots and the pondered statistics sit unused
+        let root = self.root.place(&expected_move);
+        self.ponder_from(root);
+    }
+
+    pub fn ponder_all(&mut self){
+        //as ponder_start without a guess: searches the current root, so whichever reply comes the tree
+        //below it has grown, at the cost of spreading the iterations over every reply
+        let root = self.root;
+        self.ponder_from(root);
+    }
+
+    fn ponder_from(&mut self, root : game_state::GameState){
+        self.ponder_stop();
+        let config = self.config;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let mut tree : Tree = (std::mem::take(&mut self.visited_states), std::mem::take(&mut self.statistics));
+        let mut moves = std::mem::take(&mut self.moves);
+        let handle = thread::spawn(move ||{
+            let mut rng = SearchRng::new(&config);
+            expand(node_key(&root, &config), &mut tree.0, &mut tree.1, &mut moves);
+            while !thread_stop.load(AtomicOrdering::Relaxed){
+                for _ in 0..config.check_every(){
+                    iterate(root, &mut tree.0, &mut tree.1, &mut moves, &config, &mut rng, IterationRecords::default());
+                }
+            }
+            (tree, moves)
+        });
+        self.ponder = Some(Ponder{ stop, handle });
+    }
+
+    pub fn ponder_stop(&mut self){
+        if let Some(ponder) = self.ponder.take(){
+            ponder.stop.store(true, AtomicOrdering::Relaxed);
+            let ((visited_states, statistics), moves) = ponder.handle.join().expect("ponder thread panicked");
+            self.visited_states = visited_states;
+            self.statistics = statistics;
+            self.moves = moves;
+        }
+    }
+
+    pub fn is_pondering(&self) -> bool{
+        self.ponder.is_some()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SearchProgress{
+    pub iterations : u64,
+    pub best_move : game_state::Move,
+    //win-or-tie rate of best_move for the player to move, None while it has too few visits to say,
+    //see MctsConfig.min_visits_for_winrate
+    pub win_rate : Option<f64>
+}
+
+pub struct IterSearch<'a>{
+    engine : &'a mut Mcts,
+    every : usize,
+    iterations : u64
+}
+
+impl<'a> Iterator for IterSearch<'a>{
+    type Item = SearchProgress;
+
+    fn next(&mut self) -> Option<SearchProgress>{
+        if self.engine.root.is_terminal(){
+            return None;
+        }
+        for _ in 0..self.every{
+            self.engine.step();
+        }
+        self.iterations += self.every as u64;
+        self.engine.current_best().map(|(best_move, data)| SearchProgress{
+            iterations : self.iterations,
+            best_move,
+            win_rate : data.reported_win_rate(self.engine.config.min_visits_for_winrate)
+        })
+    }
+}
+
+impl Drop for Mcts{
+    fn drop(&mut self){
+        self.ponder_stop();
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedTree{
+    //what save_tree writes, the move cache and the book are rebuilt on load
+    root : game_state::GameState,
+    config : MctsConfig,
+    statistics : Vec<(game_state::GameState, UCTData)>
+}
+
+pub fn save_tree<P : AsRef<Path>>(engine : &Mcts, path : P) -> io::Result<()>{
+    //writes the engine's position, config and every node's statistics, so a long analysis can be resumed later.
+    //a ponder running in the background is not included
+    let saved = SavedTree{
+        root : engine.root,
+        config : engine.config,
+        statistics : engine.statistics.iter().map(|(state, data)| (*state, *data)).collect()
+    };
+    let file = io::BufWriter::new(File::create(path)?);
+    bincode::serialize_into(file, &saved).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub fn load_tree<P : AsRef<Path>>(path : P) -> io::Result<Mcts>{
+    //an engine that picks up where the one given to save_tree was
+    let file = io::BufReader::new(File::open(path)?);
+    let saved : SavedTree = bincode::deserialize_from(file).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let mut engine = Mcts::new(saved.root, saved.config);
+    engine.visited_states.extend(saved.statistics.iter().map(|&(state, _)| state));
+    engine.statistics.extend(saved.statistics);
+    Ok(engine)
+}
+
+pub struct SearchHandle{
+    //a search running on its own thread, see spawn_search
+    stop : Arc<AtomicBool>,
+    best : Arc<Mutex<Option<(game_state::Move, UCTData)>>>,
+    handle : Option<thread::JoinHandle<SearchResult>>
+}
+
+//how many iterations the background search runs between updates of its best move,
+//or config.check_interval if that is more
+const HANDLE_UPDATE_EVERY : u64 = 64;
+
+pub fn spawn_search(root : game_state::GameState, config : MctsConfig) -> SearchHandle{
+    //searches root on a background thread for the configured budget, so a ui can keep running
+    //the search itself is single threaded whatever config.threads says
+    let stop = Arc::new(AtomicBool::new(false));
+    let best = Arc::new(Mutex::new(None));
+    let (thread_stop, thread_best) = (stop.clone(), best.clone());
+    let handle = thread::spawn(move ||{
+        let mut engine = Mcts::new(root, config);
+        if let Some(best_move) = engine.instant_move(){
+            *thread_best.lock().unwrap() = Some((best_move, UCTData::new(0f64, 0)));
+            return SearchResult{ best_move, iterations : 0, elapsed : 0f64, nodes : engine.nodes_explored(), proven : false, phases : None, seeds : None,
+                moves : engine.ranked_children() };
+        }
+        let think_time = engine.think_time();
+        let current_time = time::precise_time_s();
+        let mut iterations = 0u64;
+        let limit = config.iteration_limit();
+        while !thread_stop.load(AtomicOrdering::Relaxed) && time::precise_time_s() - current_time < think_time
+            && limit.is_none_or(|limit| iterations < limit){
+            let every = config.check_every().max(HANDLE_UPDATE_EVERY);
+            let every = limit.map_or(every, |limit| every.min(limit - iterations));
+            for _ in 0..every{
+                engine.step();
+            }
+            iterations += every;
+            *thread_best.lock().unwrap() = engine.current_best();
+        }
+        let best_move = match engine.current_best(){
+            Some((best_move, _)) => best_move,
+            None => choose_random(&root.legal_moves())
+        };
+        SearchResult{ best_move, iterations, elapsed : time::precise_time_s() - current_time, nodes : engine.nodes_explored(), proven : false,
+            phases : engine.measured_phases(), seeds : Some(engine.rng.seeds), moves : engine.ranked_children() }
+    });
+    SearchHandle{ stop, best, handle : Some(handle) }
+}
+
+impl SearchHandle{
+    pub fn current_best(&self) -> Option<(game_state::Move, UCTData)>{
+        //the best move found so far and its statistics, None until the search has something to say
+        *self.best.lock().unwrap()
+    }
+
+    pub fn stop(&self){
+        //asks the search to finish early, wait still returns its result
+        self.stop.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool{
+        self.handle.as_ref().is_none_or(|handle| handle.is_finished())
+    }
+
+    pub fn wait(mut self) -> SearchResult{
+        //blocks until the search is over, on its budget or after stop
+        self.handle.take().unwrap().join().expect("search thread panicked")
+    }
+}
+
+impl Drop for SearchHandle{
+    fn drop(&mut self){
+        //a handle nobody is waiting on does not keep its thread searching
+        self.stop();
+    }
+}
+
+fn optimal_move<R : Rng>(possible_moves : &[(game_state::Move, &UCTData)], selection : FinalSelection, width : usize, rng : &mut R) -> Option<game_state::Move>{
+    //None when no move qualifies, which with at least one visited move only happens to an empty list
+    match selection{
+        FinalSelection::MostVisited => optimal_move_most_visisted(possible_moves, width),
+        FinalSelection::HighestWin => optimal_move_highest_win(possible_moves),
+        FinalSelection::SecureChild(a) => optimal_move_secure_child(possible_moves, a, width),
+        FinalSelection::Robust(margin) => optimal_move_robust(possible_moves, margin, width),
+        FinalSelection::Temperature(t) => optimal_move_temperature(possible_moves, t, width, rng)
+    }
+}
+
+fn optimal_move_temperature<R : Rng>(possible_moves : &[(game_state::Move, &UCTData)], t : f64, width : usize, rng : &mut R) -> Option<game_state::Move>{
+    //draws a visited node with odds of its visits^(1 / t), scaled by the most visits so a low t can not overflow
+    let most_played = possible_moves.iter().map(|&(_, data)| data.num_plays).max().unwrap_or(0);
+    if most_played <= 0 || t <= 0f64{
+        return optimal_move_most_visisted(possible_moves, width);
+    }
+    let weight = |data : &UCTData| (data.num_plays.max(0) as f64 / most_played as f64).powf(1f64 / t);
+    let mut draw = rng.gen::<f64>() * possible_moves.iter().map(|&(_, data)| weight(data)).sum::<f64>();
+    for &(mv, data) in possible_moves{
+        draw -= weight(data);
+        if draw < 0f64 && data.num_plays > 0{
+            return Some(mv);
+        }
+    }
+    optimal_move_most_visisted(possible_moves, width)
+}
+
+fn best_by<F : Fn(&UCTData) -> Option<f64>>(possible_moves : &[(game_state::Move, &UCTData)], width : usize, value : F) -> Option<game_state::Move>{
+    //the move with the highest value, equal values broken by tie_break. moves valued None are skipped
+    let mut best : Option<(game_state::Move, f64)> = None;
+    for &(mv, data) in possible_moves{
+        if let Some(value) = value(data){
+            let better = best.is_none_or(|(best_move, best_value)|
+                value > best_value || (value == best_value && tie_break(&mv, &best_move, width) == Ordering::Greater));
+            if better{
+                best = Some((mv, value));
+            }
+        }
+    }
+    best.map(|(mv, _)| mv)
+}
+
+fn optimal_move_robust(possible_moves : &[(game_state::Move, &UCTData)], margin : f64, width : usize) -> Option<game_state::Move>{
+    //selects the best valued node among those with close to the most visits
+    let most_played = possible_moves.iter().map(|&(_, data)| data.num_plays).max().unwrap_or(0);
+    if most_played == 0{
+        return optimal_move_most_visisted(possible_moves, width);
+    }
+    let enough = most_played as f64 * (1f64 - margin);
+    best_by(possible_moves, width, |data|
+        if (data.num_plays as f64) < enough { None } else { Some(data.wins / data.num_plays as f64) })
+}
+
+fn optimal_move_secure_child(possible_moves : &[(game_state::Move, &UCTData)], a : f64, width : usize) -> Option<game_state::Move>{
+    //selects the node whose value is best once penalized for how few times it was tried
+    best_by(possible_moves, width, |data|
+        if data.num_plays == 0 { None } else { Some(data.wins / data.num_plays as f64 - a / (data.num_plays as f64).sqrt()) })
+}
+
+fn optimal_move_highest_win(possible_moves : &[(game_state::Move, &UCTData)]) -> Option<game_state::Move>{
+    //selects the highest winning node as optimal, the first one listed on equal totals
+    //totals can be negative on the MinusOneToOne scale
+    let mut best : Option<(game_state::Move, f64)> = None;
+    for &(mv, data) in possible_moves{
+        if best.is_none_or(|(_, highest_win)| data.wins > highest_win){
+            best = Some((mv, data.wins));
+        }
+    }
+    best.map(|(mv, _)| mv)
+}
+
+fn tie_break(a : &game_state::Move, b : &game_state::Move, width : usize) -> Ordering{
+    //orders equally good moves so the preferred one is greater
+    //closer to the center first, then the lower column, then drops before pops
+    let center_distance = |mv : &game_state::Move| (2 * mv.x as i32 - (width as i32 - 1)).abs();
+    center_distance(b).cmp(&center_distance(a))
+        .then(b.x.cmp(&a.x))
+        .then((b.kind as u8).cmp(&(a.kind as u8)))
+}
+
+fn optimal_move_most_visisted(possible_moves : &[(game_state::Move, &UCTData)], width : usize) -> Option<game_state::Move>{
+    //selects the most visited node as optimal, None when nothing has been visited
+    //ties are broken by tie_break so the same statistics always give the same move
+    best_by(possible_moves, width, |data| if data.num_plays > 0 { Some(data.num_plays as f64) } else { None })
+}
+
+
+pub fn tree_policy<G : Game>(
+    current_state : &G,
+    visisted_states : &HashSet<G>,
+    stats : &HashMap<G, UCTData>
+    ) -> TreePolicyResult<G>{
+    select(current_state, &MapStore{ visited_states : visisted_states, stats, moves : None, history : None }, &MctsConfig::default())
+}
+
+pub fn root_moves(root : &game_state::GameState, config : &MctsConfig) -> Vec<game_state::Move>{
+    //the moves searched at the root. on a board that is its own mirror image, like the empty one,
+    //a move and its mirror lead to mirrored positions, so only the left half and the middle are searched
+    let moves = root.legal_moves();
+    if !config.fold_symmetry{
+        return moves;
+    }
+    fold_symmetric(root, moves)
+}
+
+pub fn fold_symmetric(root : &game_state::GameState, moves : Vec<game_state::Move>) -> Vec<game_state::Move>{
+    //a move on the right half goes when its mirror image is there to stand in for it
+    if root.mirrored() != *root{
+        return moves;
+    }
+    let width = root.width();
+    let mirror = |mv : &game_state::Move| game_state::Move{ x : width - 1 - mv.x, ..*mv };
+    moves.iter().cloned().filter(|mv| mv.x * 2 < width || !moves.contains(&mirror(mv))).collect()
+}
+
+pub fn select<G : Game, S : NodeStore<G>>(current_state : &G, store : &S, config : &MctsConfig) -> TreePolicyResult<G>{
+    select_with(current_state, store, config, &mut rand::thread_rng())
+}
+
+pub fn select_with<G : Game, S : NodeStore<G>, R : Rng>(current_state : &G, store : &S, config : &MctsConfig, rng : &mut R) -> TreePolicyResult<G>{
+
+    //represents the states we went through to get to the selected node
+    //used for backpropogation without an actual tree structure
+    let mut path : Vec<(G, game_state::Color)> = Vec::new();
+
+    let mut current_node = *current_state;
+    //nobody moved into the root during this search, it counts as the last move of whoever is not to move
+    let mut mover = current_state.previous_player();
+    //every iteration passes through the root once, so its visits say how far into the search we are
+    let exploration = config.exploration.constant(store.data(&node_key(current_state, config)).map_or(0, |data| data.num_plays)) * config.reward_scale.width().powi(2);
+    let loss = config.reward_scale.loss();
+
+    loop{
+
+        path.push((current_node, mover));
+
+        //a proven node needs no more search below it, its result is backed up as it is
+        if config.solver && path.len() > 1 && store.data(&node_key(&current_node, config)).is_some_and(|data| data.proven.is_some()){
+            return TreePolicyResult::new(path, current_node);
+        }
+
+        let mut possible_moves = match store.legal_moves(&current_node){
+            Some(moves) => Cow::Borrowed(moves),
+            None => Cow::Owned(current_node.legal_moves())
+        };
+        if path.len() == 1 && config.fold_symmetry{
+            possible_moves = Cow::Owned(current_node.fold_symmetric(possible_moves.into_owned()));
+        }
+
+        if possible_moves.is_empty() || current_node.is_terminal(){
+            //no legal moves or game ends
+            return TreePolicyResult::new(path, current_node);
+        }
+
+        //the path holds the root too, so it is one longer than the depth of the node it ends on
+        if config.max_depth.is_some_and(|depth| path.len() > depth.max(1)){
+            return TreePolicyResult::new(path, current_node);
+        }
+        
+        //has every possible move been explored?
+        let (explored, not_explored) : (Vec<_>, Vec<_>) = possible_moves.iter().cloned().partition(
+            |x|
+            store.visited(&node_key(&current_node.place(x), config))
+        );
+
+        //progressive widening only lets a node open more children as its visit count grows
+        let allowed_children = match config.progressive_widening{
+            Some(widening) => widening.children(store.data(&node_key(&current_node, config)).map_or(0, |data| data.num_plays)),
+            None => explored.len() + not_explored.len()
+        };
+
+        //if not, exploration
+        if !not_explored.is_empty() && explored.len() < allowed_children {
+            //for a node with number played of 0, ucb1 returns infinity
+            //in other words unexplored child nodes are always explored at least once
+            let choice = current_node.expansion_move(config.expansion_order, &not_explored, rng);
+            check_legal(config.validate, &current_node, &choice, "select");
+            let chosen_node = current_node.place(&choice);
+            path.push((chosen_node, current_node.current_player()));
+            let result = TreePolicyResult::new(path, chosen_node);
+            return result;
+        }
+
+        //children added by full expansion are in the tree without having been simulated yet,
+        //ucb1 would put them at infinity so they are simulated first, from where they are
+        let fresh = explored.iter().cloned().filter(|x| store.data(&node_key(&current_node.place(x), config))
+            .is_some_and(|data| data.num_plays == 0 && data.virtual_loss == 0f64)).collect::<Vec<_>>();
+        if !fresh.is_empty(){
+            let choice = current_node.expansion_move(config.expansion_order, &fresh, rng);
+            check_legal(config.validate, &current_node, &choice, "select");
+            path.push((current_node.place(&choice), current_node.current_player()));
+            return TreePolicyResult::new(path, current_node.place(&choice));
+        }
+
+        //all child nodes have been simulated at least once, so use ucb1 to select best
+        else{
+            //sort 
+            let mut best_move = explored.last().unwrap();
+            //ucb1 never scores below a loss, so when every move looks like one the last is kept
+            let mut best_uct = loss;
+            let parent = store.data(&node_key(&current_node, config)).unwrap();
+            let total_played = parent.num_plays as f64 + parent.virtual_loss;
+            for possibility in explored.iter(){
+                
+                //TODO: switch to pattern matching
+                let data = store.data(&node_key(&current_node.place(possibility), config)).unwrap();
+                //the solver plays a proven win for the mover straight away and leaves proven losses alone
+                match data.proven{
+                    Some(result) if config.solver && result.is_win_for(current_node.current_player()) => {
+                        best_move = possibility;
+                        break;
+                    },
+                    Some(result) if config.solver && result.is_win_for(current_node.previous_player()) => continue,
+                    _ => ()
+                }
+                let bonus = config.history_weight.and_then(|weight| store.history(possibility).map(|value| weight * value / (data.num_plays as f64 + 1f64)));
+                let uct = ucb1(data.wins + loss * data.virtual_loss, data.num_plays as f64 + data.virtual_loss, total_played, exploration) + bonus.unwrap_or(0f64);
+                if uct > best_uct{
+                    best_uct = uct;
+                    best_move = possibility;
+                }
+            }
+            check_legal(config.validate, &current_node, best_move, "select");
+            mover = current_node.current_player();
+            current_node = current_node.place(best_move);
+        }
+    }
+}
+
+
+pub fn back_propogate<G : Game>(win_value : game_state::End, stats : &mut HashMap<G, UCTData>, path : &[(G, game_state::Color)]){
+        back_propogate_all(&[win_value], stats, path, Backup::default());
+}
+
+pub fn rollout_rewards(results : &[game_state::End], mover : game_state::Color, backup : Backup) -> Vec<(f64, i32, i32)>{
+    //the visits, as (reward, win_tie, ties), that the results of one iteration's rollouts add to a node scored for mover.
+    //aggregated they make a single visit with the mean reward, so num_plays keeps counting trips through the tree
+    //and several rollouts just make each trip more accurate. separate, every rollout is a visit of its own,
+    //which inflates the counts and makes ucb1 stop exploring a node sooner than its trips through the tree justify.
+    //a blend only moves the rewards, the breakdown still counts what the rollouts played out
+    let reward = |result| {
+        let reward = backup.scale.value_with(result, mover, backup.tie_winner);
+        backup.blend.map_or(reward, |blend| blend.mix(reward, mover, backup.scale))
+    };
+    let visits = results.iter().map(|&result| (reward(result), get_tie_or_win(result, mover), get_tie(result)));
+    if !backup.aggregate || results.len() <= 1{
+        return visits.collect();
+    }
+    let count = results.len() as f64;
+    let (reward, win_tie, ties) = visits.fold((0f64, 0, 0), |(reward, win_tie, ties), visit| (reward + visit.0, win_tie + visit.1, ties + visit.2));
+    //win_tie and ties are counts, so the averaged visit counts as a win or tie if most of its rollouts were,
+    //and as a tie if most were ties. ties never outnumber wins or ties, so the breakdown still adds up
+    vec![(reward / count, (win_tie as f64 / count).round() as i32, (ties as f64 / count).round() as i32)]
+}
+
+pub fn back_propogate_all<G : Game>(results : &[game_state::End], stats : &mut HashMap<G, UCTData>,
+    path : &[(G, game_state::Color)], backup : Backup){
+        //each node is scored for the player who moved into it, that is the player choosing it at its parent
+        for &(node, mover) in path.iter(){
+            if let Some(stat) = stats.get_mut(&node){
+                for (reward, win_tie, ties) in rollout_rewards(results, mover, backup){
+                    stat.wins += backup.wins_added(stat.wins, stat.num_plays, reward);
+                    stat.sum_squares += reward * reward;
+                    stat.num_plays += 1;
+                    stat.win_tie += win_tie;
+                    stat.ties += ties;
+                }
+            }
+        }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn play(columns : &[usize]) -> game_state::GameState{
+        columns.iter().fold(game_state::GameState::new(), |state, &x| state.place(&game_state::Move::new(x, state.player)))
+    }
+
+    fn short_search() -> MctsConfig{
+        MctsConfig{
+            budget : Budget::TimeSeconds(0.05f64),
+            threads : 1,
+            use_book : false,
+            quiet : true,
+            ..MctsConfig::default()
+        }
+    }
+
+    #[test]
+    fn virtual_loss_is_removed_after_a_shared_search(){
+        for &virtual_loss in &[0.5f64, 1f64, 3f64]{
+            let config = MctsConfig{
+                threads : 4,
+                virtual_loss,
+                ..short_search()
+            };
+            let mut engine = Mcts::new(play(&[3, 3]), config);
+            let best_move = engine.search();
+            assert!(engine.root().legal(&best_move));
+            assert!(engine.statistics().get(&engine.root()).unwrap().num_plays > 0);
+            assert!(engine.statistics().values().all(|data| data.virtual_loss.abs() < 1e-9));
+        }
+    }
+
+    fn threat_positions() -> Vec<(game_state::GameState, usize)>{
+        //positions where the side to move has to block one immediate threat, with the blocking column
+        vec![
+            //horizontal on the bottom row
+            (play(&[0, 6, 1, 6, 2]), 3),
+            //horizontal with the gap in the middle
+            (play(&[0, 6, 1, 6, 3]), 2),
+            //vertical
+            (play(&[3, 0, 3, 1, 3]), 3),
+            //rising diagonal ending at the fourth row of column 3
+            (play(&[0, 1, 1, 2, 3, 2, 2, 3, 3]), 3),
+            //black threatening, so white has to block
+            (play(&[6, 0, 6, 1, 5, 2]), 3),
+        ]
+    }
+
+    #[test]
+    fn threat_positions_are_what_they_claim(){
+        for (state, column) in threat_positions(){
+            let opponent = state_previous_player(&state);
+            assert!(state.winning_moves(state.player).is_empty());
+            let threats = state.winning_moves(opponent);
+            assert_eq!(threats.len(), 1);
+            assert_eq!(threats[0].x, column);
+        }
+    }
+
+    #[test]
+    fn always_blocks_a_single_threat(){
+        for (state, column) in threat_positions(){
+            assert_eq!(forced_block(&state).map(|mv| mv.x), Some(column));
+            for _ in 0..3{
+                let mut engine = Mcts::new(state, short_search());
+                let mv = engine.search();
+                assert_eq!(mv.x, column);
+                assert_eq!(mv.color, state.player);
+            }
+        }
+    }
+
+    #[test]
+    fn no_forced_block_without_a_threat_or_with_an_own_win(){
+        assert_eq!(forced_block(&game_state::GameState::new()), None);
+        //white to move can win in column 3 itself, black threatens column 3 as well
+        assert_eq!(forced_block(&play(&[0, 0, 1, 1, 2, 2])), None);
+    }
+
+    fn winning_positions() -> Vec<(game_state::GameState, usize)>{
+        //positions where the side to move wins immediately, with the winning column
+        vec![
+            //horizontal on the bottom row
+            (play(&[0, 0, 1, 1, 2, 2]), 3),
+            //vertical, winning beats blocking black's three on the bottom row
+            (play(&[6, 0, 6, 1, 6, 2]), 6),
+            //rising diagonal
+            (play(&[0, 1, 1, 2, 3, 2, 2, 3, 3, 6]), 3),
+            //black to move with three stacked in column 6
+            (play(&[0, 6, 1, 6, 0, 6, 5]), 6),
+        ]
+    }
+
+    #[test]
+    fn always_takes_an_immediate_win(){
+        for (state, column) in winning_positions(){
+            assert_eq!(tactical_move(&state).map(|mv| mv.x), Some(column));
+            for _ in 0..5{
+                let mut engine = Mcts::new(state, short_search());
+                assert_eq!(engine.search().x, column);
+            }
+        }
+    }
+
+    #[test]
+    fn win_probability_of_terminal_positions(){
+        let config = short_search();
+        //white just completed four on the bottom row, black is to move and has lost
+        assert_eq!(estimate_win_probability(play(&[0, 0, 1, 1, 2, 2, 3]), &config), 0f64);
+    }
+
+    #[test]
+    fn win_probability_follows_the_position(){
+        let config = MctsConfig{
+            budget : Budget::TimeSeconds(0.3f64),
+            ..MctsConfig::default()
+        };
+        //white can win on the spot
+        let winning = estimate_win_probability(play(&[0, 0, 1, 1, 2, 2]), &config);
+        //black is to move but white has two open ends on the bottom row
+        let losing = estimate_win_probability(play(&[2, 2, 3, 3, 4]), &config);
+        assert!(winning > 0.8f64, "{}", winning);
+        assert!(losing < 0.5f64, "{}", losing);
+        assert!((0f64..=1f64).contains(&estimate_win_probability(game_state::GameState::new(), &config)));
+    }
+
+    #[test]
+    fn widening_children_grow_with_visits(){
+        let widening = Widening{ coefficient : 1f64, exponent : 0.5f64 };
+        assert_eq!(widening.children(0), 1);
+        assert_eq!(widening.children(1), 1);
+        assert_eq!(widening.children(4), 2);
+        assert_eq!(widening.children(10), 4);
+    }
+
+    #[test]
+    fn widening_descends_before_opening_a_new_child(){
+        let config = MctsConfig{
+            progressive_widening : Some(Widening{ coefficient : 1f64, exponent : 0.5f64 }),
+            ..MctsConfig::default()
+        };
+        let root = game_state::GameState::new();
+        let child = root.place(&game_state::Move::white_new(3));
+        let mut visited_states = HashSet::new();
+        let mut stats = HashMap::new();
+        visited_states.insert(root);
+        visited_states.insert(child);
+        stats.insert(root, UCTData::new(1f64, 3));
+        stats.insert(child, UCTData::new(1f64, 3));
+        //3 visits only allow two children, and root has one, so a second is opened
+        let store = MapStore{ visited_states : &visited_states, stats : &stats, moves : None, history : None };
+        assert_eq!(select(&root, &store, &config).path.len(), 2);
+        //with 1 visit the root only gets one child, so the search goes through it
+        stats.insert(root, UCTData::new(1f64, 1));
+        let store = MapStore{ visited_states : &visited_states, stats : &stats, moves : None, history : None };
+        let path = select(&root, &store, &config).path;
+        assert_eq!(path[1], (child, game_state::Color::White));
+        assert_eq!(path.len(), 3);
+
+        let mut engine = Mcts::new(root, MctsConfig{ budget : Budget::TimeSeconds(0.1f64), ..config });
+        assert!(root.legal(&engine.search()));
+    }
+
+    #[test]
+    fn root_parallel_finds_the_only_block(){
+        //black threatens the bottom row at column 3, the summed trees have to find that without the tactics stage
+        let root = play(&[6, 0, 6, 1, 5, 2]);
+        for &threads in &[1, 4]{
+            let config = MctsConfig{
+                budget : Budget::TimeSeconds(0.3f64),
+                threads,
+                parallelism : Parallelism::Root,
+                use_tactics : false,
+                use_book : false,
+                ..short_search()
+            };
+            let mut engine = Mcts::new(root, config);
+            assert_eq!(engine.search().x, 3);
+        }
+    }
+
+    #[test]
+    fn one_thread_searches_the_same_with_root_parallelism(){
+        //a single thread never splits the tree, so it is the plain search whichever parallelism is configured
+        let searched = |parallelism|{
+            let config = MctsConfig{ threads : 1, parallelism, budget : Budget::Iterations(200), ..short_search() }.with_seed(257);
+            let mut engine = Mcts::new(play(&[3]), config);
+            engine.think();
+            engine.statistics().iter().map(|(state, data)| (*state, (data.wins, data.num_plays))).collect::<HashMap<_, _>>()
+        };
+        assert_eq!(searched(Parallelism::Root), searched(Parallelism::Tree));
+    }
+
+    #[test]
+    fn advance_keeps_the_subtree(){
+        let mut engine = Mcts::new(game_state::GameState::new(), short_search());
+        let best_move = engine.search();
+        let child = engine.root().place(&best_move);
+        let visits = engine.statistics().get(&child).unwrap().num_plays;
+        assert!(visits > 0);
+        //a root move other than the one played, and one not just its mirror image
+        let root = engine.root();
+        let sibling = root.legal_moves().iter().map(|mv| node_key(&root.place(mv), &engine.config))
+            .find(|&state| state != node_key(&child, &engine.config) && engine.statistics().contains_key(&state)).unwrap();
+        let nodes = engine.nodes_explored();
+        engine.advance(&best_move);
+        assert_eq!(engine.root(), child);
+        assert_eq!(engine.statistics().get(&child).unwrap().num_plays, visits);
+        //the positions the game can no longer reach are gone
+        assert!(!engine.statistics().contains_key(&sibling));
+        assert!(!engine.statistics().contains_key(&root));
+        assert!(engine.nodes_explored() < nodes);
+    }
+
+    #[test]
+    fn iter_search_reports_progress(){
+        let mut engine = Mcts::new(play(&[0, 0, 1, 1, 2, 2]), short_search());
+        let progress = engine.iter_search(50).take(10).collect::<Vec<_>>();
+        assert_eq!(progress.len(), 10);
+        assert_eq!(progress[0].iterations, 50);
+        assert_eq!(progress[9].iterations, 500);
+        assert!(progress.iter().all(|step| step.win_rate.is_none_or(|win_rate| (0f64..=1f64).contains(&win_rate))));
+        assert!(progress[9].win_rate.is_some());
+        //by the end the immediate win must have taken over
+        assert_eq!(progress[9].best_move.x, 3);
+        assert_eq!(engine.statistics().get(&engine.root()).unwrap().num_plays, 500);
+    }
+
+    #[test]
+    fn iter_search_stops_on_a_finished_game(){
+        let mut engine = Mcts::new(play(&[0, 0, 1, 1, 2, 2, 3]), short_search());
+        assert_eq!(engine.iter_search(10).next(), None);
+    }
+
+    fn empty_stats(path : &[(game_state::GameState, game_state::Color)]) -> HashMap<game_state::GameState, UCTData>{
+        path.iter().map(|&(node, _)| (node, UCTData::new(0f64, 0))).collect()
+    }
+
+    #[test]
+    fn backprop_credits_the_player_who_moved_into_each_node(){
+        //white wins with its move into the last node
+        let root = play(&[0, 0, 1, 1, 2, 2]);
+        let won = root.place(&game_state::Move::white_new(3));
+        let path = vec![(root, game_state::Color::Black), (won, game_state::Color::White)];
+        let mut stats = empty_stats(&path);
+        back_propogate(won.win(), &mut stats, &path);
+        assert_eq!(stats[&won].wins, 1f64);
+        assert_eq!(stats[&root].wins, 0f64);
+
+        //white to move, black wins two plies later
+        let root = play(&[0, 6, 1, 6, 0, 6]);
+        let white_move = root.place(&game_state::Move::white_new(4));
+        let black_win = white_move.place(&game_state::Move::black_new(6));
+        assert_eq!(black_win.win(), game_state::End::Victory(game_state::Color::Black));
+        let path = vec![(root, game_state::Color::Black), (white_move, game_state::Color::White), (black_win, game_state::Color::Black)];
+        let mut stats = empty_stats(&path);
+        back_propogate(black_win.win(), &mut stats, &path);
+        assert_eq!(stats[&black_win].wins, 1f64);
+        assert_eq!(stats[&white_move].wins, 0f64);
+        assert_eq!(stats[&root].wins, 1f64);
+        assert!(path.iter().all(|(node, _)| stats[node].num_plays == 1));
+    }
+
+    #[test]
+    fn select_records_the_mover_of_every_node(){
+        let root = play(&[3]);
+        let mut visited_states = HashSet::new();
+        let mut stats = HashMap::new();
+        visited_states.insert(root);
+        stats.insert(root, UCTData::new(0f64, 1));
+        let path = tree_policy(&root, &visited_states, &stats).path;
+        assert_eq!(path[0], (root, game_state::Color::White));
+        assert_eq!(path[1].1, game_state::Color::Black);
+        assert_eq!(path[1].0.player, game_state::Color::White);
+    }
+
+    #[test]
+    fn backprop_splits_a_tie(){
+        let root = play(&[3]);
+        let child = root.place(&game_state::Move::black_new(3));
+        let path = vec![(root, game_state::Color::White), (child, game_state::Color::Black)];
+        let mut stats = empty_stats(&path);
+        back_propogate(game_state::End::Tie, &mut stats, &path);
+        assert!(path.iter().all(|(node, _)| stats[node].wins == 0.5f64 && stats[node].win_tie == 1));
+        assert!(path.iter().all(|(node, _)| stats[node].outcomes() == Outcomes{ wins : 0, ties : 1, losses : 0, visits : 1 }));
+    }
+
+    #[test]
+    fn root_outcomes_add_up(){
+        let root = play(&[3, 2]);
+        let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, ..short_search() });
+        engine.think();
+        let outcomes = engine.root_outcomes();
+        assert_eq!(outcomes.iter().map(|&(mv, _)| mv).collect::<Vec<_>>(), root.legal_moves());
+        for (mv, outcome) in outcomes{
+            let data = engine.statistics()[&root.place(&mv)];
+            assert_eq!(outcome.wins + outcome.ties + outcome.losses, data.num_plays);
+            assert_eq!(outcome.wins + outcome.ties, data.win_tie);
+            assert!(outcome.wins >= 0 && outcome.ties >= 0 && outcome.losses >= 0);
+        }
+    }
+
+    #[test]
+    fn second_player_values_its_own_win(){
+        //black to move can finish four in column 6, searched without the tactical shortcut
+        let root = play(&[0, 6, 1, 6, 0, 6, 5]);
+        let mut engine = Mcts::new(root, MctsConfig{ budget : Budget::TimeSeconds(0.2f64), ..MctsConfig::default() });
+        engine.think();
+        let (best_move, data) = engine.current_best().unwrap();
+        assert_eq!(best_move.x, 6);
+        assert_eq!(data.wins, data.num_plays as f64);
+    }
+
+    #[test]
+    fn reset_forgets_everything(){
+        let mut engine = Mcts::new(play(&[3]), short_search());
+        engine.search();
+        engine.ponder_start(game_state::Move::black_new(3));
+        engine.reset();
+        assert!(!engine.is_pondering());
+        assert_eq!(engine.root(), play(&[3]));
+        assert_eq!(engine.statistics().len(), 1);
+        assert_eq!(engine.statistics().get(&engine.root()).unwrap().num_plays, 0);
+    }
+
+    #[test]
+    fn set_position_re_roots_on_a_clean_tree(){
+        let mut engine = Mcts::new(game_state::GameState::new(), short_search());
+        engine.search();
+        let position = play(&[0, 6, 1]);
+        engine.set_position(position);
+        assert_eq!(engine.root(), position);
+        assert_eq!(engine.statistics().len(), 1);
+        assert!(position.legal(&engine.search()));
+    }
+
+    #[test]
+    fn pondering_searches_the_expected_reply(){
+        let mut engine = Mcts::new(play(&[3]), short_search());
+        let expected = game_state::Move::black_new(3);
+        let pondered = engine.root().place(&expected);
+        engine.ponder_start(expected);
+        assert!(engine.is_pondering());
+        thread::sleep(std::time::Duration::from_millis(50));
+        engine.ponder_stop();
+        assert!(!engine.is_pondering());
+        assert!(engine.statistics().get(&pondered).unwrap().num_plays > 0);
+
+        engine.advance(&expected);
+        assert_eq!(engine.root(), pondered);
+        engine.search();
+    }
+
+    #[test]
+    fn pondering_every_reply_grows_each_of_them(){
+        let mut engine = Mcts::new(play(&[3]), short_search());
+        engine.ponder_all();
+        thread::sleep(std::time::Duration::from_millis(50));
+        //the root is symmetric, so only the left half of the replies gets searched
+        let reply = game_state::Move::black_new(2);
+        engine.advance(&reply);
+        assert!(!engine.is_pondering());
+        assert!(engine.node(&engine.root()).unwrap().num_plays > 0);
+        assert!(engine.root().legal(&engine.search()));
+    }
+
+    #[test]
+    fn wrong_prediction_re_roots(){
+        let mut engine = Mcts::new(play(&[3]), short_search());
+        engine.ponder_start(game_state::Move::black_new(3));
+        thread::sleep(std::time::Duration::from_millis(20));
+        let actual = game_state::Move::black_new(0);
+        engine.advance(&actual);
+        assert!(!engine.is_pondering());
+        assert_eq!(engine.root(), play(&[3, 0]));
+        let best_move = engine.search();
+        assert!(engine.root().legal(&best_move));
+    }
+
+    #[test]
+    fn think_time_peaks_in_the_middle_game(){
+        let opening = think_time_for(&game_state::GameState::new(), 60f64);
+        let middle = think_time_for(&play(&[0, 1, 2, 3, 4, 5, 6, 0, 1, 2, 3, 4, 5, 6, 0, 1, 2, 3, 4, 5]), 60f64);
+        assert!(middle > opening);
+    }
+
+    #[test]
+    fn think_time_stays_within_the_clock(){
+        let state = play(&[3, 3, 3]);
+        assert!(think_time_for(&state, 1f64) <= 0.5f64);
+        assert_eq!(think_time_for(&state, 0f64), 0f64);
+        //the last empty cell should not eat more than half of what is left
+        let almost_full = play(&[0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2]);
+        assert!(think_time_for(&almost_full, 10f64) <= 5f64);
+    }
+
+    #[test]
+    fn most_visited_wins_regardless_of_position(){
+        let edge = UCTData::new(0f64, 10);
+        let center = UCTData::new(0f64, 9);
+        let moves = vec![(game_state::Move::white_new(0), &edge), (game_state::Move::white_new(3), &center)];
+        assert_eq!(optimal_move_most_visisted(&moves, 7).unwrap().x, 0);
+        //with nothing visited there is no move to pick, rather than a made up one
+        let untried = UCTData::new(0f64, 0);
+        assert_eq!(optimal_move_most_visisted(&[(game_state::Move::white_new(0), &untried)], 7), None);
+        assert_eq!(optimal_move(&[], FinalSelection::Robust(ROBUST_MARGIN), 7, &mut rand::thread_rng()), None);
+    }
+
+    #[test]
+    fn secure_child_prefers_established_value(){
+        let popular = UCTData::new(20f64, 50);
+        let solid = UCTData::new(36f64, 40);
+        let gamble = UCTData::new(2f64, 2);
+        let untried = UCTData::new(0f64, 0);
+        let moves = vec![
+            (game_state::Move::white_new(0), &popular),
+            (game_state::Move::white_new(1), &solid),
+            (game_state::Move::white_new(2), &gamble),
+            (game_state::Move::white_new(3), &untried)
+        ];
+        assert_eq!(optimal_move(&moves, FinalSelection::MostVisited, 7, &mut rand::thread_rng()).unwrap().x, 0);
+        assert_eq!(optimal_move(&moves, FinalSelection::SecureChild(1f64), 7, &mut rand::thread_rng()).unwrap().x, 1);
+        //without the penalty the two-visit gamble looks best
+        assert_eq!(optimal_move(&moves, FinalSelection::SecureChild(0f64), 7, &mut rand::thread_rng()).unwrap().x, 2);
+    }
+
+    #[test]
+    fn temperature_draws_by_visits(){
+        let popular = UCTData::new(30f64, 60);
+        let other = UCTData::new(10f64, 30);
+        let untried = UCTData::new(0f64, 0);
+        let moves = vec![
+            (game_state::Move::white_new(0), &popular),
+            (game_state::Move::white_new(1), &other),
+            (game_state::Move::white_new(2), &untried)
+        ];
+        let mut rng = StdRng::from_seed(&[514]);
+        let mut counts = [0; 3];
+        for _ in 0..3000{
+            counts[optimal_move_temperature(&moves, 1f64, 7, &mut rng).unwrap().x] += 1;
+        }
+        //two to one, and never a move without visits
+        assert!(counts[0] > 1800 && counts[0] < 2200 && counts[2] == 0);
+        for _ in 0..100{
+            assert_eq!(optimal_move_temperature(&moves, 0.01f64, 7, &mut rng).unwrap().x, 0);
+        }
+    }
+
+    #[test]
+    fn difficulty_holds_the_search_back(){
+        let config = MctsConfig::default();
+        assert_eq!(config.with_difficulty(Difficulty::Hard), config);
+        let easy = config.with_difficulty(Difficulty::Easy);
+        assert_eq!(easy.budget, Budget::Iterations(200));
+        assert!(!easy.use_tactics);
+        assert_eq!(Difficulty::from_name("medium"), Some(Difficulty::Medium));
+        assert_eq!(Difficulty::from_name("impossible"), None);
+        let root = play(&[0, 0, 0, 0, 0, 0, 3]);
+        for &difficulty in [Difficulty::Easy, Difficulty::Medium].iter(){
+            let config = MctsConfig{ quiet : true, ..config.with_difficulty(difficulty) };
+            assert!(root.legal(&tree_search_with(root, &config)));
+        }
+        //the sampled move comes from the seeded selection stream, so a seed repeats it
+        for seed in 0..3{
+            let seeded = MctsConfig{ quiet : true, ..easy.with_seed(seed) };
+            assert_eq!(tree_search_with(play(&[3]), &seeded), tree_search_with(play(&[3]), &seeded));
+        }
+    }
+
+    #[test]
+    fn robust_breaks_near_ties_by_value(){
+        //the most visited move is barely ahead of a move that wins far more often
+        let popular = UCTData::new(50f64, 100);
+        let strong = UCTData::new(70f64, 97);
+        let gamble = UCTData::new(10f64, 10);
+        let moves = vec![
+            (game_state::Move::white_new(0), &popular),
+            (game_state::Move::white_new(1), &strong),
+            (game_state::Move::white_new(2), &gamble)
+        ];
+        assert_eq!(optimal_move(&moves, FinalSelection::MostVisited, 7, &mut rand::thread_rng()).unwrap().x, 0);
+        assert_eq!(optimal_move(&moves, FinalSelection::HighestWin, 7, &mut rand::thread_rng()).unwrap().x, 1);
+        assert_eq!(optimal_move(&moves, FinalSelection::Robust(ROBUST_MARGIN), 7, &mut rand::thread_rng()).unwrap().x, 1);
+        //with no margin it is most visited again, and the barely tried gamble never gets in
+        assert_eq!(optimal_move(&moves, FinalSelection::Robust(0f64), 7, &mut rand::thread_rng()).unwrap().x, 0);
+        assert_eq!(optimal_move(&moves, FinalSelection::Robust(0.95f64), 7, &mut rand::thread_rng()).unwrap().x, 2);
+        assert_eq!(MctsConfig::default().final_selection, FinalSelection::Robust(ROBUST_MARGIN));
+    }
+
+    #[test]
+    fn equal_visits_prefer_the_center(){
+        let data = UCTData::new(0f64, 10);
+        let moves = (0..7).map(|x| (game_state::Move::white_new(x), &data)).collect::<Vec<_>>();
+        assert_eq!(optimal_move_most_visisted(&moves, 7).unwrap().x, 3);
+        let mut reversed = moves.clone();
+        reversed.reverse();
+        assert_eq!(optimal_move_most_visisted(&reversed, 7).unwrap().x, 3);
+    }
+
+    #[test]
+    fn equal_distance_prefers_the_lower_column(){
+        let data = UCTData::new(0f64, 10);
+        let moves = vec![(game_state::Move::white_new(4), &data), (game_state::Move::white_new(2), &data)];
+        assert_eq!(optimal_move_most_visisted(&moves, 7).unwrap().x, 2);
+        //with an even width both middle columns are equally central
+        let moves = vec![(game_state::Move::white_new(2), &data), (game_state::Move::white_new(1), &data)];
+        assert_eq!(optimal_move_most_visisted(&moves, 4).unwrap().x, 1);
+    }
+
+    #[test]
+    fn expanded_nodes_cache_their_legal_moves(){
+        let mut engine = Mcts::new(game_state::GameState::new(), short_search());
+        for _ in 0..200{
+            engine.step();
+        }
+        assert_eq!(engine.moves.len(), engine.statistics().len());
+        for (state, moves) in engine.moves.iter(){
+            assert_eq!(moves, &state.legal_moves());
+        }
+        engine.reset();
+        assert_eq!(engine.moves.len(), 1);
+    }
+
+    #[test]
+    fn select_follows_the_cached_moves(){
+        //a cache that only knows one move at the root is trusted over recomputing
+        let root = game_state::GameState::new();
+        let visited_states = [root].iter().cloned().collect::<HashSet<_>>();
+        let stats = [(root, UCTData::new(0f64, 1))].iter().cloned().collect::<HashMap<_, _>>();
+        let moves = [(root, vec![game_state::Move::white_new(5)])].iter().cloned().collect::<MoveCache>();
+        let store = MapStore{ visited_states : &visited_states, stats : &stats, moves : Some(&moves), history : None };
+        let config = MctsConfig{ fold_symmetry : false, ..MctsConfig::default() };
+        for _ in 0..10{
+            assert_eq!(select(&root, &store, &config).expanded_node, root.place(&game_state::Move::white_new(5)));
+        }
+    }
+
+    #[test]
+    fn history_breaks_ties_between_children(){
+        //two children with the same statistics, the one whose move has the better history is selected
+        let root = game_state::GameState::new();
+        let (left, right) = (game_state::Move::white_new(0), game_state::Move::white_new(1));
+        let visited_states = [root, root.place(&left), root.place(&right)].iter().cloned().collect::<HashSet<_>>();
+        let stats = [(root, UCTData::new(1f64, 2)), (root.place(&left), UCTData::new(0.5f64, 1)), (root.place(&right), UCTData::new(0.5f64, 1))]
+            .iter().cloned().collect::<HashMap<_, _>>();
+        let moves = [(root, vec![left, right])].iter().cloned().collect::<MoveCache>();
+        let mut history = HistoryTable::new();
+        history.record(right, 1f64);
+        history.record(left, 0f64);
+        assert_eq!((history.value(&right), history.visits(&left)), (Some(1f64), 1));
+        let store = MapStore{ visited_states : &visited_states, stats : &stats, moves : Some(&moves), history : Some(&history) };
+        let config = MctsConfig{ fold_symmetry : false, history_weight : Some(0.5f64), ..MctsConfig::default() };
+        assert_eq!(select(&root, &store, &config).path[1].0, root.place(&right));
+        //the search fills the table only when the bonus is on
+        let mut engine = Mcts::new(root, MctsConfig{ history_weight : Some(0.5f64), ..short_search() });
+        for _ in 0..50{
+            engine.step();
+        }
+        assert!(root.legal_moves().iter().any(|mv| engine.history().visits(mv) > 0));
+        engine.reset();
+        assert!(root.legal_moves().iter().all(|mv| engine.history().visits(mv) == 0));
+    }
+
+    #[test]
+    fn the_book_comes_first(){
+        let mut engine = Mcts::new(game_state::GameState::new(), short_search());
+        engine.config.use_book = true;
+        assert_eq!(best_move(&mut engine, game_state::GameState::new()), game_state::Move::white_new(3));
+        assert_eq!(best_move(&mut engine, play(&[3])), game_state::Move::black_new(3));
+        assert_eq!(engine.statistics().get(&engine.root()).unwrap().num_plays, 0);
+        //with the book off the same position is searched
+        engine.config.use_book = false;
+        assert!(engine.root().legal(&best_move(&mut engine, play(&[3]))));
+        assert!(engine.statistics().get(&engine.root()).unwrap().num_plays > 0);
+    }
+
+    #[test]
+    fn a_custom_book_is_consulted(){
+        let mut engine = Mcts::new(game_state::GameState::new(), short_search());
+        engine.config.use_book = true;
+        engine.book.insert(play(&[0]), game_state::Move::black_new(6));
+        assert_eq!(best_move(&mut engine, play(&[0])), game_state::Move::black_new(6));
+    }
+
+    #[test]
+    fn tactics_can_be_switched_off(){
+        //white wins in column 3, tactics play it at once, the search alone still finds it
+        let root = play(&[0, 6, 1, 6, 2, 5]);
+        let mut engine = Mcts::new(root, short_search());
+        assert_eq!(best_move(&mut engine, root).x, 3);
+        assert_eq!(engine.statistics().get(&root).unwrap().num_plays, 0);
+        engine.config.use_tactics = false;
+        engine.config.budget = Budget::TimeSeconds(0.2f64);
+        assert_eq!(best_move(&mut engine, root).x, 3);
+        assert!(engine.statistics().get(&root).unwrap().num_plays > 0);
+    }
+
+    #[test]
+    fn without_search_any_legal_move_is_played(){
+        let root = play(&[3, 3]);
+        let config = MctsConfig{ use_search : false, ..short_search() };
+        let mut engine = Mcts::new(root, config);
+        assert!(root.legal(&best_move(&mut engine, root)));
+        assert_eq!(engine.statistics().get(&root).unwrap().num_plays, 0);
+        //tactics still run before giving up on knowing better
+        let threat = play(&[0, 6, 1, 6, 2, 5]);
+        assert_eq!(best_move(&mut engine, threat).x, 3);
+    }
+
+    #[test]
+    fn antithetic_rollouts_mirror_each_other(){
+        //always taking the leftmost move is mirrored by always taking the rightmost, which ends the same way
+        let state = game_state::GameState::new();
+        let (first, second) = antithetic_simulations(state, RolloutOptions::default(), &mut || 0f64);
+        assert_eq!(first, rollout_with_draws(state, RolloutOptions::default(), &mut || 0f64));
+        assert_eq!(second, rollout_with_draws(state, RolloutOptions::default(), &mut || 1f64));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn antithetic_search_backs_up_pairs(){
+        let root = play(&[0, 6, 1, 6, 2, 5]);
+        let config = MctsConfig{ antithetic : true, use_tactics : false, budget : Budget::TimeSeconds(0.2f64), ..short_search() };
+        let mut engine = Mcts::new(root, config);
+        for _ in 0..50{
+            engine.step();
+        }
+        assert_eq!(engine.statistics().get(&root).unwrap().num_plays, 100);
+        assert_eq!(engine.search().x, 3);
+    }
+
+    #[test]
+    fn variance_of_the_backed_up_rewards(){
+        let root = play(&[3]);
+        let path = vec![(root, game_state::Color::White)];
+        let mut stats = empty_stats(&path);
+        assert_eq!(stats[&root].variance(), 0f64);
+        back_propogate(game_state::End::Victory(game_state::Color::White), &mut stats, &path);
+        back_propogate(game_state::End::Victory(game_state::Color::White), &mut stats, &path);
+        assert_eq!(stats[&root].variance(), 0f64);
+        back_propogate(game_state::End::Victory(game_state::Color::Black), &mut stats, &path);
+        back_propogate(game_state::End::Victory(game_state::Color::Black), &mut stats, &path);
+        assert_eq!(stats[&root].sum_squares, 2f64);
+        assert!((stats[&root].variance() - 0.25f64).abs() < 1e-12);
+        //ties sit in the middle, so they pull the spread down
+        for _ in 0..4{
+            back_propogate(game_state::End::Tie, &mut stats, &path);
+        }
+        assert!((stats[&root].variance() - 0.125f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn search_result_reports_throughput(){
+        let mut engine = Mcts::new(play(&[3, 3]), short_search());
+        let result = engine.search_result();
+        assert!(engine.root().legal(&result.best_move));
+        assert!(result.iterations > 0);
+        assert!(result.elapsed >= 0.05f64);
+        assert_eq!(result.nps(), result.iterations as f64 / result.elapsed);
+        assert_eq!(engine.statistics().get(&engine.root()).unwrap().num_plays as u64, result.iterations);
+
+        //a tactical move is not searched at all
+        let mut engine = Mcts::new(play(&[0, 6, 1, 6, 2, 5]), short_search());
+        let result = engine.search_result();
+        assert_eq!((result.best_move.x, result.iterations), (3, 0));
+        assert_eq!(result.nps(), 0f64);
+    }
+
+    #[test]
+    fn one_iteration_still_plays_a_legal_move(){
+        //a single iteration reaches one root child, the others are missing from the statistics.
+        //column 0 is full, so a made up default move would be illegal
+        let root = play(&[0, 0, 0, 0, 0, 0, 3]);
+        let selections = [FinalSelection::MostVisited, FinalSelection::HighestWin, FinalSelection::SecureChild(1f64), FinalSelection::Robust(0.1f64),
+            FinalSelection::Temperature(1f64)];
+        for &final_selection in selections.iter(){
+            let config = MctsConfig{ budget : Budget::Iterations(1), final_selection, use_book : false, ..short_search() };
+            assert!(root.legal(&tree_search_with(root, &config)));
+        }
+    }
+
+    #[test]
+    fn search_result_ranks_every_root_move(){
+        let root = play(&[3, 3]);
+        let mut engine = Mcts::new(root, MctsConfig{ budget : Budget::Iterations(3), fold_symmetry : false, ..short_search() });
+        let result = engine.search_result();
+        assert_eq!(result.moves.len(), root.legal_moves().len());
+        assert!(result.moves.windows(2).all(|pair| pair[0].data.num_plays >= pair[1].data.num_plays));
+        //three iterations leave most moves unvisited, they are listed with nothing rather than left out
+        assert_eq!(result.moves.iter().filter(|child| !child.visited).count(), root.legal_moves().len() - 3);
+        assert!(result.moves.iter().filter(|child| !child.visited).all(|child| child.data.num_plays == 0));
+    }
+
+    #[test]
+    fn search_result_counts_nodes(){
+        let mut engine = Mcts::new(play(&[3, 3]), short_search());
+        let result = engine.search_result();
+        assert_eq!(result.nodes, engine.nodes_explored());
+        assert_eq!(result.nodes, engine.statistics().len());
+        //an iteration opens at most one node, and the root is there from the start
+        assert!(result.nodes > 1 && result.nodes as u64 <= result.iterations + 1);
+        assert_eq!(result.iterations_per_node(), result.iterations as f64 / result.nodes as f64);
+        assert_eq!(SearchResult{ nodes : 0, ..result }.iterations_per_node(), 0f64);
+    }
+
+    #[test]
+    fn exploration_schedules(){
+        assert_eq!(Exploration::Constant(1.5f64).constant(0), 1.5f64);
+        assert_eq!(Exploration::Constant(1.5f64).constant(100000), 1.5f64);
+        let linear = Exploration::Linear{ start : 2f64, end : 0.5f64, iterations : 100 };
+        assert_eq!(linear.constant(0), 2f64);
+        assert_eq!(linear.constant(50), 1.25f64);
+        assert_eq!(linear.constant(100), 0.5f64);
+        assert_eq!(linear.constant(1000), 0.5f64);
+        let exponential = Exploration::Exponential{ start : 3f64, end : 1f64, half_life : 10f64 };
+        assert_eq!(exponential.constant(0), 3f64);
+        assert_eq!(exponential.constant(10), 2f64);
+        assert_eq!(exponential.constant(20), 1.5f64);
+        assert!(exponential.constant(1000) - 1f64 < 1e-9);
+    }
+
+    #[test]
+    fn the_constant_steers_selection(){
+        //one child has done well over many visits, the other is barely tried
+        let root = game_state::GameState::new();
+        let strong = root.place(&game_state::Move::white_new(3));
+        let fresh = root.place(&game_state::Move::white_new(0));
+        let mut visited_states = HashSet::new();
+        let mut stats = HashMap::new();
+        stats.insert(root, UCTData::new(0f64, 101));
+        for mv in root.legal_moves(){
+            let child = root.place(&mv);
+            visited_states.insert(child);
+            stats.insert(child, UCTData::new(0f64, 10));
+        }
+        visited_states.insert(root);
+        stats.insert(strong, UCTData::new(60f64, 90));
+        stats.insert(fresh, UCTData::new(0.3f64, 1));
+        let store = MapStore{ visited_states : &visited_states, stats : &stats, moves : None, history : None };
+        let greedy = MctsConfig{ exploration : Exploration::Constant(0f64), ..MctsConfig::default() };
+        assert_eq!(select(&root, &store, &greedy).path[1].0, strong);
+        let curious = MctsConfig{ exploration : Exploration::Constant(2f64), ..MctsConfig::default() };
+        assert_eq!(select(&root, &store, &curious).path[1].0, fresh);
+        //a schedule that has decayed to nothing by the root's visit count exploits
+        let decayed = MctsConfig{ exploration : Exploration::Linear{ start : 5f64, end : 0f64, iterations : 100 }, ..MctsConfig::default() };
+        assert_eq!(select(&root, &store, &decayed).path[1].0, strong);
+    }
+
+    #[test]
+    fn ucb1_tries_unplayed_children_first(){
+        for &c in [0f64, 0.5f64, 2f64].iter(){
+            assert_eq!(ucb1(0f64, 0f64, 10f64, c), f64::INFINITY);
+        }
+        assert_eq!(ucb1(3f64, 4f64, 1f64, 2f64), 0.75f64);
+        assert!(ucb1(1f64, 2f64, 16f64, 0.5f64) < ucb1(1f64, 2f64, 16f64, 2f64));
+        //the constant scales ln N / n under the root, 2 is sqrt(2 ln N / n)
+        assert_eq!(ucb1(0f64, 2f64, std::f64::consts::E, 4f64), 2f64.sqrt());
+    }
+
+    #[test]
+    fn background_search_runs_its_budget(){
+        let root = play(&[3, 3]);
+        let handle = spawn_search(root, MctsConfig{ budget : Budget::TimeSeconds(0.1f64), ..short_search() });
+        let result = handle.wait();
+        assert!(root.legal(&result.best_move));
+        assert!(result.iterations > 0);
+        assert!(result.elapsed >= 0.1f64);
+    }
+
+    #[test]
+    fn background_search_can_be_stopped(){
+        let root = play(&[3, 3]);
+        let handle = spawn_search(root, MctsConfig{ budget : Budget::TimeSeconds(60f64), ..short_search() });
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        let (best_move, data) = handle.current_best().unwrap();
+        assert!(root.legal(&best_move));
+        assert!(data.num_plays > 0);
+        handle.stop();
+        let result = handle.wait();
+        assert!(result.elapsed < 30f64);
+        assert!(root.legal(&result.best_move));
+    }
+
+    #[test]
+    fn stop_handle_ends_a_think(){
+        //an UntilStopped think on one thread or on several comes back once another thread sets the flag
+        let root = play(&[3, 3]);
+        for &(threads, parallelism) in &[(1, Parallelism::Tree), (2, Parallelism::Tree), (2, Parallelism::Root)]{
+            let mut engine = Mcts::new(root, MctsConfig{ budget : Budget::UntilStopped, threads, parallelism, ..short_search() });
+            let stop = engine.stop_handle();
+            let stopper = thread::spawn(move ||{
+                thread::sleep(std::time::Duration::from_millis(50));
+                stop.store(true, AtomicOrdering::Relaxed);
+            });
+            let (iterations, _) = engine.think();
+            stopper.join().unwrap();
+            assert!(iterations > 0);
+            //and the next think runs its own budget
+            assert!(!engine.stop_handle().load(AtomicOrdering::Relaxed));
+        }
+    }
+
+    #[test]
+    fn background_search_runs_until_stopped(){
+        let root = play(&[3, 3]);
+        assert_eq!(Budget::UntilStopped.think_time(&root), f64::INFINITY);
+        let handle = spawn_search(root, MctsConfig{ budget : Budget::UntilStopped, ..short_search() });
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        handle.stop();
+        let result = handle.wait();
+        assert!(result.iterations > 0 && root.legal(&result.best_move));
+    }
+
+    #[test]
+    fn background_search_plays_tactics_at_once(){
+        let handle = spawn_search(play(&[0, 6, 1, 6, 2, 5]), short_search());
+        let result = handle.wait();
+        assert_eq!((result.best_move.x, result.iterations), (3, 0));
+    }
+
+    #[test]
+    fn confident_searches_stop_early(){
+        //white wins at once in column 3, the search only has to notice
+        let root = play(&[0, 6, 1, 6, 2, 5]);
+        let config = MctsConfig{
+            budget : Budget::TimeSeconds(30f64),
+            use_tactics : false,
+            confidence_target : Some(0.95f64),
+            ..short_search()
+        };
+        let mut engine = Mcts::new(root, config);
+        let result = engine.search_result();
+        assert_eq!(result.best_move.x, 3);
+        assert!(result.elapsed < 15f64);
+        assert!(result.iterations.is_multiple_of(CONFIDENCE_CHECK_EVERY));
+        let data = engine.statistics().get(&root.place(&result.best_move)).unwrap();
+        assert!(data.win_percentage() >= 0.95f64 && data.num_plays >= MIN_CONFIDENT_VISITS);
+    }
+
+    #[test]
+    fn an_unreachable_target_uses_the_whole_budget(){
+        let config = MctsConfig{ confidence_target : Some(2f64), ..short_search() };
+        let mut engine = Mcts::new(play(&[3, 3]), config);
+        assert!(engine.search_result().elapsed >= 0.05f64);
+    }
+
+    #[test]
+    fn review_flags_the_missed_block(){
+        //black lets white's three on the bottom row through, and white takes the win
+        let game = [0, 6, 1, 6, 2, 5, 3, 4];
+        let reviews = review_game(&game, &short_search());
+        assert_eq!(reviews.len(), 7);
+        assert_eq!(reviews[5].played, game_state::Move::black_new(5));
+        assert_eq!(reviews[5].best, game_state::Move::black_new(3));
+        assert!(reviews[5].is_blunder(0.2f64));
+        assert!(reviews[5].eval_after < 0.1f64);
+        assert_eq!(reviews[6].played, reviews[6].best);
+        assert_eq!((reviews[6].eval_before, reviews[6].eval_after), (1f64, 1f64));
+        assert!(!reviews[6].is_blunder(0f64));
+    }
+
+    #[test]
+    fn side_to_move_keeps_statistics_apart(){
+        //the same pieces with the other player to move is another node, backing up one must not touch the other
+        let white = play(&[3, 3]);
+        let mut black = white;
+        black.player = game_state::Color::Black;
+        let path = vec![(white, game_state::Color::Black)];
+        let mut stats = empty_stats(&[(white, game_state::Color::Black), (black, game_state::Color::White)]);
+        assert_eq!(stats.len(), 2);
+        back_propogate(game_state::End::Victory(game_state::Color::Black), &mut stats, &path);
+        assert_eq!((stats[&white].wins, stats[&white].num_plays), (1f64, 1));
+        assert_eq!((stats[&black].wins, stats[&black].num_plays), (0f64, 0));
+    }
+
+    #[test]
+    fn quiet_tree_search_still_plays(){
+        assert!(short_search().quiet);
+        assert!(!MctsConfig::default().quiet);
+        assert_eq!(tree_search_with(play(&[0, 6, 1, 6, 2, 5]), &short_search()).x, 3);
+        assert!(play(&[3, 3]).legal(&tree_search_with(play(&[3, 3]), &short_search())));
+    }
+
+    #[test]
+    fn symmetric_roots_fold_mirrored_moves(){
+        let empty = game_state::GameState::new();
+        let folded = root_moves(&empty, &MctsConfig::default());
+        assert_eq!(folded.iter().map(|mv| mv.x).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(root_moves(&empty, &MctsConfig{ fold_symmetry : false, ..MctsConfig::default() }).len(), 7);
+        assert_eq!(root_moves(&play(&[3, 3]), &MctsConfig::default()).len(), 4);
+        assert_eq!(root_moves(&play(&[2]), &MctsConfig::default()).len(), 7);
+
+        //with the book off the empty board is searched over the four distinct first moves only
+        let mut engine = Mcts::new(empty, short_search());
+        let result = engine.search_result();
+        assert!(result.best_move.x <= 3);
+        for x in 4..7{
+            assert!(engine.statistics().get(&empty.place(&game_state::Move::white_new(x))).is_none());
+        }
+    }
+
+    #[test]
+    fn aggregated_rollouts_make_one_visit(){
+        let root = play(&[3]);
+        let path = vec![(root, game_state::Color::White)];
+        let results = [game_state::End::Victory(game_state::Color::White), game_state::End::Tie,
+            game_state::End::Victory(game_state::Color::Black), game_state::End::Victory(game_state::Color::White)];
+        assert_eq!(rollout_rewards(&results, game_state::Color::White, Backup{ aggregate : true, ..Backup::default() }), vec![(0.625f64, 1, 0)]);
+        assert_eq!(rollout_rewards(&results, game_state::Color::White, Backup::default()).len(), 4);
+
+        let mut separate = empty_stats(&path);
+        back_propogate_all(&results, &mut separate, &path, Backup::default());
+        assert_eq!((separate[&root].wins, separate[&root].num_plays, separate[&root].win_tie), (2.5f64, 4, 3));
+        let mut aggregated = empty_stats(&path);
+        back_propogate_all(&results, &mut aggregated, &path, Backup{ aggregate : true, ..Backup::default() });
+        assert_eq!((aggregated[&root].wins, aggregated[&root].num_plays, aggregated[&root].win_tie), (0.625f64, 1, 1));
+        //the mean is the same either way
+        assert_eq!(separate[&root].wins / separate[&root].num_plays as f64, aggregated[&root].wins);
+    }
+
+    #[test]
+    fn aggregated_antithetic_pairs_count_once(){
+        let root = play(&[3, 3]);
+        let config = MctsConfig{ antithetic : true, aggregate_rollouts : true, ..short_search() };
+        let mut engine = Mcts::new(root, config);
+        for _ in 0..50{
+            engine.step();
+        }
+        assert_eq!(engine.statistics().get(&root).unwrap().num_plays, 50);
+    }
+
+    #[test]
+    fn saved_trees_load_back(){
+        let path = std::env::temp_dir().join(format!("connect_three_tree_{}.bin", std::process::id()));
+        let mut engine = Mcts::new(play(&[3, 3]), short_search());
+        engine.think();
+        save_tree(&engine, &path).unwrap();
+        let mut loaded = load_tree(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.root(), engine.root());
+        assert_eq!(loaded.config, engine.config);
+        assert_eq!(loaded.statistics().len(), engine.statistics().len());
+        for (state, data) in engine.statistics().iter(){
+            let other = loaded.statistics()[state];
+            assert_eq!((other.wins, other.num_plays, other.win_tie), (data.wins, data.num_plays, data.win_tie));
+        }
+        assert_eq!(loaded.best_move(), engine.best_move());
+        //and the search carries on from there
+        let visits = loaded.statistics()[&loaded.root()].num_plays;
+        loaded.think();
+        assert!(loaded.statistics()[&loaded.root()].num_plays > visits);
+    }
+
+    #[test]
+    fn loading_garbage_is_an_error(){
+        let path = std::env::temp_dir().join(format!("connect_three_garbage_{}.bin", std::process::id()));
+        std::fs::write(&path, b"not a tree").unwrap();
+        assert!(load_tree(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_tree(&path).is_err());
+
+        //a root whose width was damaged to 20, more columns than a board holds
+        let engine = Mcts::new(game_state::GameState::new(), short_search());
+        save_tree(&engine, &path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        let size = [7u64.to_le_bytes(), 6u64.to_le_bytes()].concat();
+        let at = bytes.windows(size.len()).position(|window| window == size.as_slice()).unwrap();
+        bytes[at] = 20;
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(load_tree(&path).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn phases_are_timed_on_request(){
+        let config = MctsConfig{ time_phases : true, use_tactics : false, ..short_search() };
+        let result = Mcts::new(play(&[3]), config).search_result();
+        let phases = result.phases.unwrap();
+        assert!(phases.selection > 0f64 && phases.expansion > 0f64);
+        assert!(phases.simulation > 0f64 && phases.backpropagation > 0f64);
+        //the phases are part of the search, the rest is the loop around them
+        assert!(phases.total() <= result.elapsed);
+        assert!(phases.total() > result.elapsed / 2f64);
+
+        let untimed = Mcts::new(play(&[3]), MctsConfig{ use_tactics : false, ..short_search() }).search_result();
+        assert_eq!(untimed.phases, None);
+        //instant moves never get as far as a phase
+        let instant = Mcts::new(play(&[0, 6, 1, 6, 2, 5]), MctsConfig{ time_phases : true, ..short_search() }).search_result();
+        assert_eq!(instant.phases, None);
+    }
+
+    #[test]
+    fn max_depth_bounds_the_tree(){
+        let root = play(&[3, 2]);
+        for &depth in &[0usize, 1, 2]{
+            let mut engine = Mcts::new(root, MctsConfig{ max_depth : Some(depth), use_tactics : false, ..short_search() });
+            engine.think();
+            let deepest = engine.statistics().keys().map(|state| state.ply() - root.ply()).max().unwrap();
+            assert_eq!(deepest, depth.max(1));
+            //the nodes at the cap keep getting rolled out from
+            assert!(root.legal_moves().iter().all(|mv| engine.statistics()[&root.place(mv)].num_plays > 1));
+            engine.best_move();
+        }
+    }
+
+    #[test]
+    fn explains_tactical_moves(){
+        let (mv, why) = explain_move(play(&[0, 6, 1, 6, 2, 5]), &short_search());
+        assert_eq!(mv.x, 3);
+        assert_eq!(why, "wins immediately in column 4");
+        let (mv, why) = explain_move(play(&[0, 6, 1, 6, 2]), &short_search());
+        assert_eq!(mv.x, 3);
+        assert!(why.starts_with("blocks opponent's threat in column 4"), "{}", why);
+    }
+
+    #[test]
+    fn explains_double_threats_and_statistics(){
+        //white to move with two on the bottom row, a third in column 4 leaves both ends open
+        let root = play(&[1, 1, 2, 2]);
+        let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, ..short_search() });
+        engine.think();
+        let why = explain(&engine, game_state::Move::new(3, root.player));
+        assert!(why.starts_with("creates a double threat, most-searched move with "), "{}", why);
+        assert!(why.contains("% win rate, expecting "), "{}", why);
+
+        let why = explain(&engine, game_state::Move::new(6, root.player));
+        assert!(!why.contains("double threat"), "{}", why);
+        let book = Mcts::new(game_state::GameState::new(), MctsConfig::default());
+        assert_eq!(explain(&book, game_state::Move::new(3, game_state::Color::White)), "plays the opening book move");
+    }
+
+    #[test]
+    fn principal_variation_can_start_from_any_root_move(){
+        //without folding, so every root move has statistics of its own
+        let mut engine = Mcts::new(play(&[3]), MctsConfig{ budget : Budget::Iterations(500), fold_symmetry : false, ..short_search() });
+        engine.think();
+        //the runner up, whose line differs from the principal variation from the first move on.
+        //on equal visits ranked_children keeps legal_moves order where the line breaks the tie, so skip past its first move
+        let first = engine.principal_variation(1)[0];
+        let second = engine.ranked_children().iter().map(|child| child.mv).find(|&mv| mv != first).unwrap();
+        let line = engine.principal_variation_from(second, 3);
+        assert_eq!(line.len(), 3);
+        assert_eq!(line[0], second);
+        assert_ne!(line[0], first);
+        let after = engine.root().place(&second);
+        let visits = |mv : &game_state::Move| engine.node(&after.place(mv)).map_or(0, |data| data.num_plays);
+        assert_eq!(visits(&line[1]), after.legal_moves().iter().map(visits).max().unwrap());
+        assert!(engine.principal_variation_from(second, 0).is_empty());
+    }
+
+    #[test]
+    fn principal_variation_follows_the_most_visited_children(){
+        let mut engine = Mcts::new(play(&[3, 2]), MctsConfig{ use_tactics : false, ..short_search() });
+        assert!(engine.principal_variation(5).is_empty());
+        engine.think();
+        let line = engine.principal_variation(3);
+        assert_eq!(line.len(), 3);
+        let root = engine.root();
+        let visits = |mv : &game_state::Move| engine.statistics()[&root.place(mv)].num_plays;
+        assert_eq!(visits(&line[0]), root.legal_moves().iter().map(visits).max().unwrap());
+        assert!(engine.principal_variation(0).is_empty());
+    }
+
+    #[test]
+    fn seeds_fix_the_search(){
+        let searched = |selection_seed, rollout_seed|{
+            let config = MctsConfig{ selection_seed, rollout_seed, antithetic : true, ..short_search() };
+            let mut engine = Mcts::new(play(&[3, 2]), config);
+            for _ in 0..300{
+                engine.step();
+            }
+            let mut stats = engine.statistics().iter().map(|(state, data)| (*state, data.num_plays, data.win_tie)).collect::<Vec<_>>();
+            stats.sort_by_key(|&(state, _, _)| format!("{:?}", state));
+            stats
+        };
+        let fixed = searched(Some(1), Some(2));
+        assert_eq!(searched(Some(1), Some(2)), fixed);
+        //either seed on its own changes what the search sees
+        assert_ne!(searched(Some(1), Some(3)), fixed);
+        assert_ne!(searched(Some(4), Some(2)), fixed);
+        assert_ne!(searched(None, None), fixed);
+    }
+
+    #[test]
+    fn safe_moves_keep_the_opponent_from_winning(){
+        //black threatens the bottom row, every white move but the block in column 3 loses at once
+        let state = play(&[0, 6, 0, 5, 1, 4]);
+        assert_eq!(state.player, game_state::Color::White);
+        assert_eq!(safe_moves(&state, state.legal_moves()), vec![game_state::Move::white_new(3)]);
+        //with two threats nothing is safe, so every move stays
+        let lost = play(&[0, 2, 0, 3, 6, 4]);
+        assert_eq!(safe_moves(&lost, lost.legal_moves()), lost.legal_moves());
+        assert_eq!(safe_moves(&game_state::GameState::new(), game_state::GameState::new().legal_moves()).len(), 7);
+    }
+
+    #[test]
+    fn safe_rollouts_block_the_threat(){
+        //drawing 0 every time would play column 0 and let black win, a safe rollout blocks first
+        let state = play(&[0, 6, 0, 5, 1, 4]);
+        assert_eq!(rollout_with_draws(state, RolloutOptions::default(), &mut || 0f64), game_state::End::Victory(game_state::Color::Black));
+        let blocked = state.place(&game_state::Move::white_new(3));
+        let safe = RolloutOptions{ safe : true, ..RolloutOptions::default() };
+        assert_eq!(rollout_with_draws(state, safe, &mut || 0f64), rollout_with_draws(blocked, safe, &mut || 0f64));
+        //a move that cannot be played here is not a way out
+        let full = play(&[0, 0, 0, 0, 0, 0]);
+        assert_eq!(safe_moves(&full, vec![game_state::Move::white_new(0), game_state::Move::white_new(1)]), vec![game_state::Move::white_new(1)]);
+    }
+
+    #[test]
+    fn child_stats_look_up_a_column(){
+        let root = play(&[3, 2]);
+        let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, ..short_search() });
+        engine.think();
+        let data = child_stats(&root, engine.statistics(), 5).unwrap();
+        assert_eq!(data.num_plays, engine.statistics()[&root.place(&game_state::Move::white_new(5))].num_plays);
+        assert!(data.num_plays > 0);
+        assert!(child_stats(&root, engine.statistics(), 7).is_none());
+        assert!(child_stats(&root, &HashMap::new(), 5).is_none());
+        let full = play(&[0, 0, 0, 0, 0, 0]);
+        assert!(child_stats(&full, engine.statistics(), 0).is_none());
+    }
+
+    #[test]
+    fn rollouts_score_a_full_board_as_a_tie(){
+        let rows = ["xoxxoxo", "xoxxoxo", "xoxxoxo", "oxooxox", "oxooxox", "oxooxox"];
+        let mut grid = rows.iter().map(|row| row.chars().map(|cell| match cell{
+            'x' => Some(game_state::Color::White),
+            _ => Some(game_state::Color::Black)
+        }).collect::<Vec<_>>()).collect::<Vec<_>>();
+        let full = game_state::GameState::from_grid(&grid, game_state::Color::White).unwrap();
+        assert_eq!(run_simulation(full, full.player), game_state::End::Tie);
+        grid[0][0] = None;
+        let last = game_state::GameState::from_grid(&grid, game_state::Color::White).unwrap();
+        assert_eq!(run_simulation(last, last.player), game_state::End::Tie);
+        assert_eq!(rollout_with_draws(last, RolloutOptions::default(), &mut rand::random::<f64>), game_state::End::Tie);
+        assert_eq!(get_result_value(game_state::End::Tie, game_state::Color::White), get_result_value(game_state::End::Tie, game_state::Color::Black));
+    }
+
+    #[test]
+    fn restricted_searches_stay_on_the_candidates(){
+        let root = play(&[3, 2]);
+        let candidates = [game_state::Move::white_new(0), game_state::Move::white_new(6)];
+        let config = MctsConfig{ use_tactics : false, ..short_search() };
+        let mut engine = Mcts::new(root, config);
+        engine.restrict(&candidates);
+        engine.think();
+        for mv in root.legal_moves(){
+            let visits = engine.statistics().get(&root.place(&mv)).map_or(0, |data| data.num_plays);
+            assert_eq!(visits > 0, candidates.contains(&mv), "{:?}", mv);
+        }
+        assert!(candidates.contains(&engine.best_move()));
+        assert!(candidates.contains(&engine.principal_variation(1)[0]));
+        assert!(candidates.contains(&tree_search_restricted(root, &candidates, &config)));
+
+        //lifting the restriction opens the other moves again
+        engine.unrestrict();
+        engine.think();
+        assert!(root.legal_moves().iter().all(|mv| engine.statistics().contains_key(&root.place(mv))));
+
+        //asking for several threads still keeps every iteration on the candidates
+        let mut threaded = Mcts::new(root, MctsConfig{ threads : 4, budget : Budget::Iterations(2000), ..config });
+        threaded.restrict(&candidates);
+        threaded.think();
+        let searched = candidates.iter().map(|mv| threaded.node(&root.place(mv)).unwrap().num_plays).sum::<i32>();
+        assert_eq!(searched, 2000);
+    }
+
+    #[test]
+    fn restrictions_overrule_tactics_and_symmetry(){
+        //white wins in column 3 but is only allowed to look at columns 0 and 5
+        let root = play(&[0, 6, 1, 6, 2, 5]);
+        let candidates = [game_state::Move::white_new(0), game_state::Move::white_new(5), game_state::Move::white_new(7)];
+        let best_move = tree_search_restricted(root, &candidates, &short_search());
+        assert!(best_move.x == 0 || best_move.x == 5, "{:?}", best_move);
+        //on the symmetric empty board a lone move on the right is not folded away
+        let empty = game_state::GameState::new();
+        assert_eq!(tree_search_restricted(empty, &[game_state::Move::white_new(5)], &short_search()).x, 5);
+        //with nothing legal to restrict to every move is searched
+        let mut engine = Mcts::new(empty, short_search());
+        engine.restrict(&[game_state::Move::white_new(9)]);
+        assert_eq!(engine.root_moves().len(), 4);
+    }
+
+    #[test]
+    fn threads_default_to_the_core_count(){
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        assert_eq!(MctsConfig::default().thread_count(), cores);
+        assert_eq!(MctsConfig{ threads : 3, ..MctsConfig::default() }.thread_count(), 3);
+        assert_eq!(MctsConfig{ threads : 1, ..MctsConfig::default() }.thread_count(), 1);
+        //options the parallel searches do not carry out keep the search on one thread
+        let four = MctsConfig{ threads : 4, ..MctsConfig::default() };
+        assert_eq!(MctsConfig{ confidence_target : Some(0.9f64), ..four }.thread_count(), 1);
+        assert_eq!(MctsConfig{ time_phases : true, ..four }.thread_count(), 1);
+        assert_eq!(MctsConfig{ history_weight : Some(1f64), ..four }.thread_count(), 1);
+    }
+
+    #[test]
+    fn root_children_list_every_legal_move(){
+        let root = play(&[3, 2]);
+        let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, ..short_search() });
+        let children = engine.root_children();
+        assert_eq!(children.iter().map(|child| child.mv).collect::<Vec<_>>(), root.legal_moves());
+        assert!(children.iter().all(|child| !child.visited && child.data.num_plays == 0));
+        engine.step();
+        assert_eq!(engine.root_children().iter().filter(|child| child.visited).count(), 1);
+        engine.think();
+        for child in engine.root_children(){
+            assert!(child.visited);
+            assert_eq!(child.data.num_plays, engine.statistics()[&root.place(&child.mv)].num_plays);
+        }
+        //restricted moves are listed but never visited
+        engine.reset();
+        engine.restrict(&[game_state::Move::white_new(1)]);
+        engine.think();
+        let visited = engine.root_children().iter().filter(|child| child.visited).map(|child| child.mv.x).collect::<Vec<_>>();
+        assert_eq!(visited, vec![1]);
+    }
+
+    #[test]
+    fn folded_root_children_borrow_their_mirror(){
+        let mut engine = Mcts::new(game_state::GameState::new(), short_search());
+        engine.think();
+        let children = engine.root_children();
+        assert_eq!(children.len(), 7);
+        for child in children.iter(){
+            let mirror = &children[6 - child.mv.x];
+            assert!(child.visited);
+            assert_eq!(child.data.num_plays, mirror.data.num_plays);
+        }
+    }
+
+    #[test]
+    fn full_expansion_adds_every_child_at_once(){
+        let root = play(&[3, 2]);
+        let mut engine = Mcts::new(root, MctsConfig{ full_expansion : true, ..short_search() });
+        assert_eq!(engine.statistics().len(), 8);
+        engine.step();
+        //the visited child brings all seven of its own children with it
+        assert_eq!(engine.statistics().len(), 15);
+        assert_eq!(engine.statistics().values().filter(|data| data.num_plays > 0).count(), 2);
+        //every child is simulated once before any is visited twice
+        for _ in 0..6{
+            engine.step();
+        }
+        assert!(root.legal_moves().iter().all(|mv| engine.statistics()[&root.place(mv)].num_plays == 1));
+        assert_eq!(engine.statistics().len(), 8 + 7 * 7);
+        let mut plain = Mcts::new(root, short_search());
+        plain.step();
+        assert_eq!(plain.statistics().len(), 2);
+    }
+
+    #[test]
+    fn full_expansion_still_finds_the_win(){
+        let config = MctsConfig{ full_expansion : true, use_tactics : false, ..short_search() };
+        assert_eq!(tree_search_with(play(&[0, 6, 1, 6, 2, 5]), &config).x, 3);
+        let config = MctsConfig{ threads : 2, ..config };
+        assert_eq!(tree_search_with(play(&[0, 6, 1, 6, 2, 5]), &config).x, 3);
+    }
+
+    #[test]
+    fn stopping_at_a_win_scores_it_as_played(){
+        let stop = RolloutOptions{ stop_at_win : true, ..RolloutOptions::default() };
+        let mut rng = seeded_rng(Some(179));
+        for state in random_positions(40){
+            let wins = state.winning_moves(state.player);
+            let stopped = rollout_with_draws(state, stop, &mut || rng.gen::<f64>());
+            if state.is_terminal(){
+                assert_eq!(stopped, state.win());
+            }
+            else if !wins.is_empty(){
+                //the result is the one taking the win gives, whichever winning move that is
+                for mv in &wins{
+                    assert_eq!(stopped, state.place(mv).win());
+                    assert_eq!(stopped, rollout_with_draws(state.place(mv), RolloutOptions::default(), &mut || rng.gen::<f64>()));
+                }
+                assert_eq!(stopped, run_simulation_with(state, state.player, stop, &mut rng));
+            }
+        }
+        //black to move can win in column 3, a playout drawing 0 every time misses it and only ties
+        let state = play(&[0, 3, 1, 3, 6, 3, 0]);
+        assert_eq!(rollout_with_draws(state, RolloutOptions::default(), &mut || 0f64), game_state::End::Tie);
+        assert_eq!(rollout_with_draws(state, stop, &mut || 0f64), game_state::End::Victory(game_state::Color::Black));
+    }
+
+    fn random_positions(count : usize) -> Vec<game_state::GameState>{
+        //every prefix of some random games, on a few boards and both variants, finished games included
+        let mut rng = seeded_rng(Some(162));
+        let starts = [
+            game_state::GameState::new(),
+            game_state::GameState::with_variant(game_state::Variant::PopOut),
+            game_state::GameState::with_size(4, 4).unwrap(),
+            game_state::GameState::with_size_and_variant(5, 4, game_state::Variant::PopOut).unwrap()
+        ];
+        let mut positions = Vec::new();
+        for game in 0..count{
+            let mut state = starts[game % starts.len()];
+            positions.push(state);
+            while !state.is_terminal() && state.move_count() < 60{
+                let moves = state.legal_moves();
+                if moves.is_empty(){
+                    break;
+                }
+                state = state.place(&choose_random_with(&moves, &mut rng));
+                positions.push(state);
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn never_panics_on_random_positions(){
+        let config = MctsConfig{ budget : Budget::TimeSeconds(0.001f64), ..short_search() };
+        let configs = [
+            config,
+            MctsConfig{ use_tactics : false, full_expansion : true, antithetic : true, safe_rollouts : true, ..config },
+            MctsConfig{ use_tactics : false, progressive_widening : Some(Widening{ coefficient : 1f64, exponent : 0.5f64 }), max_depth : Some(2), ..config },
+            MctsConfig{ use_tactics : false, threads : 2, ..config },
+            MctsConfig{ use_tactics : false, threads : 2, parallelism : Parallelism::Root, ..config }
+        ];
+        for (i, state) in random_positions(12).into_iter().enumerate(){
+            run_simulation(state, state.player);
+            tree_policy(&state, &HashSet::new(), &HashMap::new());
+            let best_move = tree_search_with(state, &configs[i % configs.len()]);
+            if !state.is_terminal(){
+                assert!(state.legal(&best_move), "{:?} is not legal in\n{}", best_move, state.print());
+            }
+        }
+    }
+
+    #[test]
+    fn backup_alpha_weights_recent_visits(){
+        let root = play(&[3]);
+        let white = game_state::Color::White;
+        let path = vec![(root, white)];
+        let results = [game_state::End::Victory(white), game_state::End::Victory(game_state::Color::Black), game_state::End::Victory(game_state::Color::Black)];
+        let mut averaged = empty_stats(&path);
+        back_propogate_all(&results, &mut averaged, &path, Backup::default());
+        let mut weighted = empty_stats(&path);
+        back_propogate_all(&results, &mut weighted, &path, Backup{ alpha : Some(0.5f64), ..Backup::default() });
+        //1, then halfway to 0, then halfway again, against a third for the plain average
+        assert_eq!(weighted[&root].num_plays, 3);
+        assert!((weighted[&root].wins / 3f64 - 0.25f64).abs() < 1e-9);
+        assert!((averaged[&root].wins / 3f64 - 1f64 / 3f64).abs() < 1e-9);
+        assert_eq!(weighted[&root].win_tie, averaged[&root].win_tie);
+        assert_eq!(Backup::from_config(&MctsConfig{ backup_alpha : Some(0.1f64), ..MctsConfig::default() }, &root, &root).alpha, Some(0.1f64));
+    }
+
+    #[test]
+    fn rewards_on_the_symmetric_scale(){
+        let scale = RewardScale::MinusOneToOne;
+        let white = game_state::Color::White;
+        assert_eq!(scale.value(game_state::End::Victory(white), white), 1f64);
+        assert_eq!(scale.value(game_state::End::Tie, white), 0f64);
+        assert_eq!(scale.value(game_state::End::Victory(game_state::Color::Black), white), -1f64);
+        assert_eq!((scale.loss(), scale.width(), scale.to_unit(0.5f64)), (-1f64, 2f64, 0.75f64));
+        assert_eq!((RewardScale::ZeroToOne.loss(), RewardScale::ZeroToOne.width()), (0f64, 1f64));
+
+        let root = play(&[3]);
+        let path = vec![(root, white)];
+        let results = [game_state::End::Victory(white), game_state::End::Tie, game_state::End::Victory(game_state::Color::Black)];
+        let mut stats = empty_stats(&path);
+        back_propogate_all(&results, &mut stats, &path, Backup{ scale, ..Backup::default() });
+        assert_eq!((stats[&root].wins, stats[&root].num_plays, stats[&root].win_tie), (0f64, 3, 2));
+    }
+
+    #[test]
+    fn selection_agrees_across_reward_scales(){
+        //the same visits on either scale, with the exploration term stretched to match, lead selection the same way
+        let root = play(&[3, 2]);
+        let mut unit = HashMap::new();
+        let mut symmetric = HashMap::new();
+        unit.insert(root, UCTData::new(0f64, 70));
+        symmetric.insert(root, UCTData::new(0f64, 70));
+        for (i, mv) in root.legal_moves().iter().enumerate(){
+            let (wins, plays) = (i as f64 + 1f64, 10);
+            unit.insert(root.place(mv), UCTData::new(wins, plays));
+            symmetric.insert(root.place(mv), UCTData::new(2f64 * wins - plays as f64, plays));
+        }
+        let config = MctsConfig{ fold_symmetry : false, ..MctsConfig::default() };
+        let scaled = MctsConfig{ reward_scale : RewardScale::MinusOneToOne, ..config };
+        let visited_states = unit.keys().cloned().collect::<HashSet<_>>();
+        let chosen = select(&root, &MapStore{ visited_states : &visited_states, stats : &unit, moves : None, history : None }, &config).path[1].0;
+        assert_eq!(select(&root, &MapStore{ visited_states : &visited_states, stats : &symmetric, moves : None, history : None }, &scaled).path[1].0, chosen);
+        assert_eq!(chosen, root.place(&game_state::Move::new(6, game_state::Color::White)));
+        //every child losing every game still leaves something to choose
+        for data in symmetric.values_mut(){
+            data.wins = -(data.num_plays as f64);
+        }
+        assert!(symmetric.contains_key(&select(&root, &MapStore{ visited_states : &visited_states, stats : &symmetric, moves : None, history : None }, &scaled).path[1].0));
+    }
+
+    #[test]
+    fn searches_on_the_symmetric_scale(){
+        let config = MctsConfig{ reward_scale : RewardScale::MinusOneToOne, use_tactics : false, antithetic : true, ..short_search() };
+        assert_eq!(tree_search_with(play(&[0, 6, 1, 6, 2, 5]), &config).x, 3);
+        let highest = MctsConfig{ final_selection : FinalSelection::HighestWin, ..config };
+        let root = play(&[0, 6, 1, 6, 2, 5]);
+        assert_eq!(tree_search_with(root, &highest).x, 3);
+        //a review still reports expected rewards between 0 and 1
+        let reviews = review_game(&[3, 3], &MctsConfig{ budget : Budget::TimeSeconds(0.02f64), ..config });
+        assert!(reviews.iter().all(|review| (0f64..=1f64).contains(&review.eval_after)));
+    }
+
+    #[test]
+    fn one_seed_repeats_the_move(){
+        let config = MctsConfig{ budget : Budget::Iterations(5000), threads : 1, quiet : true, use_book : false, ..MctsConfig::default() }.with_seed(254);
+        assert_ne!(config.selection_seed, config.rollout_seed);
+        let root = game_state::GameState::new();
+        assert_eq!(tree_search_with(root, &config), tree_search_with(root, &config));
+
+        //asking for threads does not undo it, the whole tree comes out the same
+        let threaded = MctsConfig{ threads : 4, budget : Budget::Iterations(3000), ..config }.with_seed(510);
+        assert_eq!(threaded.thread_count(), 1);
+        let visits = ||{
+            let mut engine = Mcts::new(play(&[3]), threaded);
+            engine.think();
+            engine.root_children().iter().map(|child| child.data.num_plays).collect::<Vec<_>>()
+        };
+        assert_eq!(visits(), visits());
+    }
+
+    #[test]
+    fn reported_seeds_replay_the_search(){
+        let root = play(&[3, 2]);
+        let config = MctsConfig{ use_tactics : false, ..short_search() };
+        let mut engine = Mcts::new(root, config);
+        let result = engine.search_result();
+        let seeds = result.seeds.unwrap();
+        assert!(result.iterations > 0);
+        let replayed = replay_search(root, &config, &seeds, result.iterations);
+        assert_eq!(replayed.best_move(), result.best_move);
+        for mv in root.legal_moves(){
+            let (original, again) = (engine.statistics()[&root.place(&mv)], replayed.statistics()[&root.place(&mv)]);
+            assert_eq!((original.wins, original.num_plays), (again.wins, again.num_plays));
+        }
+        //fixed seeds are reported as they were given, and nothing is reported without a search
+        let fixed = MctsConfig{ selection_seed : Some(5), rollout_seed : Some(6), ..config };
+        assert_eq!(Mcts::new(root, fixed).search_result().seeds, Some(SearchSeeds{ selection : 5, rollout : 6 }));
+        assert_eq!(Mcts::new(play(&[0, 6, 1, 6, 2, 5]), short_search()).search_result().seeds, None);
+    }
+
+    #[test]
+    fn primed_lines_start_in_the_tree(){
+        let root = game_state::GameState::new();
+        let mut engine = Mcts::new(root, short_search());
+        prime_with(&mut engine, &[&[3, 3, 2], &[3, 2]]).unwrap();
+        let after = |columns : &[usize]| columns.iter().fold(root, |state, &x| state.place(&game_state::Move::new(x, state.player)));
+        //a position's own visit plus one for each new position below it
+        for &(line, plays) in &[(&[3][..], 4), (&[3, 3], 2), (&[3, 3, 2], 1), (&[3, 2], 1)]{
+            assert_eq!(engine.statistics()[&after(line)].num_plays, plays);
+        }
+        //every new position's visit also counts at the root, and priming a line again adds nothing
+        assert_eq!(engine.statistics()[&root].num_plays, 4);
+        prime_with(&mut engine, &[&[3, 3]]).unwrap();
+        assert_eq!(engine.statistics()[&root].num_plays, 4);
+        assert_eq!(engine.current_best().map(|(mv, _)| mv.x), Some(3));
+        //a line that can not be played leaves the tree as it was
+        assert_eq!(prime_with(&mut engine, &[&[0, 9]]), Err(game_state::GridError::Unplayable{ column : 9 }));
+        assert!(!engine.statistics().contains_key(&after(&[0])));
+        assert_eq!(prime_with(&mut engine, &[&[0; 7]]), Err(game_state::GridError::Unplayable{ column : 0 }));
+        //the search carries on from the primed tree
+        engine.think();
+        assert!(engine.statistics()[&root].num_plays > 4);
+    }
+
+    #[test]
+    fn searches_for_either_side(){
+        //white to move, but black has three on the bottom row and would win at once
+        let root = play(&[6, 0, 6, 1, 5, 2]);
+        assert_eq!(root.player, game_state::Color::White);
+        let mv = tree_search_as(root, game_state::Color::Black, &short_search());
+        assert_eq!(mv, game_state::Move::new(3, game_state::Color::Black));
+        assert_eq!(tree_search_as(root, game_state::Color::White, &short_search()), game_state::Move::new(3, game_state::Color::White));
+    }
+
+    #[test]
+    fn checks_the_clock_every_interval(){
+        let root = play(&[3, 2]);
+        let config = MctsConfig{ check_interval : 100, use_tactics : false, budget : Budget::TimeSeconds(0.01f64), ..short_search() };
+        let result = Mcts::new(root, config).search_result();
+        assert!(result.iterations >= 100 && result.iterations.is_multiple_of(100), "{}", result.iterations);
+        //a search stopped by its confidence target still makes whole intervals, enough of them for the
+        //best of seven children to have the visits the target asks for
+        let confident = MctsConfig{ confidence_target : Some(0f64), check_interval : 1500, budget : Budget::TimeSeconds(60f64), ..config };
+        assert_eq!(Mcts::new(root, confident).search_result().iterations, 1500);
+        assert_eq!(MctsConfig{ check_interval : 0, ..config }.check_every(), 1);
+    }
+
+    #[test]
+    fn expansion_order_picks_the_first_child(){
+        let root = play(&[3, 2]);
+        let first_child = |order| {
+            let mut engine = Mcts::new(root, MctsConfig{ expansion_order : order, use_tactics : false, ..short_search() });
+            engine.step();
+            root.legal_moves().into_iter().find(|mv| engine.statistics().contains_key(&root.place(mv))).unwrap()
+        };
+        assert_eq!(first_child(ExpansionOrder::CenterFirst).x, 3);
+        let chosen = ExpansionOrder::Evaluation.choose(&root, &root.legal_moves(), &mut rand::thread_rng());
+        let value = |mv : &game_state::Move| evaluation::evaluate(&root.place(mv), game_state::Color::White);
+        assert!(root.legal_moves().iter().all(|mv| value(mv) <= value(&chosen)));
+        assert_eq!(first_child(ExpansionOrder::Evaluation), chosen);
+        //center first then works outwards, left before right
+        let order = (0..7).scan(root.legal_moves(), |moves, _|{
+            let mv = ExpansionOrder::CenterFirst.choose(&root, moves, &mut rand::thread_rng());
+            moves.retain(|other| *other != mv);
+            Some(mv.x)
+        }).collect::<Vec<_>>();
+        assert_eq!(order, vec![3, 2, 4, 1, 5, 0, 6]);
+    }
+
+    #[test]
+    fn priors_order_the_expansion(){
+        let priors = center_priors(7);
+        assert!((priors.iter().sum::<f64>() - 1f64).abs() < 1e-9);
+        assert_eq!((priors[0], priors[3], priors[7]), (1f64 / 16f64, 4f64 / 16f64, 0f64));
+        assert_eq!(priors[2], priors[4]);
+
+        let root = play(&[3, 2]);
+        let mut favour_five = [0.1f64; game_state::MAX_WIDTH];
+        favour_five[5] = 0.4f64;
+        let mut engine = Mcts::new(root, MctsConfig{ expansion_order : ExpansionOrder::Priors(favour_five), use_tactics : false, ..short_search() });
+        engine.step();
+        assert!(engine.statistics().contains_key(&root.place(&game_state::Move::white_new(5))));
+        //equal priors leave the choice to chance among them
+        let tied = ExpansionOrder::Priors(priors).choose(&root, &[game_state::Move::white_new(2), game_state::Move::white_new(4), game_state::Move::white_new(6)], &mut rand::thread_rng());
+        assert!(tied.x == 2 || tied.x == 4);
+    }
+
+    #[test]
+    fn traces_every_iteration(){
+        let root = play(&[3, 2]);
+        let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, ..short_search() });
+        let mut out = Vec::new();
+        let result = engine.search_traced(&mut out).unwrap();
+        let trace = String::from_utf8(out).unwrap();
+        let lines = trace.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len() as u64, result.iterations + 7);
+        assert!(lines[0].starts_with("iteration 1: "));
+        //the per iteration lines add up to the visits in the summary
+        let summary = &lines[lines.len() - 7..];
+        for (line, mv) in summary.iter().zip(root.legal_moves().iter()){
+            let visits = lines.iter().filter(|other| other.ends_with(&format!(": {}", mv.x + 1))).count();
+            assert_eq!(*line, format!("{}: {} visits, {:.3} reward, {} wins or ties", mv.x + 1, visits,
+                engine.statistics()[&root.place(mv)].wins, engine.statistics()[&root.place(mv)].win_tie));
+        }
+        assert!(root.legal(&result.best_move));
+
+        //the breakdown is added on request
+        let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, report_outcomes : true, ..short_search() });
+        let mut out = Vec::new();
+        engine.search_traced(&mut out).unwrap();
+        let trace = String::from_utf8(out).unwrap();
+        let first = root.legal_moves()[0];
+        let outcomes = engine.statistics()[&root.place(&first)].outcomes();
+        let line = trace.lines().find(|line| line.starts_with("1: ")).unwrap();
+        assert!(line.ends_with(&format!(" ({} wins, {} ties, {} losses)", outcomes.wins, outcomes.ties, outcomes.losses)));
+
+        let mut out = Vec::new();
+        Mcts::new(play(&[0, 6, 1, 6, 2, 5]), short_search()).search_traced(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "nothing searched, 4 came before the search\n");
+        assert_eq!(move_name(&game_state::Move::pop(2, game_state::Color::White)), "p3");
+    }
+
+    #[test]
+    fn barely_tried_moves_report_no_win_rate(){
+        let mut twice = UCTData::new(2f64, 2);
+        twice.win_tie = 2;
+        assert_eq!(twice.reported_win_rate(10), None);
+        assert_eq!(twice.reported_win_rate(2), Some(1f64));
+        assert_eq!(UCTData::new(0f64, 0).reported_win_rate(0), None);
+
+        let root = play(&[3, 2]);
+        let config = MctsConfig{ use_tactics : false, min_visits_for_winrate : 1000000, ..short_search() };
+        let mut engine = Mcts::new(root, config);
+        engine.think();
+        assert!(engine.root_children().iter().all(|child| child.visited && child.win_rate.is_none()));
+        engine.config.min_visits_for_winrate = 1;
+        assert!(engine.root_children().iter().all(|child| child.win_rate == Some(child.data.win_percentage())));
+        let (_, why) = explain_move(root, &config);
+        assert!(why.contains("too little searched for a win rate"), "{}", why);
+    }
+
+    #[test]
+    fn searches_from_a_handicap_start(){
+        //black starts with three on the bottom row, so white has to block at once
+        let root = game_state::GameState::new().handicap(game_state::Color::Black, &[0, 1, 2]).unwrap();
+        assert_eq!(tree_search_with(root, &MctsConfig{ use_tactics : false, budget : Budget::Iterations(2000), ..short_search() }).x, 3);
+        //and a lighter handicap still gets a legal move from every stage
+        let root = game_state::GameState::new().handicap(game_state::Color::Black, &[3]).unwrap();
+        assert!(root.legal(&tree_search_with(root, &short_search())));
+        assert!(root.legal(&tree_search_with(root, &MctsConfig{ use_book : true, ..short_search() })));
+    }
+
+    #[test]
+    fn the_most_defensive_move_stops_the_threat(){
+        //black threatens the bottom row, every move but the block lets them win at once
+        let root = play(&[6, 0, 6, 1, 5, 2]);
+        let config = MctsConfig{ budget : Budget::TimeSeconds(0.01f64), ..short_search() };
+        assert_eq!(most_defensive_move(root, &config), Some(game_state::Move::new(3, game_state::Color::White)));
+        //a move that wins outright leaves the opponent nothing at all
+        let root = play(&[0, 6, 1, 6, 2, 5]);
+        assert_eq!(most_defensive_move(root, &config).map(|mv| mv.x), Some(3));
+    }
+
+    #[test]
+    fn swindles_a_lost_position(){
+        //black has three on the bottom row open at both ends, and every rollout ends in black taking a win
+        let root = play(&[6, 1, 6, 2, 5, 3]);
+        let config = MctsConfig{ use_tactics : false, stop_at_win : true, swindle : Some(0.5f64), ..short_search() };
+        let mut engine = Mcts::new(root, config);
+        //blocking one end leaves black a single win instead of two, and the right end is nearer the center
+        assert_eq!(engine.search(), game_state::Move::white_new(4));
+        assert_eq!(swindle_move(&root, &[]), None);
+        //with something left to play for the final selection decides as usual
+        let mut engine = Mcts::new(play(&[0, 6, 1, 6, 2, 5]), config);
+        engine.think();
+        assert_eq!(engine.swindle(), None);
+    }
+
+    #[test]
+    fn ties_can_count_as_wins(){
+        let (white, black) = (game_state::Color::White, game_state::Color::Black);
+        assert_eq!(result_value(game_state::End::Tie, black, Some(black)), 1f64);
+        assert_eq!(result_value(game_state::End::Tie, white, Some(black)), 0.5f64);
+        assert_eq!(result_value(game_state::End::Victory(white), black, Some(black)), 0f64);
+        assert_eq!(RewardScale::MinusOneToOne.value_with(game_state::End::Tie, black, Some(black)), 1f64);
+
+        //black's last drop fills the 4 by 4 board without four in a row
+        let grid = ["ww.b", "bbww", "wwbb", "bbww"].iter().map(|row| row.chars().map(|cell| match cell{
+            'w' => Some(white),
+            'b' => Some(black),
+            _ => None
+        }).collect()).collect::<Vec<_>>();
+        let root = game_state::GameState::from_grid(&grid, black).unwrap();
+        let tie = root.place(&game_state::Move::black_new(2));
+        assert_eq!(tie.win(), game_state::End::Tie);
+        for &(ties_as_wins, value) in &[(false, 0.5f64), (true, 1f64)]{
+            let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, ties_as_wins, ..short_search() });
+            engine.think();
+            let data = engine.statistics()[&tie];
+            assert_eq!(data.wins / data.num_plays as f64, value);
+            //the root is scored for white, who gets no such bonus
+            let data = engine.statistics()[&root];
+            assert_eq!(data.wins / data.num_plays as f64, 0.5f64);
+        }
+    }
+
+    #[test]
+    fn mirror_keys_share_statistics(){
+        let config = MctsConfig{ use_tactics : false, mirror_keys : true, fold_symmetry : false, ..short_search() };
+        let root = play(&[3]);
+        let mut engine = Mcts::new(root, config);
+        engine.think();
+        assert!(engine.statistics().keys().all(|state| *state == state.canonical()));
+        //the two moves next to the center lead to mirror images, which are one node
+        let left = root.place(&game_state::Move::black_new(2));
+        assert!(engine.node(&left).is_some_and(|data| data.num_plays > 0));
+        assert_eq!(engine.node(&left).map(|data| data.num_plays), engine.node(&left.mirrored()).map(|data| data.num_plays));
+        assert!(root.legal(&engine.best_move()));
+        for &threads in &[1, 2]{
+            let config = MctsConfig{ threads, ..config };
+            assert_eq!(tree_search_with(play(&[0, 6, 1, 6, 2, 5]), &config).x, 3);
+            assert_eq!(tree_search_with(play(&[6, 0, 5, 0, 4, 1]), &config).x, 3);
+        }
+    }
+
+    #[test]
+    fn iteration_budgets_split_tree_and_rollouts(){
+        let root = play(&[3, 2]);
+        for &(rollouts_per_leaf, antithetic, aggregate_rollouts, visits) in
+            &[(1, false, false, 50), (4, false, false, 200), (4, false, true, 50), (3, true, false, 300), (0, false, false, 50)]{
+            let config = MctsConfig{ budget : Budget::Iterations(50), use_tactics : false, check_interval : 7,
+                rollouts_per_leaf, antithetic, aggregate_rollouts, ..short_search() };
+            let mut engine = Mcts::new(root, config);
+            let result = engine.search_result();
+            assert_eq!(result.iterations, 50);
+            assert_eq!(engine.statistics()[&root].num_plays, visits);
+        }
+        //shared searches hand out the iterations between their threads
+        for &parallelism in &[Parallelism::Tree, Parallelism::Root]{
+            let config = MctsConfig{ budget : Budget::Iterations(50), use_tactics : false, threads : 3, parallelism, ..short_search() };
+            let mut engine = Mcts::new(root, config);
+            assert_eq!(engine.think().0, 50);
+            assert_eq!(engine.statistics()[&root].num_plays, 50);
+        }
+        let handle = spawn_search(root, MctsConfig{ budget : Budget::Iterations(70), use_tactics : false, ..short_search() });
+        assert_eq!(handle.wait().iterations, 70);
+    }
+
+    #[test]
+    fn rollouts_stop_at_the_move_cap(){
+        let capped = RolloutOptions{ max_moves : Some(3), ..RolloutOptions::default() };
+        //three moves from the empty board can not end the game
+        let mut rng = seeded_rng(Some(190));
+        assert_eq!(rollout_with_draws(game_state::GameState::new(), capped, &mut || rng.gen::<f64>()), game_state::End::Tie);
+        assert_eq!(run_simulation_with(game_state::GameState::new(), game_state::Color::White, capped, &mut rng), game_state::End::Tie);
+        //a finished game is scored as it is
+        let won = play(&[0, 6, 1, 6, 2, 5, 3]);
+        assert_eq!(rollout_with_draws(won, RolloutOptions{ max_moves : Some(0), ..capped }, &mut || 0f64), won.win());
+        //the default caps
+        assert_eq!(RolloutOptions::default().move_cap(&game_state::GameState::new()), 42);
+        assert_eq!(RolloutOptions::default().move_cap(&game_state::GameState::with_variant(game_state::Variant::PopOut)), 168);
+        //an uncapped standard rollout always ends on its own before the cap
+        for _ in 0..20{
+            assert!(rollout_with_draws(game_state::GameState::new(), RolloutOptions::default(), &mut || rng.gen::<f64>()).is_over());
+        }
+    }
+
+    #[test]
+    fn lambda_blends_the_evaluation_into_rewards(){
+        let leaf = play(&[3, 3, 2]);
+        assert_eq!(Blend::at(&leaf, &MctsConfig::default()), None);
+        let blend = Blend::at(&leaf, &MctsConfig{ lambda : 0.25f64, ..MctsConfig::default() }).unwrap();
+        assert_eq!(blend.white, evaluation::evaluate(&leaf, game_state::Color::White));
+
+        let blend = Blend{ lambda : 0.25f64, white : 0.2f64, black : 0.8f64 };
+        let white = game_state::Color::White;
+        let won = [game_state::End::Victory(white)];
+        let reward = |scale, mover| rollout_rewards(&won, mover, Backup{ aggregate : true, scale, blend : Some(blend), ..Backup::default() })[0];
+        assert!((reward(RewardScale::ZeroToOne, white).0 - 0.8f64).abs() < 1e-9);
+        assert!((reward(RewardScale::MinusOneToOne, white).0 - 0.6f64).abs() < 1e-9);
+        //the loss still counts as a loss in the breakdown
+        assert_eq!(reward(RewardScale::ZeroToOne, game_state::Color::Black), (0.2f64, 0, 0));
+
+        let config = MctsConfig{ lambda : 0.5f64, use_tactics : false, ..short_search() };
+        assert_eq!(Mcts::new(play(&[0, 6, 1, 6, 2, 5]), config).search().x, 3);
+    }
+
+    #[test]
+    fn rollouts_without_moves_are_ties(){
+        assert_eq!(no_moves_result(&game_state::GameState::new()), game_state::End::Tie);
+        let won = play(&[0, 6, 1, 6, 2, 6, 3]);
+        assert_eq!(no_moves_result(&won), game_state::End::Victory(game_state::Color::White));
+        //a single cell fills up without a winner
+        let cell = game_state::GameState::with_size(1, 1).unwrap();
+        assert_eq!(run_simulation_with(cell, cell.player, RolloutOptions::default(), &mut seeded_rng(Some(1))), game_state::End::Tie);
+        assert_eq!(rollout_with_draws(cell, RolloutOptions{ safe : true, ..RolloutOptions::default() }, &mut || 0.5f64), game_state::End::Tie);
+    }
+
+    #[test]
+    fn rollouts_play_the_board_they_are_on(){
+        //one row with two gaps and black to move, whichever gap black fills white wins in the other.
+        //the moves have to come from the board the rollout has reached, not the one it started from
+        let white = Some(game_state::Color::White);
+        let row = vec![None, white, white, white, None, white, white, white];
+        let state = game_state::GameState::from_grid(&[row], game_state::Color::Black).unwrap();
+        let mut rng = seeded_rng(Some(252));
+        for _ in 0..20{
+            assert_eq!(run_simulation_with(state, state.player, RolloutOptions::default(), &mut rng), game_state::End::Victory(game_state::Color::White));
+        }
+    }
+
+    #[test]
+    fn heavy_rollouts_take_tactics_then_weighted_columns(){
+        let mut weights = [0f64; game_state::MAX_WIDTH];
+        weights[1] = 1f64;
+        weights[5] = 3f64;
+        let heavy = RolloutPolicy::Heavy(weights);
+        let quiet = play(&[3]);
+        let moves = quiet.legal_moves();
+        assert_eq!(heavy.choose(&quiet, &moves, 0.1f64).x, 1);
+        assert_eq!(heavy.choose(&quiet, &moves, 0.3f64).x, 5);
+        assert_eq!(heavy.choose(&quiet, &moves, 1f64).x, 5);
+        //no weight on any move leaves the choice to u
+        assert_eq!(RolloutPolicy::Heavy([0f64; game_state::MAX_WIDTH]).choose(&quiet, &moves, 0f64).x, 0);
+        //a win, and then a block, come before the weights
+        let white = play(&[0, 6, 1, 6, 2, 5]);
+        assert_eq!(heavy.choose(&white, &white.legal_moves(), 0.5f64).x, 3);
+        let black = play(&[0, 6, 1, 6, 2]);
+        assert_eq!(heavy.choose(&black, &black.legal_moves(), 0.5f64).x, 3);
+        //the search plays them for both sides
+        let config = MctsConfig{ rollout_policy : Some(heavy), ..short_search() };
+        let options = RolloutOptions::for_search(&config, &quiet);
+        assert_eq!((options.policy(&quiet), options.policy(&quiet.flip_player())), (Some(heavy), Some(heavy)));
+    }
+
+    #[test]
+    fn opponent_policy_plays_the_other_side(){
+        let white = play(&[0, 6, 1, 6, 2, 5]);
+        let black = play(&[0, 6, 1, 6, 2]);
+        for &u in [0f64, 0.5f64, 1f64].iter(){
+            assert_eq!(RolloutPolicy::Tactical.choose(&white, &white.legal_moves(), u).x, 3);
+            assert_eq!(RolloutPolicy::Tactical.choose(&black, &black.legal_moves(), u), game_state::Move::new(3, game_state::Color::Black));
+        }
+        let empty = game_state::GameState::new();
+        assert_eq!(RolloutPolicy::CenterFirst.choose(&empty, &empty.legal_moves(), 0f64).x, 3);
+        assert_eq!(RolloutPolicy::Random.choose(&empty, &empty.legal_moves(), 0f64).x, 0);
+
+        let config = MctsConfig{ opponent_policy : Some(RolloutPolicy::CenterFirst), ..MctsConfig::default() };
+        let options = RolloutOptions::for_search(&config, &empty);
+        assert_eq!(options.opponent, Some((game_state::Color::Black, RolloutPolicy::CenterFirst)));
+        assert_eq!(RolloutOptions::for_search(&MctsConfig::default(), &empty).opponent, None);
+        //white always takes the leftmost column while black, playing the center, is a move behind
+        assert_eq!(rollout_with_draws(empty, options, &mut || 0f64), game_state::End::Victory(game_state::Color::White));
+        let best_move = Mcts::new(play(&[3, 3]), MctsConfig{ opponent_policy : Some(RolloutPolicy::Tactical), ..short_search() }).search();
+        assert!(play(&[3, 3]).legal(&best_move));
+    }
+
+    #[test]
+    fn proves_decided_roots(){
+        let config = MctsConfig{ use_tactics : false, prove : true, ..short_search() };
+        //white wins on the spot, which needs no visits to prove
+        let mut engine = Mcts::new(play(&[0, 6, 1, 6, 2, 5]), config);
+        assert_eq!(engine.proven_value(), Some(game_state::End::Victory(game_state::Color::White)));
+        let result = engine.search_result();
+        assert!(result.proven);
+        assert_eq!(result.best_move.x, 3);
+
+        //black can not stop both ends of white's three
+        let mut engine = Mcts::new(play(&[1, 6, 2, 6, 3]), config);
+        let result = engine.search_result();
+        assert!(result.proven);
+        assert_eq!(engine.proven_value(), Some(game_state::End::Victory(game_state::Color::White)));
+        assert!(engine.root().legal(&result.best_move));
+
+        let mut open = Mcts::new(game_state::GameState::new(), config);
+        let result = open.search_result();
+        assert!(!result.proven);
+        assert_eq!(open.proven_value(), None);
+    }
+
+    #[test]
+    fn solver_proves_results_during_the_search(){
+        let config = MctsConfig{ use_tactics : false, solver : true, budget : Budget::Iterations(200_000), ..short_search() };
+        //white wins on the spot, the search stops as soon as the winning child is proven
+        let mut engine = Mcts::new(play(&[0, 6, 1, 6, 2, 5]), config);
+        let result = engine.search_result();
+        assert!(result.proven && result.iterations < 200_000);
+        assert_eq!(result.best_move.x, 3);
+        assert_eq!(engine.node(&engine.root().place(&result.best_move)).unwrap().proven, Some(game_state::End::Victory(game_state::Color::White)));
+
+        //black can not stop both ends of white's three, every black move is proven lost
+        let mut engine = Mcts::new(play(&[1, 6, 2, 6, 3]), config);
+        let result = engine.search_result();
+        assert!(result.proven && result.iterations < 200_000);
+        assert_eq!(engine.proven_value(), Some(game_state::End::Victory(game_state::Color::White)));
+        assert!(engine.root().legal(&result.best_move));
+
+        //the parallel searches do not prove anything, so asking for threads still searches on one
+        let mut threaded = Mcts::new(play(&[1, 6, 2, 6, 3]), MctsConfig{ threads : 4, ..config });
+        let result = threaded.search_result();
+        assert!(result.proven);
+        assert_eq!(threaded.proven_value(), Some(game_state::End::Victory(game_state::Color::White)));
+
+        let mut open = Mcts::new(game_state::GameState::new(), MctsConfig{ budget : Budget::Iterations(2000), ..config });
+        let result = open.search_result();
+        assert!(!result.proven);
+        assert_eq!(result.iterations, 2000);
+    }
+
+    #[test]
+    fn best_two_ranks_by_visits(){
+        let root = play(&[3, 3]);
+        let mut statistics = HashMap::new();
+        assert!(best_two(&root, &statistics).is_none());
+        let child = |x| root.place(&game_state::Move::white_new(x));
+        statistics.insert(child(0), UCTData::new(5f64, 9));
+        assert_eq!(best_two(&root, &statistics).map(|(mv, data, second)| (mv.x, data.num_plays, second.is_none())), Some((0, 9, true)));
+        statistics.insert(child(2), UCTData::new(1f64, 12));
+        statistics.insert(child(4), UCTData::new(8f64, 12));
+        statistics.insert(child(5), UCTData::new(0f64, 0));
+        //equal visits go to the move tie_break prefers, 2 and 4 are as far from the center and 2 is lower
+        let (best, data, second) = best_two(&root, &statistics).unwrap();
+        assert_eq!((best.x, data.wins), (2, 1f64));
+        assert_eq!(second.map(|(mv, data)| (mv.x, data.wins)), Some((4, 8f64)));
+
+        let mut engine = Mcts::new(root, MctsConfig{ use_tactics : false, ..short_search() });
+        assert!(engine.best_two().is_none());
+        engine.think();
+        let (best, data, second) = engine.best_two().unwrap();
+        let (_, second_data) = second.unwrap();
+        assert!(data.num_plays >= second_data.num_plays);
+        assert_eq!(best, engine.principal_variation(1)[0]);
+    }
+
+    #[test]
+    fn validation_catches_illegal_moves(){
+        let state = play(&[0, 0, 0, 0, 0, 0]);
+        check_legal(true, &state, &game_state::Move::new(1, state.player), "test");
+        //a full column, and a move for the player who just moved
+        for mv in &[game_state::Move::new(0, state.player), game_state::Move::new(1, state.player.opponent())]{
+            check_legal(false, &state, mv, "test");
+            let caught = std::panic::catch_unwind(|| check_legal(true, &state, mv, "test")).is_err();
+            assert_eq!(caught, cfg!(debug_assertions));
+        }
+    }
+
+    //tic-tac-toe, a game the search only knows through Game
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    struct TicTacToe{
+        cells : [Option<game_state::Color>; 9],
+        player : game_state::Color
+    }
+
+    impl Game for TicTacToe{
+        type Move = usize;
+
+        fn current_player(&self) -> game_state::Color{
+            self.player
+        }
+
+        fn legal_moves(&self) -> Vec<usize>{
+            if self.win().is_over(){
+                return Vec::new();
+            }
+            (0..9).filter(|&cell| self.cells[cell].is_none()).collect()
+        }
+
+        fn place(&self, mv : &usize) -> Self{
+            let mut next = *self;
+            next.cells[*mv] = Some(self.player);
+            next.player = self.player.opponent();
+            next
+        }
+
+        fn win(&self) -> game_state::End{
+            let lines = [[0, 1, 2], [3, 4, 5], [6, 7, 8], [0, 3, 6], [1, 4, 7], [2, 5, 8], [0, 4, 8], [2, 4, 6]];
+            for line in lines.iter(){
+                if let Some(color) = self.cells[line[0]].filter(|&color| line.iter().all(|&cell| self.cells[cell] == Some(color))){
+                    return game_state::End::Victory(color);
+                }
+            }
+            if self.cells.iter().all(Option::is_some) { game_state::End::Tie } else { game_state::End::Ongoing }
+        }
+
+        fn move_cap(&self) -> usize{
+            9
+        }
+    }
+
+    #[test]
+    fn the_search_plays_other_games(){
+        let tic_tac_toe = |white : &[usize], black : &[usize], player|{
+            let mut cells = [None; 9];
+            for (color, taken) in [(game_state::Color::White, white), (game_state::Color::Black, black)].iter(){
+                for &cell in taken.iter(){
+                    cells[cell] = Some(*color);
+                }
+            }
+            TicTacToe{ cells, player }
+        };
+        let config = MctsConfig{ selection_seed : Some(3), rollout_seed : Some(4), ..short_search() };
+        //white completes the top row, black has to block it
+        for &(root, expected) in &[(tic_tac_toe(&[0, 1], &[3, 4], game_state::Color::White), 2), (tic_tac_toe(&[0, 1], &[4], game_state::Color::Black), 2)]{
+            let (mut visited_states, mut statistics, mut moves) = (HashSet::new(), HashMap::new(), MoveCache::new());
+            let mut rng = SearchRng::new(&config);
+            expand(root, &mut visited_states, &mut statistics, &mut moves);
+            for _ in 0..2000{
+                iterate(root, &mut visited_states, &mut statistics, &mut moves, &config, &mut rng, IterationRecords::default());
+            }
+            let best = root.legal_moves().into_iter().max_by_key(|mv| statistics[&root.place(mv)].num_plays);
+            assert_eq!(best, Some(expected));
+            assert_eq!(statistics[&root].num_plays, 2000);
+        }
+    }
 }
@@ -0,0 +1,415 @@
+use game_state;
+use monte_carlo;
+use monte_carlo::{NodeStore, UCTData};
+use time;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::cmp;
+use std::thread;
+
+pub struct AtomicUCTData{
+    //UCTData that many threads can update at once without a lock
+    //f64 fields are stored as their bit patterns and updated with compare and swap
+    wins : AtomicU64,
+    num_plays : AtomicU64,
+    win_tie : AtomicU64,
+    ties : AtomicU64,
+    sum_squares : AtomicU64,
+    virtual_loss : AtomicU64
+}
+
+fn add_f64(cell : &AtomicU64, value : f64){
+    let mut current = cell.load(Ordering::Relaxed);
+    loop{
+        let next = (f64::from_bits(current) + value).to_bits();
+        match cell.compare_exchange_weak(current, next, Ordering::Relaxed, Ordering::Relaxed){
+            Ok(_) => return,
+            Err(actual) => current = actual
+        }
+    }
+}
+
+impl AtomicUCTData{
+    pub fn new(data : &UCTData) -> AtomicUCTData{
+        AtomicUCTData{
+            wins : AtomicU64::new(data.wins.to_bits()),
+            num_plays : AtomicU64::new(data.num_plays as u64),
+            win_tie : AtomicU64::new(data.win_tie as u64),
+            ties : AtomicU64::new(data.ties as u64),
+            sum_squares : AtomicU64::new(data.sum_squares.to_bits()),
+            virtual_loss : AtomicU64::new(data.virtual_loss.to_bits())
+        }
+    }
+
+    pub fn load(&self) -> UCTData{
+        let mut data = UCTData::new(f64::from_bits(self.wins.load(Ordering::Relaxed)), self.num_plays.load(Ordering::Relaxed) as i32);
+        data.win_tie = self.win_tie.load(Ordering::Relaxed) as i32;
+        data.ties = self.ties.load(Ordering::Relaxed) as i32;
+        data.sum_squares = f64::from_bits(self.sum_squares.load(Ordering::Relaxed));
+        data.virtual_loss = f64::from_bits(self.virtual_loss.load(Ordering::Relaxed));
+        data
+    }
+
+    pub fn add_virtual_loss(&self, virtual_loss : f64){
+        add_f64(&self.virtual_loss, virtual_loss);
+    }
+
+    pub fn record(&self, result : game_state::End, player : game_state::Color){
+        //the atomic version of one back_propogate step
+        self.record_visit(monte_carlo::get_result_value(result, player), monte_carlo::get_tie_or_win(result, player), monte_carlo::get_tie(result),
+            monte_carlo::Backup::default());
+    }
+
+    pub fn record_visit(&self, reward : f64, win_tie : i32, ties : i32, backup : monte_carlo::Backup){
+        //a weighted backup reads the node first, so a visit from another thread in between can be weighted as if it came later
+        let added = if backup.alpha.is_some(){
+            let data = self.load();
+            backup.wins_added(data.wins, data.num_plays, reward)
+        }
+        else{
+            reward
+        };
+        add_f64(&self.wins, added);
+        add_f64(&self.sum_squares, reward * reward);
+        self.num_plays.fetch_add(1, Ordering::Relaxed);
+        self.win_tie.fetch_add(win_tie as u64, Ordering::Relaxed);
+        self.ties.fetch_add(ties as u64, Ordering::Relaxed);
+    }
+}
+
+pub type AtomicStatistics = HashMap<game_state::GameState, Arc<AtomicUCTData>>;
+
+impl NodeStore for AtomicStatistics{
+    fn visited(&self, state : &game_state::GameState) -> bool{
+        self.contains_key(state)
+    }
+
+    fn data(&self, state : &game_state::GameState) -> Option<UCTData>{
+        self.get(state).map(|data| data.load())
+    }
+}
+
+fn iterate_atomic(root : game_state::GameState, tree : &RwLock<AtomicStatistics>, config : &monte_carlo::MctsConfig, rng : &mut monte_carlo::SearchRng){
+    //selection only needs a read lock, expansion briefly takes the write lock,
+    //and everything after that goes through the atomics of the nodes on the path
+    let virtual_loss = config.virtual_loss;
+    let selected_state = monte_carlo::select_with(&root, &*tree.read().unwrap(), config, &mut rng.selection);
+
+    let expanded = monte_carlo::node_key(&selected_state.expanded_node, config);
+    if !tree.read().unwrap().contains_key(&expanded){
+        let mut nodes = tree.write().unwrap();
+        nodes.entry(expanded)
+            .or_insert_with(|| Arc::new(AtomicUCTData::new(&UCTData::new(0f64, 0))));
+        if config.full_expansion{
+            for child in monte_carlo::child_states(&selected_state.expanded_node){
+                nodes.entry(monte_carlo::node_key(&child, config)).or_insert_with(|| Arc::new(AtomicUCTData::new(&UCTData::new(0f64, 0))));
+            }
+        }
+    }
+
+    let path = {
+        let nodes = tree.read().unwrap();
+        selected_state.path.iter().filter_map(|&(node, mover)| nodes.get(&monte_carlo::node_key(&node, config)).map(|data| (mover, data.clone()))).collect::<Vec<_>>()
+    };
+    for (_, data) in path.iter(){
+        data.add_virtual_loss(virtual_loss);
+    }
+
+    let results = monte_carlo::leaf_results(&root, selected_state.expanded_node, config, &mut rng.rollout);
+    let backup = monte_carlo::Backup::from_config(config, &root, &selected_state.expanded_node);
+
+    for &(mover, ref data) in path.iter(){
+        data.add_virtual_loss(-virtual_loss);
+        for (reward, win_tie, ties) in monte_carlo::rollout_rewards(&results, mover, backup){
+            data.record_visit(reward, win_tie, ties, backup);
+        }
+    }
+}
+
+pub fn to_atomic(statistics : &HashMap<game_state::GameState, UCTData>) -> AtomicStatistics{
+    statistics.iter().map(|(state, data)| (*state, Arc::new(AtomicUCTData::new(data)))).collect()
+}
+
+pub fn from_atomic(statistics : &AtomicStatistics) -> monte_carlo::Tree{
+    let visited_states = statistics.keys().cloned().collect();
+    let stats = statistics.iter().map(|(state, data)| (*state, data.load())).collect();
+    (visited_states, stats)
+}
+
+pub fn thread_seed(seed : u64, index : usize) -> u64{
+    //the seed of thread index in a search seeded with seed, well mixed (splitmix64) so that neighbouring
+    //threads and seeds draw unrelated streams instead of all repeating the same one
+    let mut z = seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn thread_config(config : &monte_carlo::MctsConfig, index : usize) -> monte_carlo::MctsConfig{
+    //what thread index of a shared search runs with, its seeds derived from the configured ones
+    monte_carlo::MctsConfig{
+        threads : 1,
+        selection_seed : config.selection_seed.map(|seed| thread_seed(seed, index)),
+        rollout_seed : config.rollout_seed.map(|seed| thread_seed(seed, index)),
+        ..*config
+    }
+}
+
+fn claim(claimed : &AtomicU64, chunk : u64, limit : Option<u64>) -> u64{
+    //how many of its next chunk of iterations a thread may run, once config.iteration_limit is shared out none are left
+    match limit{
+        Some(limit) => cmp::min(chunk, limit.saturating_sub(claimed.fetch_add(chunk, Ordering::Relaxed))),
+        None => chunk
+    }
+}
+
+pub fn tree_parallel(root : game_state::GameState, tree : monte_carlo::Tree, config : &monte_carlo::MctsConfig,
+    think_time : f64, stop : &AtomicBool) -> (monte_carlo::Tree, u64){
+    //config.thread_count() threads search one shared tree for think_time seconds, or until stop is set
+    //returns the grown tree and how many iterations were run in total. the threads' seeds come from config's,
+    //but how their iterations interleave does not, so unlike root_parallel this can not be replayed
+    let mut statistics = to_atomic(&tree.1);
+    statistics.entry(monte_carlo::node_key(&root, config)).or_insert_with(|| Arc::new(AtomicUCTData::new(&UCTData::new(0f64, 0))));
+    let shared = RwLock::new(statistics);
+    let claimed = AtomicU64::new(0);
+    let current_time = time::precise_time_s();
+    let iterations = thread::scope(|scope|{
+        let (shared, claimed) = (&shared, &claimed);
+        let workers = (0..config.thread_count()).map(|index| scope.spawn(move ||{
+            let mut rng = monte_carlo::SearchRng::new(&thread_config(config, index));
+            let mut iterations = 0u64;
+            while !stop.load(Ordering::Relaxed) && time::precise_time_s() - current_time < think_time{
+                let chunk = claim(claimed, config.check_every(), config.iteration_limit());
+                if chunk == 0{
+                    break;
+                }
+                for _ in 0..chunk{
+                    iterate_atomic(root, shared, config, &mut rng);
+                }
+                iterations += chunk;
+            }
+            iterations
+        })).collect::<Vec<_>>();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+    });
+    let shared = shared.into_inner().unwrap();
+    (from_atomic(&shared), iterations)
+}
+
+fn merge_into(target : &mut HashMap<game_state::GameState, UCTData>, source : &HashMap<game_state::GameState, UCTData>){
+    //the same position found by different threads is the same node, so statistics are summed by key
+    for (state, data) in source.iter(){
+        let merged = target.entry(*state).or_insert_with(|| UCTData::new(0f64, 0));
+        merged.wins += data.wins;
+        merged.num_plays += data.num_plays;
+        merged.win_tie += data.win_tie;
+        merged.ties += data.ties;
+        merged.sum_squares += data.sum_squares;
+    }
+}
+
+pub fn merge_statistics(maps : &[HashMap<game_state::GameState, UCTData>]) -> HashMap<game_state::GameState, UCTData>{
+    //combines trees searched separately, e.g. on different machines, into one
+    //virtual loss only means something while a search is running, so it is not carried over
+    let mut merged = HashMap::new();
+    for map in maps.iter(){
+        merge_into(&mut merged, map);
+    }
+    merged
+}
+
+pub fn root_parallel(root : game_state::GameState, tree : monte_carlo::Tree, config : &monte_carlo::MctsConfig,
+    think_time : f64, stop : &AtomicBool) -> (monte_carlo::Tree, u64){
+    //every thread searches its own fresh tree for the whole think_time, not a share of it, or until stop is set,
+    //and whatever they found is added on top of the tree we started with.
+    //an iteration limit is split into fixed shares, so with seeds in config the same thread count
+    //searches the same trees every time
+    let threads = config.thread_count();
+    let current_time = time::precise_time_s();
+    let results = thread::scope(|scope|{
+        let workers = (0..threads).map(|index| scope.spawn(move ||{
+            let mut engine = monte_carlo::Mcts::new(root, thread_config(config, index));
+            let share = config.iteration_limit().map(|limit| limit / threads as u64 + ((index as u64) < limit % threads as u64) as u64);
+            let mut iterations = 0u64;
+            while !stop.load(Ordering::Relaxed) && time::precise_time_s() - current_time < think_time{
+                let chunk = share.map_or(config.check_every(), |share| cmp::min(config.check_every(), share - iterations));
+                if chunk == 0{
+                    break;
+                }
+                for _ in 0..chunk{
+                    engine.step();
+                }
+                iterations += chunk;
+            }
+            (engine.statistics().clone(), iterations)
+        })).collect::<Vec<_>>();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect::<Vec<_>>()
+    });
+
+    let (mut visited_states, mut statistics) = tree;
+    let mut iterations = 0;
+    for (thread_statistics, thread_iterations) in results.iter(){
+        merge_into(&mut statistics, thread_statistics);
+        iterations += thread_iterations;
+    }
+    visited_states.extend(statistics.keys().cloned());
+    ((visited_states, statistics), iterations)
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn concurrent_records_are_not_lost(){
+        let data = AtomicUCTData::new(&UCTData::new(0f64, 0));
+        thread::scope(|scope|{
+            for _ in 0..4{
+                scope.spawn(||{
+                    for _ in 0..1000{
+                        data.add_virtual_loss(1f64);
+                        data.record(game_state::End::Tie, game_state::Color::White);
+                        data.add_virtual_loss(-1f64);
+                    }
+                });
+            }
+        });
+        let data = data.load();
+        assert_eq!(data.num_plays, 4000);
+        assert_eq!(data.win_tie, 4000);
+        assert_eq!(data.wins, 2000f64);
+        assert_eq!(data.sum_squares, 1000f64);
+        assert_eq!(data.virtual_loss, 0f64);
+    }
+
+    #[test]
+    fn virtual_loss_makes_a_child_less_attractive(){
+        let root = game_state::GameState::new();
+        let mut statistics = HashMap::new();
+        statistics.insert(root, UCTData::new(0f64, 14));
+        for mv in root.legal_moves(){
+            statistics.insert(root.place(&mv), UCTData::new(1f64, 2));
+        }
+        let statistics = to_atomic(&statistics);
+        let first = monte_carlo::select(&root, &statistics, &monte_carlo::MctsConfig::default()).path[1].0;
+        statistics[&root].add_virtual_loss(1f64);
+        statistics[&first].add_virtual_loss(1f64);
+        assert_ne!(monte_carlo::select(&root, &statistics, &monte_carlo::MctsConfig::default()).path[1].0, first);
+        statistics[&root].add_virtual_loss(-1f64);
+        statistics[&first].add_virtual_loss(-1f64);
+        assert_eq!(monte_carlo::select(&root, &statistics, &monte_carlo::MctsConfig::default()).path[1].0, first);
+    }
+
+    #[test]
+    fn root_parallel_sums_the_threads(){
+        let root = game_state::GameState::new();
+        let config = monte_carlo::MctsConfig{
+            threads : 3,
+            ..monte_carlo::MctsConfig::default()
+        };
+        let ((visited_states, statistics), iterations) = root_parallel(root, Default::default(), &config, 0.05f64, &AtomicBool::new(false));
+        assert_eq!(statistics[&root].num_plays as u64, iterations);
+        assert_eq!(visited_states.len(), statistics.len());
+        let children = monte_carlo::root_moves(&root, &config).iter().map(|mv| statistics[&root.place(mv)].num_plays).sum::<i32>();
+        assert_eq!(children, statistics[&root].num_plays);
+    }
+
+    #[test]
+    fn seeded_root_parallel_searches_repeat(){
+        let root = game_state::GameState::new();
+        let config = monte_carlo::MctsConfig{
+            threads : 3,
+            parallelism : monte_carlo::Parallelism::Root,
+            budget : monte_carlo::Budget::Iterations(200),
+            selection_seed : Some(11),
+            rollout_seed : Some(12),
+            ..monte_carlo::MctsConfig::default()
+        };
+        let search = ||{
+            let ((_, statistics), iterations) = root_parallel(root, Default::default(), &config, f64::INFINITY, &AtomicBool::new(false));
+            (statistics.into_iter().map(|(state, data)| (state, (data.wins, data.num_plays))).collect::<HashMap<_, _>>(), iterations)
+        };
+        let (first, iterations) = search();
+        let (second, _) = search();
+        assert_eq!(iterations, 200);
+        assert_eq!(first, second);
+        //the threads do not just repeat one another
+        assert_ne!(thread_seed(11, 0), thread_seed(11, 1));
+        assert_ne!(thread_seed(11, 0), thread_seed(12, 0));
+        assert_eq!(thread_config(&config, 2).selection_seed, Some(thread_seed(11, 2)));
+        assert_eq!(thread_config(&monte_carlo::MctsConfig::default(), 2).selection_seed, None);
+    }
+
+    #[test]
+    fn merge_sums_overlapping_states(){
+        let root = game_state::GameState::new();
+        let child = root.place(&game_state::Move::white_new(3));
+        let mut first = HashMap::new();
+        first.insert(root, UCTData::new(2f64, 4));
+        first.insert(child, UCTData::new(1.5f64, 2));
+        let mut second = HashMap::new();
+        let mut data = UCTData::new(3f64, 5);
+        data.win_tie = 4;
+        data.virtual_loss = 2f64;
+        second.insert(root, data);
+        let merged = merge_statistics(&[first, second]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[&root].wins, 5f64);
+        assert_eq!(merged[&root].num_plays, 9);
+        assert_eq!(merged[&root].win_tie, 4);
+        assert_eq!(merged[&root].virtual_loss, 0f64);
+        assert_eq!(merged[&child].wins, 1.5f64);
+        assert_eq!(merged[&child].num_plays, 2);
+    }
+
+    #[test]
+    fn merge_keeps_disjoint_states(){
+        let root = game_state::GameState::new();
+        let left = root.place(&game_state::Move::white_new(0));
+        let right = root.place(&game_state::Move::white_new(6));
+        let first = [(left, UCTData::new(1f64, 1))].iter().cloned().collect::<HashMap<_, _>>();
+        let second = [(right, UCTData::new(0f64, 3))].iter().cloned().collect::<HashMap<_, _>>();
+        let merged = merge_statistics(&[first, second]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!((merged[&left].wins, merged[&left].num_plays), (1f64, 1));
+        assert_eq!((merged[&right].wins, merged[&right].num_plays), (0f64, 3));
+        assert!(merge_statistics(&[]).is_empty());
+    }
+
+    #[test]
+    fn every_thread_shares_the_work(){
+        //an iteration budget shared out between the threads is run exactly, whichever way they share the tree
+        let root = game_state::GameState::new();
+        let config = monte_carlo::MctsConfig{ threads : 4, budget : monte_carlo::Budget::Iterations(400), check_interval : 10,
+            ..monte_carlo::MctsConfig::default() };
+        for &search in &[tree_parallel, root_parallel]{
+            let ((_, statistics), iterations) = search(root, Default::default(), &config, f64::INFINITY, &AtomicBool::new(false));
+            assert_eq!(iterations, 400);
+            assert_eq!(statistics[&root].num_plays, 400);
+        }
+        //and on a machine with cores to spare, two independent trees get through more than one in the same time
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        if cores >= 2{
+            let timed = |threads| root_parallel(root, Default::default(), &monte_carlo::MctsConfig{ threads, ..monte_carlo::MctsConfig::default() }, 0.3f64, &AtomicBool::new(false)).1;
+            let (single, double) = (timed(1), timed(2));
+            assert!(double as f64 > 1.2f64 * single as f64, "{} iterations on two threads, {} on one", double, single);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn scaling_across_threads(){
+        //cargo test --release -- --ignored --nocapture scaling
+        let root = game_state::GameState::new();
+        let single = tree_parallel(root, Default::default(), &monte_carlo::MctsConfig{ threads : 1, ..monte_carlo::MctsConfig::default() }, 2f64, &AtomicBool::new(false)).1;
+        for &threads in &[1, 2, 4, 8]{
+            let config = monte_carlo::MctsConfig{
+                threads,
+                ..monte_carlo::MctsConfig::default()
+            };
+            let iterations = tree_parallel(root, Default::default(), &config, 2f64, &AtomicBool::new(false)).1;
+            println!("{} threads: {} iterations/s, {:.2}x", threads, iterations / 2, iterations as f64 / single as f64);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use game_state;
+use monte_carlo;
+use std::io;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+//a line protocol for driving the engine from a gui or a script, one command per line on stdin:
+//  position 4453     the moves played so far, one column per move counting from 1 on the left,
+//                    with white moving first. a bare "position" is the empty board
+//  go time 2000      search the current position for that many milliseconds, go movetime 2000 is the same
+//  go iters 50000    search the current position for that many iterations
+//  stop              end the running search early
+//  quit              stop searching and exit, while at the end of the input a running search is finished first
+//every search finishes by printing its statistics and then the move, again counting columns from 1:
+//  info iterations 50000 visits 31250 winrate 0.612 pv 4 4 3
+//  bestmove 4
+//visits and winrate are the best move's, winrate is left out while it has too few visits to say and
+//pv is the line the search expects after the best move, starting with it. nothing else is ever printed,
+//errors are a line starting with "error"
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Limit{
+    Millis(u64),
+    Iterations(u64)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command{
+    Position(game_state::GameState),
+    Go(Limit),
+    Stop,
+    Quit
+}
+
+pub fn parse_position(moves : &str) -> Result<game_state::GameState, String>{
+    game_state::GameRecord::from_notation(game_state::GameState::new(), moves).map(|record| record.position())
+}
+
+pub fn parse_command(line : &str) -> Result<Command, String>{
+    let words : Vec<&str> = line.split_whitespace().collect();
+    let number = |word : &str| word.parse::<u64>().map_err(|_| format!("{:?} is not a number", word));
+    match words.as_slice(){
+        ["position"] => Ok(Command::Position(game_state::GameState::new())),
+        ["position", moves] => parse_position(moves).map(Command::Position),
+        ["go", "time", millis] | ["go", "movetime", millis] => Ok(Command::Go(Limit::Millis(number(millis)?))),
+        ["go", "iters", iterations] => Ok(Command::Go(Limit::Iterations(number(iterations)?))),
+        ["stop"] => Ok(Command::Stop),
+        ["quit"] => Ok(Command::Quit),
+        _ => Err(format!("unknown command {:?}", line.trim()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report{
+    //what a search answers with, printed as an info line and a bestmove line
+    pub best_move : game_state::Move,
+    pub iterations : u64,
+    pub visits : i32,
+    pub win_rate : Option<f64>,
+    pub principal_variation : Vec<game_state::Move>
+}
+
+//the longest principal variation an info line shows
+const PV_LENGTH : usize = 8;
+
+impl Report{
+    pub fn lines(&self) -> [String; 2]{
+        let mut info = format!("info iterations {} visits {}", self.iterations, self.visits);
+        if let Some(win_rate) = self.win_rate{
+            info.push_str(&format!(" winrate {:.3}", win_rate));
+        }
+        info.push_str(" pv");
+        for mv in self.principal_variation.iter(){
+            info.push_str(&format!(" {}", mv.x + 1));
+        }
+        [info, format!("bestmove {}", self.best_move.x + 1)]
+    }
+}
+
+pub fn search(root : game_state::GameState, limit : Limit, stop : &AtomicBool) -> Report{
+    let config = monte_carlo::MctsConfig{ quiet : true, ..monte_carlo::MctsConfig::default() };
+    let mut engine = monte_carlo::Mcts::new(root, config);
+    let start = time::precise_time_s();
+    let mut iterations = 0u64;
+    loop{
+        let done = match limit{
+            Limit::Millis(millis) => (time::precise_time_s() - start) * 1000f64 >= millis as f64,
+            Limit::Iterations(limit) => iterations >= limit
+        };
+        //always search a little, so there is a move to give even when stopped straight away
+        if done || stop.load(Ordering::Relaxed){
+            if let Some((best_move, data)) = engine.current_best(){
+                return Report{
+                    best_move,
+                    iterations,
+                    visits : data.num_plays,
+                    win_rate : data.reported_win_rate(config.min_visits_for_winrate),
+                    principal_variation : engine.principal_variation_from(best_move, PV_LENGTH)
+                };
+            }
+        }
+        engine.step();
+        iterations += 1;
+    }
+}
+
+pub fn run(){
+    //speaks the protocol on stdin and stdout until quit or the end of the input
+    let stdin = io::stdin();
+    serve(stdin.lock(), Arc::new(Mutex::new(io::stdout())));
+}
+
+fn say<W : Write>(output : &Mutex<W>, line : &str){
+    //one whole line at a time, a search thread may be answering at the same moment
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{}", line).expect("could not write the output");
+    output.flush().expect("could not write the output");
+}
+
+pub fn serve<I : BufRead, W : Write + Send + 'static>(input : I, output : Arc<Mutex<W>>){
+    //run on any input and output. the output is shared with the thread of the running search
+    let mut position = game_state::GameState::new();
+    let mut running : Option<(Arc<AtomicBool>, thread::JoinHandle<()>)> = None;
+    for line in input.lines(){
+        let line = line.expect("could not read the input");
+        if line.trim().is_empty(){
+            continue;
+        }
+        let command = match parse_command(&line){
+            Ok(command) => command,
+            Err(error) => {
+                say(&output, &format!("error {}", error));
+                continue;
+            }
+        };
+        //a new command lets the previous search finish first, only stop and quit cut it short
+        if let Some((stop, handle)) = running.take(){
+            if command == Command::Stop || command == Command::Quit{
+                stop.store(true, Ordering::Relaxed);
+            }
+            handle.join().expect("search thread panicked");
+        }
+        match command{
+            Command::Position(state) => position = state,
+            Command::Go(limit) => {
+                if position.is_terminal(){
+                    say(&output, "error the game in this position is already over");
+                    continue;
+                }
+                let stop = Arc::new(AtomicBool::new(false));
+                let thread_stop = stop.clone();
+                let root = position;
+                let thread_output = output.clone();
+                let handle = thread::spawn(move ||{
+                    for line in search(root, limit, &thread_stop).lines().iter(){
+                        say(&thread_output, line);
+                    }
+                });
+                running = Some((stop, handle));
+            },
+            Command::Stop => {},
+            Command::Quit => break
+        }
+    }
+    //input that just ends, as a script piping in its commands does, still gets the whole search it asked for
+    if let Some((_, handle)) = running{
+        handle.join().expect("search thread panicked");
+    }
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    #[test]
+    fn parses_commands(){
+        let expected = [3, 3, 4, 2].iter().fold(game_state::GameState::new(), |state, &x| state.place(&game_state::Move::new(x, state.player)));
+        assert_eq!(parse_command("position 4453"), Ok(Command::Position(expected)));
+        assert_eq!(parse_command("position"), Ok(Command::Position(game_state::GameState::new())));
+        assert_eq!(parse_command("go time 2000"), Ok(Command::Go(Limit::Millis(2000))));
+        assert_eq!(parse_command("go movetime 3000"), Ok(Command::Go(Limit::Millis(3000))));
+        assert_eq!(parse_command("  go iters 50000 "), Ok(Command::Go(Limit::Iterations(50000))));
+        assert_eq!(parse_command("stop"), Ok(Command::Stop));
+        assert_eq!(parse_command("quit"), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn rejects_bad_commands(){
+        assert!(parse_command("go").is_err());
+        assert!(parse_command("go time soon").is_err());
+        assert!(parse_command("position 48").is_err());
+        assert!(parse_command("position 0").is_err());
+        //the seventh piece does not fit in a column
+        assert!(parse_command("position 1111111").is_err());
+        //nor may anyone move once white has four in a row
+        assert!(parse_command("position 1212121").is_ok());
+        assert!(parse_command("position 12121213").is_err());
+        assert!(parse_command("ponder").is_err());
+    }
+
+    #[test]
+    fn searches_for_the_win(){
+        let root = parse_position("172736").unwrap();
+        let report = search(root, Limit::Iterations(200), &AtomicBool::new(false));
+        assert_eq!(report.best_move.x, 3);
+        //a stopped search still answers
+        let report = search(game_state::GameState::new(), Limit::Millis(60000), &AtomicBool::new(true));
+        assert!(report.best_move.x < 7);
+    }
+
+    #[test]
+    fn finishes_the_search_when_the_input_ends(){
+        let output = Arc::new(Mutex::new(Vec::new()));
+        serve(io::Cursor::new("position 44\nbogus\ngo iters 3000\n"), output.clone());
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("error"));
+        assert!(lines[1].starts_with("info iterations 3000 "));
+        assert!(lines[2].starts_with("bestmove "));
+    }
+
+    #[test]
+    fn reports_the_search_statistics(){
+        let report = search(parse_position("44").unwrap(), Limit::Iterations(2000), &AtomicBool::new(false));
+        assert_eq!(report.iterations, 2000);
+        assert_eq!(report.principal_variation[0], report.best_move);
+        let [info, best] = report.lines();
+        assert_eq!(best, format!("bestmove {}", report.best_move.x + 1));
+        assert!(info.starts_with(&format!("info iterations 2000 visits {} winrate ", report.visits)));
+        assert!(info.ends_with(&format!(" pv {}", report.principal_variation.iter().map(|mv| (mv.x + 1).to_string()).collect::<Vec<_>>().join(" "))));
+
+        let quiet = Report{ best_move : game_state::Move::white_new(0), iterations : 1, visits : 1, win_rate : None, principal_variation : vec![] };
+        assert_eq!(quiet.lines(), ["info iterations 1 visits 1 pv".to_string(), "bestmove 1".to_string()]);
+    }
+}
@@ -0,0 +1,345 @@
+use game_state;
+use monte_carlo;
+use serde::{Serialize, Deserialize};
+use serde_json;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+//the engine playing itself, e.g. to generate training positions or to compare two settings by their results.
+//both sides share one engine, so each move's search starts from what the previous moves already learned
+
+//how many of their own moves in a row a player's best move has to look lost before they resign
+pub const RESIGN_PLIES : usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfPlayGame{
+    pub moves : Vec<game_state::Move>,
+    //a resigned game is a win for the side that did not resign
+    pub result : game_state::End,
+    pub resigned : bool
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct SelfPlaySummary{
+    pub games : usize,
+    pub white_wins : usize,
+    pub black_wins : usize,
+    pub ties : usize,
+    //how many of the wins above came from a resignation, to check the threshold is not resigning won games
+    pub resigned : usize
+}
+
+impl SelfPlaySummary{
+    pub fn record(&mut self, game : &SelfPlayGame){
+        self.games += 1;
+        match game.result{
+            game_state::End::Victory(game_state::Color::White) => self.white_wins += 1,
+            game_state::End::Victory(game_state::Color::Black) => self.black_wins += 1,
+            _ => self.ties += 1
+        }
+        if game.resigned{
+            self.resigned += 1;
+        }
+    }
+}
+
+//how well each exploration constant has done at each ply, so an engine that plays many games can warm-start
+//its searches with the constant that worked best so far. the quality of a search is whatever the caller
+//records, play_tuned_game records the game's result for the side that searched
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExplorationTable{
+    //ply -> the constants tried there, with their summed quality and how many searches that is over
+    plies : BTreeMap<usize, Vec<ExplorationTrial>>
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplorationTrial{
+    pub constant : f64,
+    pub quality : f64,
+    pub searches : u32
+}
+
+impl ExplorationTrial{
+    pub fn mean(&self) -> f64{
+        if self.searches == 0 { 0f64 } else { self.quality / self.searches as f64 }
+    }
+}
+
+impl ExplorationTable{
+    pub fn record(&mut self, ply : usize, constant : f64, quality : f64){
+        //one search at ply with constant, and how good it turned out to be
+        let trials = self.plies.entry(ply).or_default();
+        match trials.iter_mut().find(|trial| trial.constant == constant){
+            Some(trial) => {
+                trial.quality += quality;
+                trial.searches += 1;
+            },
+            None => trials.push(ExplorationTrial{ constant, quality, searches : 1 })
+        }
+    }
+
+    pub fn trials(&self, ply : usize) -> &[ExplorationTrial]{
+        self.plies.get(&ply).map_or(&[], |trials| trials.as_slice())
+    }
+
+    pub fn constant_at(&self, ply : usize) -> Option<f64>{
+        //the constant with the best mean quality at ply, the smaller one on a tie, or None if nothing was recorded there
+        self.trials(ply).iter()
+            .max_by(|a, b| a.mean().partial_cmp(&b.mean()).unwrap_or(Ordering::Equal).then(b.constant.partial_cmp(&a.constant).unwrap_or(Ordering::Equal)))
+            .map(|trial| trial.constant)
+    }
+
+    pub fn apply(&self, config : &monte_carlo::MctsConfig, ply : usize) -> monte_carlo::MctsConfig{
+        //config with the best constant known for ply, or unchanged where the table knows nothing
+        match self.constant_at(ply){
+            Some(constant) => monte_carlo::MctsConfig{ exploration : monte_carlo::Exploration::Constant(constant), ..*config },
+            None => *config
+        }
+    }
+
+    pub fn to_json(&self) -> String{
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json : &str) -> Result<Self, String>{
+        serde_json::from_str(json).map_err(|error| error.to_string())
+    }
+}
+
+pub fn play_game(root : game_state::GameState, config : &monte_carlo::MctsConfig) -> SelfPlayGame{
+    //plays root out with config for both sides, or until one of them resigns, see MctsConfig.resign_threshold
+    play_game_with(root, config, None)
+}
+
+pub fn play_tuned_game(root : game_state::GameState, config : &monte_carlo::MctsConfig, table : &mut ExplorationTable) -> SelfPlayGame{
+    //play_game with every search starting from the table's constant for its ply, falling back to config's.
+    //afterwards each of those constants is credited with the result for the side that used it
+    let game = play_game_with(root, config, Some(table));
+    let mut state = root;
+    let mut searches = Vec::new();
+    for mv in game.moves.iter(){
+        //what each search used, worked out before any of them is recorded
+        searches.push((state.ply(), table.apply(config, state.ply()).exploration.constant(0), state.player));
+        state = state.place(mv);
+    }
+    for (ply, constant, player) in searches{
+        table.record(ply, constant, monte_carlo::get_result_value(game.result, player));
+    }
+    game
+}
+
+fn play_game_with(root : game_state::GameState, config : &monte_carlo::MctsConfig, table : Option<&ExplorationTable>) -> SelfPlayGame{
+    let mut engine = monte_carlo::Mcts::new(root, *config);
+    let mut state = root;
+    let mut moves = Vec::new();
+    //consecutive lost looking moves of white and of black
+    let mut lost = [0usize; 2];
+    while !state.is_terminal() && !state.legal_moves().is_empty(){
+        if let Some(table) = table{
+            engine.config = table.apply(config, state.ply());
+        }
+        let best_move = engine.search();
+        let win_rate = engine.statistics().get(&state.place(&best_move))
+            .filter(|data| data.num_plays > 0)
+            .map(|data| data.win_percentage());
+        let side = (state.player == game_state::Color::Black) as usize;
+        //moves from the book or tactics were not searched, and say nothing about the position being lost
+        lost[side] = match (config.resign_threshold, win_rate){
+            (Some(threshold), Some(win_rate)) if win_rate < threshold => lost[side] + 1,
+            _ => 0
+        };
+        if lost[side] >= RESIGN_PLIES{
+            return SelfPlayGame{ moves, result : game_state::End::Victory(state.player.opponent()), resigned : true };
+        }
+        state = state.place(&best_move);
+        engine.advance(&best_move);
+        moves.push(best_move);
+    }
+    let result = if state.win().is_over() { state.win() } else { game_state::End::Tie };
+    SelfPlayGame{ moves, result, resigned : false }
+}
+
+pub fn self_play(root : game_state::GameState, config : &monte_carlo::MctsConfig, games : usize) -> (Vec<SelfPlayGame>, SelfPlaySummary){
+    //games games from root, each with a fresh engine, and their results added up
+    let mut summary = SelfPlaySummary::default();
+    let played = (0..games).map(|_| play_game(root, config)).collect::<Vec<_>>();
+    for game in played.iter(){
+        summary.record(game);
+    }
+    (played, summary)
+}
+
+//two configs playing each other, to tell whether a change to the settings makes the engine stronger
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ArenaSummary{
+    pub games : usize,
+    //the results of the first config
+    pub wins : usize,
+    pub losses : usize,
+    pub ties : usize,
+    //for the first config and then the second: the moves each made, and the seconds and iterations they took in total
+    pub moves : [usize; 2],
+    pub seconds : [f64; 2],
+    pub iterations : [u64; 2]
+}
+
+impl ArenaSummary{
+    pub fn score(&self) -> f64{
+        //the first config's average result, 1 for a win and 0.5 for a tie
+        if self.games == 0{
+            return 0f64;
+        }
+        (self.wins as f64 + 0.5f64 * self.ties as f64) / self.games as f64
+    }
+
+    pub fn move_time(&self, side : usize) -> f64{
+        //the average seconds per move of the first config, side 0, or of the second, side 1
+        if self.moves[side] == 0 { 0f64 } else { self.seconds[side] / self.moves[side] as f64 }
+    }
+
+    pub fn iterations_per_move(&self, side : usize) -> f64{
+        if self.moves[side] == 0 { 0f64 } else { self.iterations[side] as f64 / self.moves[side] as f64 }
+    }
+}
+
+pub fn arena(root : game_state::GameState, first : &monte_carlo::MctsConfig, second : &monte_carlo::MctsConfig, games : usize) -> ArenaSummary{
+    //games games from root between first and second, which takes the first move in every other game.
+    //each side keeps its own engine for the whole game, advanced past both players' moves
+    let mut summary = ArenaSummary{ games, ..ArenaSummary::default() };
+    for game in 0..games{
+        let first_color = if game % 2 == 0 { root.player } else { root.player.opponent() };
+        let mut engines = [monte_carlo::Mcts::new(root, *first), monte_carlo::Mcts::new(root, *second)];
+        let mut state = root;
+        while !state.is_terminal() && !state.legal_moves().is_empty(){
+            let side = (state.player != first_color) as usize;
+            let result = engines[side].search_result();
+            summary.moves[side] += 1;
+            summary.seconds[side] += result.elapsed;
+            summary.iterations[side] += result.iterations;
+            state = state.place(&result.best_move);
+            for engine in engines.iter_mut(){
+                engine.advance(&result.best_move);
+            }
+        }
+        match state.win(){
+            game_state::End::Victory(color) if color == first_color => summary.wins += 1,
+            game_state::End::Victory(_) => summary.losses += 1,
+            _ => summary.ties += 1
+        }
+    }
+    summary
+}
+
+pub fn vs_random(config : &monte_carlo::MctsConfig, games : usize) -> f64{
+    //the engine's average result against a player picking uniformly among its legal moves, 1 for a win and 0.5 for a tie,
+    //over games games on the standard board. the engine moves first in every other game. a sound config should score
+    //close to 1, so a much lower score points at a broken search rather than a weak one
+    if games == 0{
+        return 0f64;
+    }
+    let mut total = 0f64;
+    for game in 0..games{
+        let engine = if game % 2 == 0 { game_state::Color::White } else { game_state::Color::Black };
+        let mut state = game_state::GameState::new();
+        while !state.is_terminal() && !state.legal_moves().is_empty(){
+            let mv = if state.player == engine{
+                monte_carlo::tree_search_with(state, config)
+            }
+            else{
+                monte_carlo::choose_random(&state.legal_moves())
+            };
+            state = state.place(&mv);
+        }
+        total += monte_carlo::get_result_value(state.win(), engine);
+    }
+    total / games as f64
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+
+    fn fast() -> monte_carlo::MctsConfig{
+        monte_carlo::MctsConfig{
+            budget : monte_carlo::Budget::TimeSeconds(0.005f64),
+            threads : 1,
+            use_book : false,
+            quiet : true,
+            ..monte_carlo::MctsConfig::default()
+        }
+    }
+
+    #[test]
+    fn plays_games_to_the_end(){
+        let root = game_state::GameState::with_size(5, 4).unwrap();
+        let (games, summary) = self_play(root, &fast(), 2);
+        assert_eq!(summary.games, 2);
+        assert_eq!(summary.white_wins + summary.black_wins + summary.ties, 2);
+        assert_eq!(summary.resigned, 0);
+        for game in games.iter(){
+            let end = game.moves.iter().fold(root, |state, mv| state.place(mv));
+            assert!(end.is_terminal());
+            assert_eq!(game.result, end.win());
+        }
+    }
+
+    #[test]
+    fn arena_counts_both_sides(){
+        let root = game_state::GameState::with_size(5, 4).unwrap();
+        let slow = monte_carlo::MctsConfig{ budget : monte_carlo::Budget::Iterations(200), ..fast() };
+        let summary = arena(root, &fast(), &slow, 2);
+        assert_eq!(summary.wins + summary.losses + summary.ties, 2);
+        assert!(summary.moves[0] > 0 && summary.moves[1] > 0);
+        assert!((summary.moves[0] as i64 - summary.moves[1] as i64).abs() <= 2);
+        assert!(summary.iterations_per_move(1) <= 200f64);
+        assert!((0f64..=1f64).contains(&summary.score()));
+        assert_eq!(ArenaSummary::default().move_time(0), 0f64);
+        //settings read from json keep the defaults of whatever they leave out
+        let read : monte_carlo::MctsConfig = serde_json::from_str(r#"{"threads" : 2}"#).unwrap();
+        assert_eq!(read, monte_carlo::MctsConfig{ threads : 2, ..monte_carlo::MctsConfig::default() });
+    }
+
+    #[test]
+    fn resigns_after_enough_hopeless_moves(){
+        //nothing is ever good enough, so white resigns instead of playing its RESIGN_PLIES-th move
+        let config = monte_carlo::MctsConfig{ resign_threshold : Some(2f64), use_tactics : false, ..fast() };
+        let game = play_game(game_state::GameState::new(), &config);
+        assert!(game.resigned);
+        assert_eq!(game.moves.len(), 2 * (RESIGN_PLIES - 1));
+        assert_eq!(game.result, game_state::End::Victory(game_state::Color::Black));
+        let mut summary = SelfPlaySummary::default();
+        summary.record(&game);
+        assert_eq!((summary.black_wins, summary.resigned), (1, 1));
+    }
+
+    #[test]
+    fn exploration_table_warm_starts_searches(){
+        let mut table = ExplorationTable::default();
+        assert_eq!(table.constant_at(0), None);
+        assert_eq!(table.apply(&fast(), 0), fast());
+        table.record(0, 1f64, 0.5f64);
+        table.record(0, 2f64, 1f64);
+        table.record(0, 2f64, 0f64);
+        table.record(1, 0.5f64, 1f64);
+        //both constants average 0.5 at ply 0, so the smaller one is kept
+        assert_eq!(table.constant_at(0), Some(1f64));
+        assert_eq!(table.trials(0)[1], ExplorationTrial{ constant : 2f64, quality : 1f64, searches : 2 });
+        assert_eq!(table.apply(&fast(), 1).exploration, monte_carlo::Exploration::Constant(0.5f64));
+        assert_eq!(ExplorationTable::from_json(&table.to_json()), Ok(table.clone()));
+        assert!(ExplorationTable::from_json("[]").is_err());
+
+        let root = game_state::GameState::with_size(5, 4).unwrap();
+        let game = play_tuned_game(root, &fast(), &mut table);
+        //every ply the game reached has a search recorded, with the table's constant where it had one
+        for ply in 0..game.moves.len(){
+            assert!(!table.trials(ply).is_empty());
+        }
+        assert_eq!(table.trials(1).iter().find(|trial| trial.constant == 0.5f64).unwrap().searches, 2);
+    }
+
+    #[test]
+    fn beats_a_random_player(){
+        assert_eq!(vs_random(&fast(), 0), 0f64);
+        assert!(vs_random(&fast(), 4) >= 0.75f64);
+    }
+}
@@ -0,0 +1,179 @@
+use game_state;
+use monte_carlo;
+use rand::Rng;
+use std::collections::HashMap;
+
+//exact minimax over every continuation, a ground truth for checking the search on boards small enough to solve.
+//positions are remembered once solved, which keeps e.g. the 4 by 4 board to a few hundred thousand of them,
+//but the full 7 by 6 board is far out of reach.
+//pop out games can go on forever, so only drops are searched there and its values are those of the
+//game without pops
+
+fn moves(state : &game_state::GameState) -> Vec<game_state::Move>{
+    state.legal_moves().into_iter().filter(|mv| mv.kind == game_state::MoveKind::Drop).collect()
+}
+
+fn rank(result : game_state::End, color : game_state::Color) -> i32{
+    //how much color likes a result, a win over a tie over a loss
+    match result{
+        game_state::End::Victory(winner) if winner == color => 1,
+        game_state::End::Victory(_) => -1,
+        _ => 0
+    }
+}
+
+fn solve(state : &game_state::GameState, solved : &mut HashMap<game_state::GameState, game_state::End>) -> game_state::End{
+    let result = state.win();
+    if result.is_over(){
+        return result;
+    }
+    if let Some(&result) = solved.get(state){
+        return result;
+    }
+    let player = state.player;
+    let mut best = None;
+    for mv in moves(state){
+        let result = solve(&state.place(&mv), solved);
+        if best.is_none_or(|best| rank(result, player) > rank(best, player)){
+            best = Some(result);
+        }
+        if rank(result, player) == 1{
+            break;
+        }
+    }
+    //a full pop out board with only pops left counts as a tie
+    let result = best.unwrap_or(game_state::End::Tie);
+    solved.insert(*state, result);
+    result
+}
+
+pub fn game_theoretic_value(state : &game_state::GameState) -> game_state::End{
+    //the result of the game from state when both sides play perfectly
+    solve(state, &mut HashMap::new())
+}
+
+pub fn perfect_moves(state : &game_state::GameState) -> Vec<game_state::Move>{
+    //every move that keeps the game theoretic value, empty once the game is over
+    if state.is_terminal(){
+        return Vec::new();
+    }
+    let mut solved = HashMap::new();
+    let value = solve(state, &mut solved);
+    moves(state).into_iter().filter(|mv| solve(&state.place(mv), &mut solved) == value).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchCheck{
+    //how many positions were solved and searched
+    pub positions : usize,
+    //the positions where the search's move is worse than perfect play, throwing away a proven win or
+    //walking into a proven loss, with the move it chose
+    pub mistakes : Vec<(game_state::GameState, game_state::Move)>
+}
+
+impl SearchCheck{
+    pub fn disagreement_rate(&self) -> f64{
+        //the fraction of positions the search got wrong, 0 when nothing was checked
+        if self.positions == 0 { 0f64 } else { self.mistakes.len() as f64 / self.positions as f64 }
+    }
+}
+
+pub fn random_positions<R : Rng>(start : game_state::GameState, count : usize, rng : &mut R) -> Vec<game_state::GameState>{
+    //count unfinished positions a few random drops into a game from start, at most half the board in
+    let mut positions = Vec::new();
+    while positions.len() < count{
+        let plies = rng.gen_range(0, start.width() * start.height() / 2 + 1);
+        let mut state = start;
+        for _ in 0..plies{
+            let drops = moves(&state);
+            if state.is_terminal() || drops.is_empty(){
+                break;
+            }
+            state = state.place(&monte_carlo::choose_random_with(&drops, rng));
+        }
+        if !state.is_terminal(){
+            positions.push(state);
+        }
+    }
+    positions
+}
+
+pub fn check_search(positions : &[game_state::GameState], config : &monte_carlo::MctsConfig) -> SearchCheck{
+    //searches each position with config and compares the move with the solver's verdict, a guard against
+    //strength regressions that a test only asking for a legal move would miss. only standard positions small
+    //enough to solve belong here, finished positions and pop out ones are skipped
+    let mut check = SearchCheck::default();
+    let mut solved = HashMap::new();
+    for state in positions.iter().filter(|state| !state.is_terminal() && state.variant() == game_state::Variant::Standard){
+        let value = solve(state, &mut solved);
+        let best_move = monte_carlo::Mcts::new(*state, *config).search();
+        check.positions += 1;
+        if rank(solve(&state.place(&best_move), &mut solved), state.player) < rank(value, state.player){
+            check.mistakes.push((*state, best_move));
+        }
+    }
+    check
+}
+
+#[cfg(test)]
+mod test{
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    fn play(state : game_state::GameState, moves : &[usize]) -> game_state::GameState{
+        moves.iter().fold(state, |state, &x| state.place(&game_state::Move::new(x, state.player)))
+    }
+
+    fn small() -> game_state::GameState{
+        game_state::GameState::with_size(4, 4).unwrap()
+    }
+
+    #[test]
+    fn the_4_by_4_board_is_a_tie(){
+        assert_eq!(game_theoretic_value(&small()), game_state::End::Tie);
+        assert_eq!(perfect_moves(&small()).len(), 4);
+    }
+
+    #[test]
+    fn finds_forced_wins(){
+        //white to move with three in a row on the bottom
+        let state = play(small(), &[0, 0, 1, 1, 2, 2]);
+        assert_eq!(game_theoretic_value(&state), game_state::End::Victory(game_state::Color::White));
+        assert_eq!(perfect_moves(&state), vec![game_state::Move::new(3, game_state::Color::White)]);
+        //black, to move, has to block the bottom row
+        let state = play(small(), &[0, 0, 1, 1, 2]);
+        assert_eq!(perfect_moves(&state), vec![game_state::Move::new(3, game_state::Color::Black)]);
+    }
+
+    #[test]
+    fn finished_games_keep_their_result(){
+        let won = play(small(), &[0, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(game_theoretic_value(&won), game_state::End::Victory(game_state::Color::White));
+        assert!(perfect_moves(&won).is_empty());
+    }
+
+    #[test]
+    fn search_agrees_with_the_solver(){
+        let mut rng = StdRng::from_seed(&[199]);
+        let positions = random_positions(small(), 6, &mut rng);
+        assert_eq!(positions.len(), 6);
+        assert!(positions.iter().all(|state| !state.is_terminal()));
+        let config = monte_carlo::MctsConfig{ budget : monte_carlo::Budget::Iterations(1000), threads : 1, use_book : false, quiet : true,
+            selection_seed : Some(1), rollout_seed : Some(2), ..monte_carlo::MctsConfig::default() };
+        let check = check_search(&positions, &config);
+        assert_eq!(check.positions, 6);
+        assert!(check.disagreement_rate() <= 1f64 / 3f64, "wrong in {:?}", check.mistakes);
+        assert_eq!(SearchCheck::default().disagreement_rate(), 0f64);
+    }
+
+    #[test]
+    fn search_finds_a_perfect_move(){
+        //two ways to three in a row for white, only one of them leaves both ends open
+        let state = play(game_state::GameState::with_size(5, 4).unwrap(), &[1, 1, 2, 2]);
+        let perfect = perfect_moves(&state);
+        assert_eq!(game_theoretic_value(&state), game_state::End::Victory(game_state::Color::White));
+        let config = monte_carlo::MctsConfig{ budget : monte_carlo::Budget::TimeSeconds(0.5f64), quiet : true, ..monte_carlo::MctsConfig::default() };
+        let best_move = monte_carlo::Mcts::new(state, config).search();
+        assert!(perfect.contains(&best_move), "{:?} is not one of {:?}", best_move, perfect);
+    }
+}